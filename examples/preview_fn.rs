@@ -0,0 +1,24 @@
+extern crate skim;
+use skim::{PreviewCallback, Skim, SkimOptionsBuilder};
+use std::io::Cursor;
+
+pub fn main() {
+    // preview in process, no command is spawned
+    let options = SkimOptionsBuilder::default()
+        .preview_fn(Some(PreviewCallback::new(|item| {
+            let text = item.get_output_text();
+            format!("{}\nlength: {}", text, text.len())
+        })))
+        .build()
+        .unwrap();
+
+    let input = "aaaaa\nbbbb\nccc".to_string();
+
+    let selected_items = Skim::run_with(&options, Some(Box::new(Cursor::new(input))))
+        .map(|out| out.selected_items)
+        .unwrap_or_else(|| Vec::new());
+
+    for item in selected_items.iter() {
+        print!("{}: {}{}", item.get_index(), item.get_output_text(), "\n");
+    }
+}
@@ -0,0 +1,26 @@
+extern crate skim;
+use skim::{Skim, SkimOptionsBuilder};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+pub fn main() {
+    let options = SkimOptionsBuilder::default().multi(true).build().unwrap();
+
+    // the items are produced over time, skim matches them as they come
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        for idx in 0..100 {
+            let _ = tx.send(format!("item {}", idx));
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+
+    let selected_items = Skim::run_with_receiver(&options, rx)
+        .map(|out| out.selected_items)
+        .unwrap_or_else(|| Vec::new());
+
+    for item in selected_items.iter() {
+        print!("{}: {}{}", item.get_index(), item.get_output_text(), "\n");
+    }
+}
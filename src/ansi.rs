@@ -12,6 +12,7 @@ use vte::Perform;
 pub struct ANSIParser {
     partial_str: String,
     last_attr: Attr,
+    strip_unsupported: bool,
 
     stripped: String,
     fragments: Vec<(Attr, Cow<'static, str>)>,
@@ -22,6 +23,7 @@ impl Default for ANSIParser {
         ANSIParser {
             partial_str: String::new(),
             last_attr: Attr::default(),
+            strip_unsupported: false,
 
             stripped: String::new(),
             fragments: Vec::new(),
@@ -128,14 +130,25 @@ impl Perform for ANSIParser {
         self.attr_change(attr);
     }
 
-    fn esc_dispatch(&mut self, _params: &[i64], _intermediates: &[u8], _ignore: bool, _byte: u8) {
-        // ESC characters are replaced with \[
+    fn esc_dispatch(&mut self, _params: &[i64], _intermediates: &[u8], _ignore: bool, byte: u8) {
+        // ESC \ is the string terminator of OSC sequences (e.g. OSC 8 hyperlinks), which are dropped
+        // other ESC characters are replaced with \[
+        if self.strip_unsupported || byte == b'\\' {
+            return;
+        }
         self.partial_str.push('"');
         self.partial_str.push('[');
     }
 }
 
 impl ANSIParser {
+    /// Drop the escape sequences that could not be interpreted instead of leaving a mark for them.
+    /// Useful for command output that may move the cursor around, e.g. in the preview window.
+    pub fn strip_unsupported(mut self, strip: bool) -> Self {
+        self.strip_unsupported = strip;
+        self
+    }
+
     /// save the partial_str into fragments with current attr
     fn save_str(&mut self) {
         if self.partial_str.is_empty() {
@@ -301,4 +314,35 @@ mod tests {
 
         assert_eq!("ab", ansistring.into_inner())
     }
+
+    #[test]
+    fn test_multi_line_with_unsupported_escapes() {
+        let mut parser = ANSIParser::default().strip_unsupported(true);
+        // color set on the first line, cursor movement & save cursor in between
+        let first = parser.parse_ansi("\x1B[31ma\x1B[2Ab\x1B7");
+        let second = parser.parse_ansi("c\x1B[0md");
+
+        let red = Attr {
+            fg: Color::AnsiValue(1),
+            ..Attr::default()
+        };
+
+        assert_eq!("ab", first.get_stripped());
+        assert_eq!(vec![('a', red), ('b', red)], first.iter().collect::<Vec<_>>());
+
+        assert_eq!("cd", second.get_stripped());
+        assert_eq!(
+            vec![('c', red), ('d', Attr::default())],
+            second.iter().collect::<Vec<_>>()
+        );
+    }
+
+    // the ESC \ that ends an OSC sequence doesn't leak into the text
+    #[test]
+    fn test_osc_string_terminator() {
+        let mut parser = ANSIParser::default();
+        let line = parser
+            .parse_ansi("see \x1B]8;;http://a.b/c\x1B\\link\x1B]8;;\x1B\\ and \x1B]8;;file:///tmp\x07here\x1B]8;;\x07");
+        assert_eq!("see link and here", line.get_stripped());
+    }
 }
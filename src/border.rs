@@ -0,0 +1,118 @@
+///! the boxes drawn around the preview window and the whole finder (`--border`)
+use tuikit::prelude::*;
+
+/// border style of a window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    /// a single line between the preview and the main window (the default of the preview)
+    Line,
+    Sharp,
+    Rounded,
+    Double,
+    None,
+}
+
+impl BorderStyle {
+    /// the style of a box, e.g. `rounded`
+    pub fn parse(style: &str) -> Option<BorderStyle> {
+        match style.to_lowercase().as_str() {
+            "sharp" => Some(BorderStyle::Sharp),
+            "rounded" => Some(BorderStyle::Rounded),
+            "double" => Some(BorderStyle::Double),
+            "none" => Some(BorderStyle::None),
+            _ => None,
+        }
+    }
+
+    /// the style of `--border[=STYLE]`, rounded if the style is not given or not known
+    pub fn from_option(style: Option<&str>) -> BorderStyle {
+        match style {
+            Some(style) => BorderStyle::parse(style).unwrap_or(BorderStyle::Rounded),
+            None => BorderStyle::None,
+        }
+    }
+
+    /// the rows or columns the box takes on each side
+    pub fn thickness(self) -> usize {
+        if self.box_chars().is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    // (horizontal, vertical, top-left, top-right, bottom-left, bottom-right) of a box
+    fn box_chars(self) -> Option<(char, char, char, char, char, char)> {
+        match self {
+            BorderStyle::Sharp => Some(('─', '│', '┌', '┐', '└', '┘')),
+            BorderStyle::Rounded => Some(('─', '│', '╭', '╮', '╰', '╯')),
+            BorderStyle::Double => Some(('═', '║', '╔', '╗', '╚', '╝')),
+            BorderStyle::Line | BorderStyle::None => None,
+        }
+    }
+}
+
+/// The content surrounded by a box of the border style.
+/// The `Line` border is left to the window drawing the content.
+pub struct BorderBox<'a> {
+    content: &'a dyn Draw,
+    style: BorderStyle,
+    attr: Attr,
+}
+
+impl<'a> BorderBox<'a> {
+    pub fn new(content: &'a dyn Draw, style: BorderStyle, attr: Attr) -> Self {
+        Self { content, style, attr }
+    }
+}
+
+impl<'a> Draw for BorderBox<'a> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
+        let (width, height) = canvas.size()?;
+        let (horizontal, vertical, top_left, top_right, bottom_left, bottom_right) = match self.style.box_chars() {
+            Some(chars) if width >= 2 && height >= 2 => chars,
+            _ => return self.content.draw(canvas),
+        };
+
+        Win::new(self.content)
+            .padding_top(1)
+            .padding_right(1)
+            .padding_bottom(1)
+            .padding_left(1)
+            .draw(canvas)?;
+
+        for col in 1..width - 1 {
+            canvas.put_char_with_attr(0, col, horizontal, self.attr)?;
+            canvas.put_char_with_attr(height - 1, col, horizontal, self.attr)?;
+        }
+        for row in 1..height - 1 {
+            canvas.put_char_with_attr(row, 0, vertical, self.attr)?;
+            canvas.put_char_with_attr(row, width - 1, vertical, self.attr)?;
+        }
+        canvas.put_char_with_attr(0, 0, top_left, self.attr)?;
+        canvas.put_char_with_attr(0, width - 1, top_right, self.attr)?;
+        canvas.put_char_with_attr(height - 1, 0, bottom_left, self.attr)?;
+        canvas.put_char_with_attr(height - 1, width - 1, bottom_right, self.attr)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_option() {
+        assert_eq!(BorderStyle::from_option(None), BorderStyle::None);
+        assert_eq!(BorderStyle::from_option(Some("sharp")), BorderStyle::Sharp);
+        assert_eq!(BorderStyle::from_option(Some("DOUBLE")), BorderStyle::Double);
+        assert_eq!(BorderStyle::from_option(Some("none")), BorderStyle::None);
+        // `line` only separates the preview
+        assert_eq!(BorderStyle::from_option(Some("line")), BorderStyle::Rounded);
+
+        assert_eq!(BorderStyle::Rounded.thickness(), 1);
+        assert_eq!(BorderStyle::Line.thickness(), 0);
+        assert_eq!(BorderStyle::None.thickness(), 0);
+    }
+}
@@ -3,7 +3,6 @@ use crate::item::{Item, MatchedItem, MatchedRange, Rank};
 use crate::score;
 use regex::Regex;
 use std::sync::Arc;
-use std::thread;
 
 lazy_static! {
     static ref RE_AND: Regex = Regex::new(r"([^ |]+( +\| +[^ |]*)+)|( +)").unwrap();
@@ -14,8 +13,11 @@ lazy_static! {
 enum Algorithm {
     PrefixExact,
     SuffixExact,
+    // both prefix and suffix, i.e. the whole text
+    PrefixSuffixExact,
     Exact,
     InverseExact,
+    InversePrefixExact,
     InverseSuffixExact,
 }
 
@@ -26,6 +28,46 @@ pub enum MatcherMode {
     Exact,
 }
 
+impl MatcherMode {
+    /// the mode `rotate-mode` switches to: fuzzy -> exact -> regex -> fuzzy
+    pub fn next(self) -> Self {
+        match self {
+            MatcherMode::Fuzzy => MatcherMode::Exact,
+            MatcherMode::Exact => MatcherMode::Regex,
+            MatcherMode::Regex => MatcherMode::Fuzzy,
+        }
+    }
+
+    /// the indicator shown in the status line, empty for the fuzzy mode
+    pub fn label(self) -> &'static str {
+        match self {
+            MatcherMode::Fuzzy => "",
+            MatcherMode::Exact => "EX",
+            MatcherMode::Regex => "RE",
+        }
+    }
+}
+
+/// The algorithm used to score fuzzy matches
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum FuzzyAlgorithm {
+    /// fast, greedy matching (fzf's v1)
+    V1,
+    /// slower, but finds the best scored alignment (fzf's v2)
+    #[default]
+    V2,
+}
+
+impl FuzzyAlgorithm {
+    pub fn from_str(algo: &str) -> Option<FuzzyAlgorithm> {
+        match algo.to_lowercase().as_str() {
+            "v1" => Some(FuzzyAlgorithm::V1),
+            "v2" | "" => Some(FuzzyAlgorithm::V2),
+            _ => None,
+        }
+    }
+}
+
 // A match engine will execute the matching algorithm
 pub trait MatchEngine: Sync + Send {
     fn match_item(&self, item: Arc<Item>) -> Option<MatchedItem>;
@@ -38,6 +80,7 @@ fn build_rank(score: i64, index: i64, begin: i64, end: i64) -> Rank {
         index,
         begin,
         end,
+        frecency: 0,
     }
 }
 
@@ -104,12 +147,14 @@ impl MatchEngine for RegexEngine {
 #[derive(Debug)]
 struct FuzzyEngine {
     query: String,
+    algorithm: FuzzyAlgorithm,
 }
 
 impl FuzzyEngine {
-    pub fn builder(query: &str) -> Self {
+    pub fn builder(query: &str, algorithm: FuzzyAlgorithm) -> Self {
         FuzzyEngine {
             query: query.to_string(),
+            algorithm,
         }
     }
 
@@ -123,8 +168,12 @@ impl MatchEngine for FuzzyEngine {
         // iterate over all matching fields:
         let mut matched_result = None;
         // thread::sleep_ms(100); // FuzzzyModeの算出?
+        let fuzzy_match = match self.algorithm {
+            FuzzyAlgorithm::V1 => score::fuzzy_match_v1,
+            FuzzyAlgorithm::V2 => score::fuzzy_match,
+        };
         for &(start, end) in item.get_matching_ranges() {
-            matched_result = score::fuzzy_match(&item.get_text()[start..end], &self.query).map(|(s, vec)| {
+            matched_result = fuzzy_match(&item.get_text()[start..end], &self.query).map(|(s, vec)| {
                 if start != 0 {
                     let start_char = &item.get_text()[..start].chars().count();
                     (s, vec.iter().map(|x| x + start_char).collect())
@@ -254,10 +303,24 @@ impl MatchEngine for ExactEngine {
                     _ => None,
                 }),
             ),
+            Algorithm::PrefixSuffixExact => self.match_item_exact(
+                item,
+                Box::new(|matched_result, len| match *matched_result {
+                    Some(((s, e), _)) if s == 0 && e == len => Some((s, e)),
+                    _ => None,
+                }),
+            ),
+            Algorithm::InversePrefixExact => self.match_item_exact(
+                item,
+                Box::new(|matched_result, _| match *matched_result {
+                    Some(((0, _), _)) => None,
+                    _ => Some((0, 0)),
+                }),
+            ),
             Algorithm::InverseSuffixExact => self.match_item_exact(
                 item,
                 Box::new(|matched_result, len| match *matched_result {
-                    Some((_, (_, e))) if e != len => None,
+                    Some((_, (_, e))) if e == len => None,
                     _ => Some((0, 0)),
                 }),
             ),
@@ -276,10 +339,13 @@ struct OrEngine {
 }
 
 impl OrEngine {
-    pub fn builder(query: &str, mode: MatcherMode) -> Self {
+    pub fn builder(query: &str, mode: MatcherMode, algorithm: FuzzyAlgorithm) -> Self {
         // mock
         OrEngine {
-            engines: RE_OR.split(query).map(|q| EngineFactory::build(q, mode)).collect(),
+            engines: RE_OR
+                .split(query)
+                .map(|q| EngineFactory::build(q, mode, algorithm))
+                .collect(),
         }
     }
 
@@ -315,7 +381,7 @@ struct AndEngine {
 }
 
 impl AndEngine {
-    pub fn builder(query: &str, mode: MatcherMode) -> Self {
+    pub fn builder(query: &str, mode: MatcherMode, algorithm: FuzzyAlgorithm) -> Self {
         let query_trim = query.trim_matches(|c| c == ' ' || c == '|');
         let mut engines = vec![];
         let mut last = 0;
@@ -323,18 +389,20 @@ impl AndEngine {
             let (start, end) = (mat.start(), mat.end());
             let term = &query_trim[last..start].trim_matches(|c| c == ' ' || c == '|');
             if !term.is_empty() {
-                engines.push(EngineFactory::build(term, mode));
+                engines.push(EngineFactory::build(term, mode, algorithm));
             }
 
             if !mat.as_str().trim().is_empty() {
-                engines.push(Box::new(OrEngine::builder(mat.as_str().trim(), mode).build()));
+                engines.push(Box::new(
+                    OrEngine::builder(mat.as_str().trim(), mode, algorithm).build(),
+                ));
             }
             last = end;
         }
 
         let term = &query_trim[last..].trim_matches(|c| c == ' ' || c == '|');
         if !term.is_empty() {
-            engines.push(EngineFactory::build(term, mode));
+            engines.push(EngineFactory::build(term, mode, algorithm));
         }
 
         AndEngine { engines }
@@ -345,7 +413,9 @@ impl AndEngine {
     }
 
     fn merge_matched_items(&self, items: Vec<MatchedItem>) -> MatchedItem {
-        let rank = items[0].rank;
+        // every term contributes to the score
+        let mut rank = items[0].rank;
+        rank.score = items.iter().map(|item| item.rank.score).sum();
         let item = Arc::clone(&items[0].item);
         let mut ranges = vec![];
         for item in items {
@@ -396,66 +466,299 @@ impl MatchEngine for AndEngine {
 //------------------------------------------------------------------------------
 pub struct EngineFactory {}
 impl EngineFactory {
-    pub fn build(query: &str, mode: MatcherMode) -> Box<dyn MatchEngine> {
+    pub fn build(query: &str, mode: MatcherMode, algorithm: FuzzyAlgorithm) -> Box<dyn MatchEngine> {
         match mode {
             MatcherMode::Regex => Box::new(RegexEngine::builder(query).build()),
             MatcherMode::Fuzzy | MatcherMode::Exact => {
                 if query.contains(' ') {
-                    Box::new(AndEngine::builder(query, mode).build())
+                    Box::new(AndEngine::builder(query, mode, algorithm).build())
                 } else {
-                    EngineFactory::build_single(query, mode)
+                    EngineFactory::build_single(query, mode, algorithm)
                 }
             }
         }
     }
 
-    fn build_single(query: &str, mode: MatcherMode) -> Box<dyn MatchEngine> {
-        if query.starts_with('\'') {
-            if mode == MatcherMode::Exact {
-                Box::new(FuzzyEngine::builder(&query[1..]).build())
+    /// the whole query is a single term, without the extended search syntax (`--literal`)
+    pub fn build_literal(query: &str, mode: MatcherMode, algorithm: FuzzyAlgorithm) -> Box<dyn MatchEngine> {
+        match mode {
+            MatcherMode::Regex => Box::new(RegexEngine::builder(query).build()),
+            MatcherMode::Fuzzy => Box::new(FuzzyEngine::builder(query, algorithm).build()),
+            MatcherMode::Exact => Box::new(ExactEngine::builder(query, Algorithm::Exact).build()),
+        }
+    }
+
+    fn build_single(query: &str, mode: MatcherMode, algorithm: FuzzyAlgorithm) -> Box<dyn MatchEngine> {
+        let exact = |query: &str, algo: Algorithm| -> Box<dyn MatchEngine> {
+            Box::new(ExactEngine::builder(query, algo).build())
+        };
+
+        if let Some(query) = query.strip_prefix('!') {
+            // inverse terms are always exact, `!'abc` is the same as `!abc`
+            let query = query.strip_prefix('\'').unwrap_or(query);
+            if let Some(query) = query.strip_prefix('^') {
+                exact(query, Algorithm::InversePrefixExact)
+            } else if let Some(query) = query.strip_suffix('$') {
+                exact(query, Algorithm::InverseSuffixExact)
             } else {
-                Box::new(ExactEngine::builder(&query[1..], Algorithm::Exact).build())
+                exact(query, Algorithm::InverseExact)
             }
-        } else if query.starts_with('^') {
-            Box::new(ExactEngine::builder(&query[1..], Algorithm::PrefixExact).build())
-        } else if query.starts_with('!') {
-            if query.ends_with('$') {
-                Box::new(ExactEngine::builder(&query[1..(query.len() - 1)], Algorithm::InverseSuffixExact).build())
+        } else if let Some(query) = query.strip_prefix('\'') {
+            if mode == MatcherMode::Exact {
+                Box::new(FuzzyEngine::builder(query, algorithm).build())
             } else {
-                Box::new(ExactEngine::builder(&query[1..], Algorithm::InverseExact).build())
+                exact(query, Algorithm::Exact)
+            }
+        } else if let Some(query) = query.strip_prefix('^') {
+            match query.strip_suffix('$') {
+                Some(query) => exact(query, Algorithm::PrefixSuffixExact),
+                None => exact(query, Algorithm::PrefixExact),
             }
-        } else if query.ends_with('$') {
-            Box::new(ExactEngine::builder(&query[..(query.len() - 1)], Algorithm::SuffixExact).build())
+        } else if let Some(query) = query.strip_suffix('$') {
+            exact(query, Algorithm::SuffixExact)
         } else if mode == MatcherMode::Exact {
-            Box::new(ExactEngine::builder(query, Algorithm::Exact).build())
+            exact(query, Algorithm::Exact)
         } else {
-            Box::new(FuzzyEngine::builder(query).build())
+            Box::new(FuzzyEngine::builder(query, algorithm).build())
         }
     }
 }
 
+/// Whether every item matching `query` also matches `last_query`, so the items matched by
+/// `last_query` are enough to match `query` against.
+///
+/// That holds when the query is only extended, except for the syntax that widens the match: regex,
+/// OR (`|`), inverse (`!`) terms, and a suffix term (`$`) that is typed on.
+pub fn query_narrows(last_query: &str, query: &str, mode: MatcherMode) -> bool {
+    if mode == MatcherMode::Regex || query.len() <= last_query.len() || !query.starts_with(last_query) {
+        return false;
+    }
+
+    let appended = &query[last_query.len()..];
+    let extends_suffix_term = last_query.ends_with('$') && !appended.starts_with(' ');
+    !extends_suffix_term && !query.contains(&['|', '!'][..])
+}
+
 #[cfg(test)]
 mod test {
-    use super::{EngineFactory, MatcherMode};
+    use super::{query_narrows, EngineFactory, FuzzyAlgorithm, MatcherMode};
+    use crate::field::FieldRange;
+    use crate::item::Item;
+    use regex::Regex;
+    use std::sync::Arc;
 
     #[test]
     fn test_engine_factory() {
-        let x1 = EngineFactory::build("'abc | def ^gh ij | kl mn", MatcherMode::Fuzzy);
+        let x1 = EngineFactory::build("'abc | def ^gh ij | kl mn", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
         assert_eq!(
             x1.display(),
             "(And: (Or: (Exact: abc), (Fuzzy: def)), (PrefixExact: gh), (Or: (Fuzzy: ij), (Fuzzy: kl)), (Fuzzy: mn))"
         );
 
-        let x3 = EngineFactory::build("'abc | def ^gh ij | kl mn", MatcherMode::Regex);
+        let x3 = EngineFactory::build("'abc | def ^gh ij | kl mn", MatcherMode::Regex, FuzzyAlgorithm::V2);
         assert_eq!(x3.display(), "(Regex: 'abc | def ^gh ij | kl mn)");
 
-        let x = EngineFactory::build("abc ", MatcherMode::Fuzzy);
+        let x = EngineFactory::build("abc ", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
         assert_eq!(x.display(), "(And: (Fuzzy: abc))");
 
-        let x = EngineFactory::build("abc def", MatcherMode::Fuzzy);
+        let x = EngineFactory::build("abc def", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
         assert_eq!(x.display(), "(And: (Fuzzy: abc), (Fuzzy: def))");
 
-        let x = EngineFactory::build("abc | def", MatcherMode::Fuzzy);
+        let x = EngineFactory::build("abc | def", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
         assert_eq!(x.display(), "(And: (Or: (Fuzzy: abc), (Fuzzy: def)))");
     }
+
+    #[test]
+    fn test_query_operators() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let cases = [
+            // (query, text, matched)
+            ("abc", "axbxc", true),
+            ("'abc", "axbxc", false),
+            ("'abc", "xabcx", true),
+            ("^abc", "abcx", true),
+            ("^abc", "xabc", false),
+            ("abc$", "xabc", true),
+            ("abc$", "abcx", false),
+            ("^abc$", "abc", true),
+            ("^abc$", "abcabc", false),
+            ("!abc", "xyz", true),
+            ("!abc", "xabcx", false),
+            ("!'abc", "xabcx", false),
+            ("!^abc", "xabc", true),
+            ("!^abc", "abcx", false),
+            ("!abc$", "abcx", true),
+            ("!abc$", "xabc", false),
+            // terms are AND-ed
+            ("ab cd", "abxcd", true),
+            ("ab cd", "abxxx", false),
+            ("^ab !cd", "abxx", true),
+            ("^ab !cd", "abcd", false),
+            // `|` ORs the terms around it
+            ("^ab | cd$", "abxx", true),
+            ("^ab | cd$", "xxcd", true),
+            ("^ab | cd$", "xabcdx", false),
+            ("x ^ab | cd$", "xxcd", true),
+            ("y ^ab | cd$", "xxcd", false),
+        ];
+
+        for &(query, text, matched) in cases.iter() {
+            let item = Arc::new(Item::new(text.into(), false, &[], &[], &delimiter, (0, 0)));
+            let engine = EngineFactory::build(query, MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+            assert_eq!(
+                engine.match_item(item).is_some(),
+                matched,
+                "query: {:?}, text: {:?}, engine: {}",
+                query,
+                text,
+                engine.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_and_engine_combines_scores() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let item = |text: &str| Arc::new(Item::new(text.into(), false, &[], &[], &delimiter, (0, 0)));
+        let engine = EngineFactory::build("ab cd", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+
+        // both items match `ab` equally well, `cd` makes the difference
+        let close = engine.match_item(item("ab cd")).unwrap();
+        let scattered = engine.match_item(item("ab cxxxd")).unwrap();
+        assert!(close.rank.score < scattered.rank.score);
+    }
+
+    #[test]
+    fn test_literal() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let item = |text: &str| Arc::new(Item::new(text.into(), false, &[], &[], &delimiter, (0, 0)));
+
+        // `!a` negates by default
+        let engine = EngineFactory::build("!a", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+        assert!(engine.match_item(item("x!a")).is_none());
+        assert!(engine.match_item(item("b")).is_some());
+
+        let engine = EngineFactory::build_literal("!a", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+        assert_eq!(engine.display(), "(Fuzzy: !a)");
+        assert!(engine.match_item(item("x!a")).is_some());
+        assert!(engine.match_item(item("b")).is_none());
+
+        let engine = EngineFactory::build_literal("^a | b", MatcherMode::Exact, FuzzyAlgorithm::V2);
+        assert!(engine.match_item(item("x^a | bx")).is_some());
+        assert!(engine.match_item(item("ab")).is_none());
+    }
+
+    #[test]
+    fn test_rank_keeps_match_begin() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let item = |text: &str| Arc::new(Item::new(text.into(), false, &[], &[], &delimiter, (0, 0)));
+
+        for &mode in [MatcherMode::Exact, MatcherMode::Regex].iter() {
+            let engine = EngineFactory::build("ab", mode, FuzzyAlgorithm::V2);
+            let early = engine.match_item(item("xab")).unwrap();
+            let late = engine.match_item(item("xxab")).unwrap();
+            assert_eq!(early.rank.score, late.rank.score);
+            assert!(early.rank.begin < late.rank.begin);
+        }
+    }
+
+    #[test]
+    fn test_matched_chars_of_transformed_text() {
+        let delimiter = Regex::new(",").unwrap();
+        // displayed as "éabc"
+        let item = Arc::new(Item::new(
+            "zzzz,éabc".into(),
+            false,
+            &[FieldRange::Single(2)],
+            &[],
+            &delimiter,
+            (0, 0),
+        ));
+
+        let exact = EngineFactory::build("abc", MatcherMode::Exact, FuzzyAlgorithm::V2);
+        let matched = exact.match_item(item.clone()).unwrap();
+        assert_eq!(matched.to_chars(), Some(vec![1, 2, 3]));
+
+        let fuzzy = EngineFactory::build("éc", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+        let matched = fuzzy.match_item(item).unwrap();
+        assert_eq!(matched.to_chars(), Some(vec![0, 3]));
+    }
+
+    #[test]
+    fn test_match_nth_fields() {
+        // `--nth 2`: only the second column is matched, e.g. in the filter mode
+        let delimiter = Regex::new(",").unwrap();
+        let item = |text: &str| {
+            Arc::new(Item::new(
+                text.into(),
+                false,
+                &[],
+                &[FieldRange::Single(2)],
+                &delimiter,
+                (0, 0),
+            ))
+        };
+
+        let engine = EngineFactory::build("ab", MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+        assert!(engine.match_item(item("ab,xy")).is_none());
+        let matched = engine.match_item(item("xy,ab")).unwrap();
+        assert_eq!(matched.to_chars(), Some(vec![3, 4]));
+    }
+
+    // cargo test --release -- --ignored --nocapture bench_fuzzy_algorithms
+    #[test]
+    #[ignore]
+    fn bench_fuzzy_algorithms() {
+        use std::time::Instant;
+
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let items: Vec<_> = (0..1_000_000)
+            .map(|idx| {
+                let line = format!("src/module_{}/sub_{}/file_{}.rs", idx % 97, idx % 1013, idx);
+                Arc::new(Item::new(line.into(), false, &[], &[], &delimiter, (0, idx)))
+            })
+            .collect();
+
+        let mut matched = vec![];
+        for &algorithm in &[FuzzyAlgorithm::V1, FuzzyAlgorithm::V2] {
+            let engine = EngineFactory::build("mod1sub2fil", MatcherMode::Fuzzy, algorithm);
+            let start = Instant::now();
+            let count = items
+                .iter()
+                .filter(|item| engine.match_item((*item).clone()).is_some())
+                .count();
+            println!("{:?}: {} matched in {:?}", algorithm, count, start.elapsed());
+            matched.push(count);
+        }
+
+        // both accept the same items, only the scores differ
+        assert_eq!(matched[0], matched[1]);
+    }
+
+    #[test]
+    fn test_rotate_mode() {
+        let mut mode = MatcherMode::Fuzzy;
+        let mut labels = Vec::new();
+        for _ in 0..4 {
+            mode = mode.next();
+            labels.push(mode.label());
+        }
+        assert_eq!(labels, vec!["EX", "RE", "", "EX"]);
+    }
+
+    #[test]
+    fn test_query_narrows() {
+        assert!(query_narrows("", "a", MatcherMode::Fuzzy));
+        assert!(query_narrows("ab", "abc", MatcherMode::Fuzzy));
+        assert!(query_narrows("ab", "ab c$", MatcherMode::Exact));
+        assert!(query_narrows("a$", "a$ b", MatcherMode::Fuzzy));
+
+        assert!(!query_narrows("ab", "ab", MatcherMode::Fuzzy));
+        assert!(!query_narrows("abc", "ab", MatcherMode::Fuzzy));
+        assert!(!query_narrows("ab", "ac", MatcherMode::Fuzzy));
+        assert!(!query_narrows("ab", "abc", MatcherMode::Regex));
+        assert!(!query_narrows("a", "a | b", MatcherMode::Fuzzy));
+        assert!(!query_narrows("!a", "!ab", MatcherMode::Fuzzy));
+        assert!(!query_narrows("a$", "a$b", MatcherMode::Fuzzy));
+    }
 }
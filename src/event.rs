@@ -11,14 +11,19 @@ pub type EventSender = Sender<(Event, EventArg)>;
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Event {
     EvInputKey,
+    EvInputMouse,
     EvInputInvalid,
 
     EvHeartBeat,
 
     EvPreviewRequest,
 
+    // the actions bound to `click-header` were run
+    EvClickHeaderDone,
+
     // user bind actions
     EvActAbort,
+    EvActAbortPrintQuery,
     EvActAccept,
     EvActAddChar,
     EvActAppendAndSelect,
@@ -28,7 +33,10 @@ pub enum Event {
     EvActBackwardWord,
     EvActBeginningOfLine,
     EvActCancel,
+    EvActClearQuery,
     EvActClearScreen,
+    EvActClearSelection,
+    EvActCyclePreview,
     EvActDeleteChar,
     EvActDeleteCharEOF,
     EvActDeselectAll,
@@ -36,16 +44,24 @@ pub enum Event {
     EvActEndOfLine,
     EvActExecute,
     EvActExecuteSilent,
+    EvActFirst,
     EvActForwardChar,
     EvActForwardWord,
+    EvActHalfPageDown,
+    EvActHalfPageUp,
     EvActIfQueryEmpty,
     EvActIfQueryNotEmpty,
+    EvActIfSingleMatch,
     EvActIgnore,
+    EvActJump,
+    EvActJumpAccept,
     EvActKillLine,
     EvActKillWord,
+    EvActLast,
     EvActNextHistory,
     EvActPageDown,
     EvActPageUp,
+    EvActPos,
     EvActPreviewUp,
     EvActPreviewDown,
     EvActPreviewLeft,
@@ -54,10 +70,12 @@ pub enum Event {
     EvActPreviewPageDown,
     EvActPreviousHistory,
     EvActRedraw,
+    EvActReload,
     EvActRotateMode,
     EvActScrollLeft,
     EvActScrollRight,
     EvActSelectAll,
+    EvActSwapQuery,
     EvActToggle,
     EvActToggleAll,
     EvActToggleIn,
@@ -91,6 +109,7 @@ pub trait EventHandler {
 pub fn parse_action(action: &str) -> Option<Event> {
     match action {
         "abort"                =>   Some(Event::EvActAbort),
+        "abort-print-query"    =>   Some(Event::EvActAbortPrintQuery),
         "accept"               =>   Some(Event::EvActAccept),
         "append-and-select"    =>   Some(Event::EvActAppendAndSelect),
         "backward-char"        =>   Some(Event::EvActBackwardChar),
@@ -99,7 +118,10 @@ pub fn parse_action(action: &str) -> Option<Event> {
         "backward-word"        =>   Some(Event::EvActBackwardWord),
         "beginning-of-line"    =>   Some(Event::EvActBeginningOfLine),
         "cancel"               =>   Some(Event::EvActCancel),
+        "clear-query"          =>   Some(Event::EvActClearQuery),
         "clear-screen"         =>   Some(Event::EvActClearScreen),
+        "clear-selection"      =>   Some(Event::EvActClearSelection),
+        "cycle-preview"        =>   Some(Event::EvActCyclePreview),
         "delete-char"          =>   Some(Event::EvActDeleteChar),
         "delete-charEOF"       =>   Some(Event::EvActDeleteCharEOF),
         "deselect-all"         =>   Some(Event::EvActDeselectAll),
@@ -107,16 +129,24 @@ pub fn parse_action(action: &str) -> Option<Event> {
         "end-of-line"          =>   Some(Event::EvActEndOfLine),
         "execute"              =>   Some(Event::EvActExecute),
         "execute-silent"       =>   Some(Event::EvActExecuteSilent),
+        "first"                =>   Some(Event::EvActFirst),
         "forward-char"         =>   Some(Event::EvActForwardChar),
         "forward-word"         =>   Some(Event::EvActForwardWord),
+        "half-page-down"       =>   Some(Event::EvActHalfPageDown),
+        "half-page-up"         =>   Some(Event::EvActHalfPageUp),
         "if-query-empty"       =>   Some(Event::EvActIfQueryEmpty),
         "if-query-not-empty"   =>   Some(Event::EvActIfQueryNotEmpty),
+        "if-single-match"      =>   Some(Event::EvActIfSingleMatch),
         "ignore"               =>   Some(Event::EvActIgnore),
+        "jump"                 =>   Some(Event::EvActJump),
+        "jump-accept"          =>   Some(Event::EvActJumpAccept),
         "kill-line"            =>   Some(Event::EvActKillLine),
         "kill-word"            =>   Some(Event::EvActKillWord),
+        "last"                 =>   Some(Event::EvActLast),
         "next-history"         =>   Some(Event::EvActNextHistory),
         "page-down"            =>   Some(Event::EvActPageDown),
         "page-up"              =>   Some(Event::EvActPageUp),
+        "pos"                  =>   Some(Event::EvActPos),
         "preview-up"           =>   Some(Event::EvActPreviewUp),
         "preview-down"         =>   Some(Event::EvActPreviewDown),
         "preview-left"         =>   Some(Event::EvActPreviewLeft),
@@ -124,9 +154,12 @@ pub fn parse_action(action: &str) -> Option<Event> {
         "preview-page-up"      =>   Some(Event::EvActPreviewPageUp),
         "preview-page-down"    =>   Some(Event::EvActPreviewPageDown),
         "previous-history"     =>   Some(Event::EvActPreviousHistory),
+        "reload"               =>   Some(Event::EvActReload),
+        "rotate-mode"          =>   Some(Event::EvActRotateMode),
         "scroll-left"          =>   Some(Event::EvActScrollLeft),
         "scroll-right"         =>   Some(Event::EvActScrollRight),
         "select-all"           =>   Some(Event::EvActSelectAll),
+        "swap-query"           =>   Some(Event::EvActSwapQuery),
         "toggle"               =>   Some(Event::EvActToggle),
         "toggle-all"           =>   Some(Event::EvActToggleAll),
         "toggle-in"            =>   Some(Event::EvActToggleIn),
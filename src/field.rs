@@ -1,8 +1,13 @@
 use regex::Regex;
 use std::cmp::{max, min};
+use std::sync::Arc;
+
+// the delimiter of the fields without `--delimiter`
+pub const DELIMITER_STR: &str = r"[\t\n ]+";
 
 lazy_static! {
     static ref FIELD_RANGE: Regex = Regex::new(r"^(?P<left>-?\d+)?(?P<sep>\.\.)?(?P<right>-?\d+)?$").unwrap();
+    static ref FIELD_GROUP: Regex = Regex::new(r"^\{(?P<name>[A-Za-z_][A-Za-z0-9_]*)\}$").unwrap();
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -11,12 +16,29 @@ pub enum FieldRange {
     LeftInf(i32),
     RightInf(i32),
     Both(i32, i32),
+    // named capture group of the delimiter regex, e.g. `{name}`
+    Group(String),
+}
+
+// The `--delimiter` regex, or the default one if it isn't given or isn't valid.
+// Parsed once by the reader and shared with everything else splitting fields (`{1}` in the
+// preview and `execute`, `--accept-nth`), so that they all agree with `--nth` and `--with-nth`.
+pub fn parse_delimiter(delimiter: Option<&str>) -> Arc<Regex> {
+    let regex = delimiter
+        .and_then(|delimiter| Regex::new(delimiter).ok())
+        .unwrap_or_else(|| Regex::new(DELIMITER_STR).unwrap());
+    Arc::new(regex)
 }
 
 impl FieldRange {
     pub fn from_str(range: &str) -> Option<FieldRange> {
         use self::FieldRange::*;
 
+        // "{name}"
+        if let Some(caps) = FIELD_GROUP.captures(range) {
+            return Some(Group(caps["name"].to_string()));
+        }
+
         // "1", "1..", "..10", "1..10", etc.
         let opt_caps = FIELD_RANGE.captures(range);
         if let Some(caps) = opt_caps {
@@ -93,6 +115,8 @@ impl FieldRange {
                     Some((max(left, 1) - 1, min(right, length)))
                 }
             }
+            // groups are not positional, see `get_range_by_group`
+            Group(_) => None,
         }
     }
 
@@ -103,12 +127,18 @@ impl FieldRange {
     }
 }
 
-// ("|", "a|b||c") -> [(0, 2), (2, 4), (4, 5), (5, 6)]
-// explain: split to ["a|", "b|", "|", "c"]
+// ("|", "a|b||c") -> [(0, 1), (2, 3), (4, 4), (5, 6)]
+// explain: split to ["a", "b", "", "c"], each range excludes the delimiter after the field.
+// Consecutive, leading and trailing delimiters give empty fields, and so does every match of a
+// regex like `:` on "a::b", while `:+` would take "::" as a single delimiter.
+// A delimiter has to be non-empty, matches like the ones of `:*` between two characters are skipped.
 fn get_ranges_by_delimiter(delimiter: &Regex, text: &str) -> Vec<(usize, usize)> {
     let mut ranges = Vec::new();
     let mut last = 0;
     for mat in delimiter.find_iter(text) {
+        if mat.start() == mat.end() {
+            continue;
+        }
         ranges.push((last, mat.start()));
         last = mat.end();
     }
@@ -116,9 +146,22 @@ fn get_ranges_by_delimiter(delimiter: &Regex, text: &str) -> Vec<(usize, usize)>
     ranges
 }
 
+// The byte range of the group `name` in the first match of the delimiter it takes part in.
+// A group that never matches gives `None`, the field is then skipped like an out-of-range one.
+// ("(?P<key>\w+)=", "a=1 b=2", "key") -> Some((0, 1))
+fn get_range_by_group(delimiter: &Regex, text: &str, name: &str) -> Option<(usize, usize)> {
+    delimiter
+        .captures_iter(text)
+        .find_map(|caps| caps.name(name).map(|mat| (mat.start(), mat.end())))
+}
+
 // e.g. delimiter = Regex::new(",").unwrap()
 // Note that this is differnt with `to_index_pair`, it uses delimiters like ".*?,"
 pub fn get_string_by_field<'a>(delimiter: &Regex, text: &'a str, field: &FieldRange) -> Option<&'a str> {
+    if let FieldRange::Group(name) = field {
+        return get_range_by_group(delimiter, text, name).map(|(begin, end)| &text[begin..end]);
+    }
+
     let ranges = get_ranges_by_delimiter(delimiter, text);
 
     if let Some((start, stop)) = field.to_index_pair(ranges.len()) {
@@ -142,6 +185,11 @@ pub fn parse_matching_fields(delimiter: &Regex, text: &str, fields: &[FieldRange
 
     let mut ret = Vec::new();
     for field in fields {
+        if let FieldRange::Group(name) = field {
+            ret.extend(get_range_by_group(delimiter, text, name));
+            continue;
+        }
+
         if let Some((start, stop)) = field.to_index_pair(ranges.len()) {
             let &(begin, _) = &ranges[start];
             let &(end, _) = ranges.get(stop).unwrap_or(&(text.len(), 0));
@@ -151,20 +199,78 @@ pub fn parse_matching_fields(delimiter: &Regex, text: &str, fields: &[FieldRange
     ret
 }
 
+// The text put between two fields of `--with-nth` when the first one is the last field of the line and
+// thus has no delimiter of its own: the first delimiter of the line, or a single space if there is
+// none or it is only whitespace.
+fn get_separator<'a>(delimiter: &Regex, text: &'a str) -> &'a str {
+    match delimiter.find_iter(text).find(|mat| mat.start() != mat.end()) {
+        Some(mat) if !mat.as_str().trim().is_empty() => mat.as_str(),
+        _ => " ",
+    }
+}
+
+// The fields are output in the given order and may repeat, each with the delimiter following it.
+// Given delimiter `,`, text: "a,b,c"
+// &[Single(3), Single(1), Single(1)] => "c,a,a,"
 pub fn parse_transform_fields(delimiter: &Regex, text: &str, fields: &[FieldRange]) -> String {
     let ranges = get_ranges_by_delimiter(delimiter, text);
 
     let mut ret = String::new();
+    let mut needs_separator = false;
+    let mut push_field = |ret: &mut String, begin: usize, end: usize, has_delimiter: bool| {
+        if needs_separator && begin < end {
+            ret.push_str(get_separator(delimiter, text));
+        }
+        if begin < end {
+            ret.push_str(&text[begin..end]);
+            needs_separator = !has_delimiter;
+        }
+    };
+
     for field in fields {
+        if let FieldRange::Group(name) = field {
+            if let Some((begin, end)) = get_range_by_group(delimiter, text, name) {
+                // the delimiter matches around a group are often made of fields, don't repeat them
+                push_field(&mut ret, begin, end, true);
+            }
+            continue;
+        }
+
         if let Some((start, stop)) = field.to_index_pair(ranges.len()) {
             let &(begin, _) = &ranges[start];
             let &(end, _) = ranges.get(stop).unwrap_or(&(text.len(), 0));
-            ret.push_str(&text[begin..end]);
+            push_field(&mut ret, begin, end, stop < ranges.len());
         }
     }
     ret
 }
 
+// The fields output on accept (`--accept-nth`): as `--with-nth`, without the delimiter after the last one.
+// Given delimiter `,`, text: "a,b,c"
+// &[Single(3), Single(1)] => "c,a"
+pub fn parse_accept_fields(delimiter: &Regex, text: &str, fields: &[FieldRange]) -> String {
+    let mut ret = parse_transform_fields(delimiter, text, fields);
+    let last_delimiter = delimiter.find_iter(&ret).filter(|mat| mat.start() != mat.end()).last();
+    if let Some(mat) = last_delimiter.filter(|mat| mat.end() == ret.len()) {
+        ret.truncate(mat.start());
+    }
+    ret
+}
+
+// The byte ranges of the columns shown by `--tabular`: a field and the delimiter after it, without
+// the whitespace that ends the delimiter, it's replaced by the padding.
+// ("[\t ]+", "a  bb c") -> [(0, 1), (3, 5), (6, 7)]
+// (", *", "a, bb,c") -> [(0, 2), (3, 6), (6, 7)]
+pub fn get_columns(delimiter: &Regex, text: &str) -> Vec<(usize, usize)> {
+    let ranges = get_ranges_by_delimiter(delimiter, text);
+    let next_starts = ranges.iter().skip(1).map(|&(start, _)| start).chain(Some(text.len()));
+    ranges
+        .iter()
+        .zip(next_starts)
+        .map(|(&(start, end), next_start)| (start, end + text[end..next_start].trim_end().len()))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::FieldRange::*;
@@ -186,6 +292,12 @@ mod test {
         assert_eq!(FieldRange::from_str("a.."), None);
         assert_eq!(FieldRange::from_str("..b"), None);
         assert_eq!(FieldRange::from_str("a..b"), None);
+
+        assert_eq!(FieldRange::from_str("{name}"), Some(Group("name".to_string())));
+        assert_eq!(FieldRange::from_str("{_g1}"), Some(Group("_g1".to_string())));
+        assert_eq!(FieldRange::from_str("{1}"), None);
+        assert_eq!(FieldRange::from_str("{}"), None);
+        assert_eq!(FieldRange::from_str("{a b}"), None);
     }
 
     use regex::Regex;
@@ -261,7 +373,7 @@ mod test {
                 &"A,B,C,D,E,F",
                 &vec![RightInf(5), RightInf(-2), RightInf(-1), RightInf(8)]
             ),
-            "E,FE,FF"
+            "E,F,E,F,F"
         );
 
         assert_eq!(
@@ -270,7 +382,64 @@ mod test {
                 &"A,B,C,D,E,F",
                 &vec![Both(3, 3), Both(-9, 2), Both(6, 10), Both(-9, -5)]
             ),
-            "C,A,B,FA,B,"
+            "C,A,B,F,A,B,"
+        );
+    }
+
+    #[test]
+    fn test_parse_transform_fields_reorder() {
+        let re = Regex::new(",").unwrap();
+        assert_eq!(
+            super::parse_transform_fields(&re, "a,b,c", &[Single(3), Single(1), Single(1)]),
+            "c,a,a,"
+        );
+        assert_eq!(
+            super::parse_transform_fields(&re, "a,b,c", &[Single(3), Single(3)]),
+            "c,c"
+        );
+        assert_eq!(
+            super::parse_transform_fields(&re, "a,b,c", &[RightInf(2), Single(1)]),
+            "b,c,a,"
+        );
+        // an empty field adds nothing
+        assert_eq!(
+            super::parse_transform_fields(&re, "a,b,", &[Single(3), Single(1)]),
+            "a,"
+        );
+        assert_eq!(
+            super::parse_transform_fields(&re, "a,b,c", &[Single(3), Single(5), Single(2)]),
+            "c,b,"
+        );
+
+        // whitespace delimiters are joined by a single space
+        let re = Regex::new(r"[\t\n ]+").unwrap();
+        assert_eq!(
+            super::parse_transform_fields(&re, "a\t\tb  c", &[Single(3), Single(1)]),
+            "c a\t\t"
+        );
+        assert_eq!(
+            super::parse_transform_fields(&re, "abc", &[Single(1), Single(1)]),
+            "abc abc"
+        );
+    }
+
+    #[test]
+    fn test_parse_accept_fields() {
+        let re = Regex::new(",").unwrap();
+        assert_eq!(super::parse_accept_fields(&re, "a,b,c", &[Single(1)]), "a");
+        assert_eq!(super::parse_accept_fields(&re, "a,b,c", &[Single(3), Single(1)]), "c,a");
+        assert_eq!(super::parse_accept_fields(&re, "a,b,c", &[RightInf(2)]), "b,c");
+        // out of range
+        assert_eq!(super::parse_accept_fields(&re, "a,b,c", &[Single(5)]), "");
+
+        let re = Regex::new(r"[\t\n ]+").unwrap();
+        assert_eq!(
+            super::parse_accept_fields(&re, "42  some  description", &[Single(1)]),
+            "42"
+        );
+        assert_eq!(
+            super::parse_accept_fields(&re, "42  some  description", &[Single(3), Single(1)]),
+            "description 42"
         );
     }
 
@@ -379,4 +548,72 @@ mod test {
         assert_eq!(get_string_by_field(&re, &text, &Both(3, 3)), Some("c"));
         assert_eq!(get_string_by_field(&re, &text, &Both(4, 3)), None);
     }
+
+    #[test]
+    fn test_named_group_fields() {
+        // "key=value" pairs separated by spaces
+        let re = Regex::new(r"(?P<key>\w+)=(?P<value>\w+)|(?P<never>!)").unwrap();
+        let text = "name=foo size=3";
+        let key = Group("key".to_string());
+        let value = Group("value".to_string());
+        let never = Group("never".to_string());
+        let missing = Group("missing".to_string());
+
+        assert_eq!(get_string_by_field(&re, text, &key), Some("name"));
+        assert_eq!(get_string_by_field(&re, text, &value), Some("foo"));
+        assert_eq!(get_string_by_field(&re, text, &never), None);
+        assert_eq!(get_string_by_field(&re, text, &missing), None);
+
+        assert_eq!(
+            parse_matching_fields(&re, text, &[value.clone(), never.clone(), key.clone()]),
+            vec![(5, 8), (0, 4)]
+        );
+        assert_eq!(parse_matching_fields(&re, text, &[missing]), vec![]);
+
+        assert_eq!(
+            parse_transform_fields(&re, text, &[value, Single(2), never]),
+            "foo size=3"
+        );
+    }
+
+    #[test]
+    fn test_empty_fields() {
+        let re = Regex::new(":").unwrap();
+        assert_eq!(get_string_by_field(&re, "a::b", &Single(1)), Some("a"));
+        assert_eq!(get_string_by_field(&re, "a::b", &Single(2)), Some(""));
+        assert_eq!(get_string_by_field(&re, "a::b", &Single(3)), Some("b"));
+        assert_eq!(get_string_by_field(&re, ":a:", &Single(1)), Some(""));
+        assert_eq!(get_string_by_field(&re, ":a:", &Single(2)), Some("a"));
+        assert_eq!(get_string_by_field(&re, ":a:", &Single(-1)), Some(""));
+        assert_eq!(parse_matching_fields(&re, "a::b", &[Single(2)]), vec![(2, 3)]);
+        assert_eq!(
+            parse_transform_fields(&re, "a::b", &[Single(3), Single(2), Single(1)]),
+            "b::a:"
+        );
+
+        // a repeated delimiter is a single one
+        let re = Regex::new("[:/]+").unwrap();
+        assert_eq!(get_string_by_field(&re, "a:/b//c", &Single(2)), Some("b"));
+        assert_eq!(get_string_by_field(&re, "a:/b//c", &Single(3)), Some("c"));
+        assert_eq!(get_string_by_field(&re, "/a:/b", &Single(1)), Some(""));
+        assert_eq!(get_string_by_field(&re, "/a:/b", &RightInf(2)), Some("a:/b"));
+
+        // empty matches don't split
+        let re = Regex::new(":*").unwrap();
+        assert_eq!(get_string_by_field(&re, "ab::c", &Single(1)), Some("ab"));
+        assert_eq!(get_string_by_field(&re, "ab::c", &Single(2)), Some("c"));
+        assert_eq!(get_string_by_field(&re, "ab::c", &Single(3)), None);
+        assert_eq!(get_string_by_field(&re, "abc", &Single(1)), Some("abc"));
+    }
+
+    #[test]
+    fn test_get_columns() {
+        let re = Regex::new(r"[\t ]+").unwrap();
+        assert_eq!(get_columns(&re, "a  bb c"), vec![(0, 1), (3, 5), (6, 7)]);
+        assert_eq!(get_columns(&re, "a "), vec![(0, 1), (2, 2)]);
+        assert_eq!(get_columns(&re, ""), vec![(0, 0)]);
+
+        let re = Regex::new(", *").unwrap();
+        assert_eq!(get_columns(&re, "a, bb,c"), vec![(0, 2), (3, 6), (6, 7)]);
+    }
 }
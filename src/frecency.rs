@@ -0,0 +1,245 @@
+///! Frecency of the items (--frecency): how often and how recently they were accepted.
+///!
+///! The database is a text file, a line `COUNT<TAB>LAST_USED<TAB>TEXT` per entry. An accept only
+///! appends a line for each accepted item, so that the instances of sk running at the same time
+///! don't overwrite each other, the lines of the same text are merged on load. Once the file grows
+///! too long, it is rewritten with the merged entries, the least frecent ones dropped.
+use std::cmp::min;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// the number of entries kept when the database is compacted
+const MAX_ENTRIES: usize = 1000;
+// the database is compacted once it has this many lines
+const COMPACT_LINES: usize = 2 * MAX_ENTRIES;
+// the bonus is added to the score of the match, it shouldn't outweigh a much better match
+const MAX_BONUS: i64 = 100;
+// a lock left behind by a crashed instance is removed after this long
+const STALE_LOCK: Duration = Duration::from_secs(10);
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+struct Entry {
+    count: u64,
+    last_used: u64,
+}
+
+impl Entry {
+    // the accepts of the last hour count 4 times, the last day twice, the last week once and
+    // half as much after that
+    fn frecency(&self, now: u64) -> i64 {
+        let age = now.saturating_sub(self.last_used);
+        let count = self.count as i64;
+        if age < HOUR {
+            count * 4
+        } else if age < DAY {
+            count * 2
+        } else if age < WEEK {
+            count
+        } else {
+            count / 2
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Frecency {
+    entries: HashMap<String, Entry>,
+    now: u64,
+}
+
+impl Frecency {
+    /// load the database, a file that doesn't exist yet has no entries
+    pub fn load(path: &str) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_else(|err| {
+            if err.kind() != io::ErrorKind::NotFound {
+                warn!("frecency: failed to read {:?}: {}", path, err);
+            }
+            String::new()
+        });
+        Frecency {
+            entries: parse_entries(&content),
+            now: now(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// the bonus added to the score of the item with `text`, 0 if it was never accepted
+    pub fn bonus(&self, text: &str) -> i64 {
+        self.entries
+            .get(text)
+            .map(|entry| min(entry.frecency(self.now), MAX_BONUS))
+            .unwrap_or(0)
+    }
+}
+
+/// bump the frecency of the accepted items
+pub fn record<'a>(path: &str, texts: impl Iterator<Item = &'a str>) -> io::Result<()> {
+    let now = now();
+    let lines: String = texts.map(|text| format!("1\t{}\t{}\n", now, escape(text))).collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    // a single write, the appends of other instances are not interleaved with it
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(lines.as_bytes())?;
+
+    let num_lines = fs::read_to_string(path)?.lines().count();
+    if num_lines >= COMPACT_LINES {
+        compact(path, now)?;
+    }
+    Ok(())
+}
+
+// rewrite the database with the merged entries, only one instance does it at a time
+fn compact(path: &str, now: u64) -> io::Result<()> {
+    let lock_path = format!("{}.lock", path);
+    if let Err(err) = OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+        let stale = fs::metadata(&lock_path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map(|elapsed| elapsed > STALE_LOCK)
+            .unwrap_or(false);
+        if stale {
+            let _ = fs::remove_file(&lock_path);
+        }
+        // another instance is compacting, the lines appended meanwhile are merged next time
+        debug!("frecency: {:?} is locked: {}", path, err);
+        return Ok(());
+    }
+
+    let result = (|| {
+        let mut entries: Vec<_> = parse_entries(&fs::read_to_string(path)?).into_iter().collect();
+        entries.sort_by_key(|(_, entry)| (-entry.frecency(now), -(entry.last_used as i64)));
+        entries.truncate(MAX_ENTRIES);
+
+        let tmp_path = format!("{}.tmp", path);
+        let mut tmp = File::create(&tmp_path)?;
+        for (text, entry) in entries.iter() {
+            writeln!(tmp, "{}\t{}\t{}", entry.count, entry.last_used, escape(text))?;
+        }
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    let _ = fs::remove_file(&lock_path);
+    result
+}
+
+fn parse_entries(content: &str) -> HashMap<String, Entry> {
+    let mut entries: HashMap<String, Entry> = HashMap::new();
+    for line in content.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let count = fields.next().and_then(|count| count.parse::<u64>().ok());
+        let last_used = fields.next().and_then(|last_used| last_used.parse::<u64>().ok());
+        let text = fields.next();
+        if let (Some(count), Some(last_used), Some(text)) = (count, last_used, text) {
+            let entry = entries.entry(unescape(text)).or_default();
+            entry.count += count;
+            entry.last_used = entry.last_used.max(last_used);
+        }
+    }
+    entries
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// the items may contain newlines (--read0)
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                ret.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                ret.push('\\');
+                chars.next();
+            }
+            (ch, _) => ret.push(ch),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(count: u64, last_used: u64) -> Entry {
+        Entry { count, last_used }
+    }
+
+    #[test]
+    fn test_parse_entries_merges_lines() {
+        let entries = parse_entries("3\t100\ta\n1\t200\tb\tc\n1\t300\ta\ninvalid\n1\t50\tx\\ny\n");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries["a"], entry(4, 300));
+        assert_eq!(entries["b\tc"], entry(1, 200));
+        assert_eq!(entries["x\ny"], entry(1, 50));
+        assert_eq!(unescape(&escape("a\\nb\n\\")), "a\\nb\n\\");
+    }
+
+    #[test]
+    fn test_bonus() {
+        let now = 10 * WEEK;
+        let mut entries = HashMap::new();
+        entries.insert("recent".to_string(), entry(2, now));
+        entries.insert("old".to_string(), entry(2, now - 2 * WEEK));
+        entries.insert("often".to_string(), entry(1000, now));
+        let frecency = Frecency { entries, now };
+
+        assert_eq!(frecency.bonus("recent"), 8);
+        assert_eq!(frecency.bonus("old"), 1);
+        assert_eq!(frecency.bonus("often"), MAX_BONUS);
+        assert_eq!(frecency.bonus("never"), 0);
+    }
+
+    #[test]
+    fn test_record_and_compact() {
+        let path = std::env::temp_dir().join(format!("sk-test-frecency-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        record(path, vec!["a", "b"].into_iter()).unwrap();
+        record(path, vec!["a"].into_iter()).unwrap();
+        let frecency = Frecency::load(path);
+        assert_eq!(frecency.bonus("a"), 8);
+        assert_eq!(frecency.bonus("b"), 4);
+
+        // the database is capped
+        let texts: Vec<String> = (0..COMPACT_LINES).map(|idx| idx.to_string()).collect();
+        record(path, texts.iter().map(String::as_str)).unwrap();
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(content.lines().count(), MAX_ENTRIES);
+        // the most frecent entry is kept
+        assert!(content.lines().any(|line| line.ends_with("\ta")));
+        assert_eq!(Frecency::load(path).bonus("a"), 8);
+
+        fs::remove_file(path).unwrap();
+    }
+}
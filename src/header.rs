@@ -1,5 +1,5 @@
 ///! header of the items
-use crate::ansi::AnsiString;
+use crate::ansi::{ANSIParser, AnsiString};
 use crate::event::UpdateScreen;
 use crate::event::{Event, EventArg, EventHandler};
 use crate::item::ItemPool;
@@ -12,10 +12,13 @@ use std::sync::Arc;
 use tuikit::prelude::*;
 
 pub struct Header {
-    header: AnsiString,
+    // lines of the fixed header(--header)
+    header: Vec<AnsiString>,
     tabstop: usize,
     hscroll_offset: usize,
     reverse: bool,
+    // print the fixed header above the reserved header lines
+    header_above: bool,
     theme: Arc<ColorTheme>,
 
     // for reserved header items
@@ -25,10 +28,11 @@ pub struct Header {
 impl Header {
     pub fn empty() -> Self {
         Self {
-            header: AnsiString::new_empty(),
+            header: Vec::new(),
             tabstop: 8,
             hscroll_offset: 0,
             reverse: false,
+            header_above: false,
             theme: Arc::new(*DEFAULT_THEME),
             item_pool: Arc::new(ItemPool::new()),
         }
@@ -49,20 +53,25 @@ impl Header {
             self.reverse = true;
         }
 
+        // by default the fixed header is next to the info line
+        self.header_above = match options.header_position {
+            Some("above") => true,
+            Some("below") => false,
+            _ => self.reverse,
+        };
+
         match options.header {
             None => {}
             Some("") => {}
             Some(header) => {
-                self.header = AnsiString::from_str(header);
+                // the color is kept across the lines
+                let mut parser = ANSIParser::default();
+                self.header = header.lines().map(|line| parser.parse_ansi(line)).collect();
             }
         }
         self
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.header.is_empty()
-    }
-
     pub fn act_scroll(&mut self, offset: i32) {
         let mut hscroll_offset = self.hscroll_offset as i32;
         hscroll_offset += offset;
@@ -71,9 +80,24 @@ impl Header {
     }
 
     fn lines_of_header(&self) -> usize {
-        let fixed = if self.header.is_empty() { 0 } else { 1 };
-        let ret = fixed + self.item_pool.reserved().len();
-        ret
+        self.header.len() + self.item_pool.reserved().len()
+    }
+
+    fn print_line(&self, canvas: &mut dyn Canvas, row: usize, line: &AnsiString) {
+        let (screen_width, _) = canvas.size().unwrap_or((0, 0));
+        let mut printer = LinePrinter::builder()
+            .row(row)
+            .col(2)
+            .tabstop(self.tabstop)
+            .container_width(screen_width - 2)
+            .shift(0)
+            .text_width(screen_width - 2)
+            .hscroll_offset(self.hscroll_offset)
+            .build();
+
+        for (ch, attr) in line.iter() {
+            printer.print_char(canvas, ch, self.theme.header().extend(attr), false);
+        }
     }
 }
 
@@ -84,54 +108,37 @@ impl Draw for Header {
             return Err("screen width is too small".into());
         }
 
-        if screen_height < self.lines_of_header() {
+        let lines_of_header = self.lines_of_header();
+        if screen_height < lines_of_header {
             return Err("screen height is too small".into());
         }
 
         canvas.clear()?;
 
-        let mut lines_used = 0;
-
-        if !self.is_empty() {
-            // print fixed header(specified by --header)
-            let mut printer = LinePrinter::builder()
-                .row(if self.reverse { 0 } else { screen_height - 1 })
-                .col(2)
-                .tabstop(self.tabstop)
-                .container_width(screen_width - 2)
-                .shift(0)
-                .text_width(screen_width - 2)
-                .hscroll_offset(self.hscroll_offset)
-                .build();
-
-            for (ch, _attr) in self.header.iter() {
-                printer.print_char(canvas, ch, self.theme.header(), false);
-            }
-
-            lines_used = 1;
+        // the fixed header(--header) is printed from top to bottom regardless of the layout, while
+        // the first reserved line(--header-lines) is the closest to the info line
+        let reserved = self.item_pool.reserved();
+        let mut reserved_lines: Vec<AnsiString> = reserved
+            .iter()
+            .map(|item| AnsiString::new_string(item.get_text().to_string()))
+            .collect();
+        if !self.reverse {
+            reserved_lines.reverse();
         }
 
-        // print "reserved" header lines (--header-lines)
-        for (idx, item) in self.item_pool.reserved().iter().enumerate() {
-            let row = if self.reverse {
-                idx + lines_used
-            } else {
-                screen_height - lines_used - idx - 1
-            };
-
-            let mut printer = LinePrinter::builder()
-                .row(row)
-                .col(2)
-                .tabstop(self.tabstop)
-                .container_width(screen_width - 2)
-                .shift(0)
-                .text_width(screen_width - 2)
-                .hscroll_offset(self.hscroll_offset)
-                .build();
-
-            for ch in item.get_text().chars() {
-                printer.print_char(canvas, ch, self.theme.header(), false);
-            }
+        let (top, bottom) = if self.header_above {
+            (&self.header, &reserved_lines)
+        } else {
+            (&reserved_lines, &self.header)
+        };
+
+        let start = if self.reverse {
+            0
+        } else {
+            screen_height - lines_of_header
+        };
+        for (idx, line) in top.iter().chain(bottom.iter()).enumerate() {
+            self.print_line(canvas, start + idx, line);
         }
 
         Ok(())
@@ -39,6 +39,9 @@ impl Input {
 
     pub fn translate_event(&self, event: TermEvent) -> Vec<(Event, EventArg)> {
         match event {
+            // the position is resolved against the layout by the model
+            TermEvent::Key(key @ Key::MousePress(..)) => vec![(Event::EvInputMouse, Box::new(key) as EventArg)],
+            TermEvent::Key(Key::MouseRelease(..)) | TermEvent::Key(Key::MouseHold(..)) => vec![],
             // search event from keymap
             TermEvent::Key(key) => self
                 .keymap
@@ -62,7 +65,7 @@ impl Input {
     }
 
     pub fn bind(&mut self, key: &str, action_chain: ActionChain) {
-        let key = from_keyname(key);
+        let key = parse_key(key);
         if key == None || action_chain.is_empty() {
             return;
         }
@@ -83,24 +86,63 @@ impl Input {
     // key_action is comma separated: 'ctrl-j:accept,ctrl-k:kill-line'
     pub fn parse_keymap(&mut self, key_action: &str) {
         for (key, action_chain) in parse_key_action(key_action).into_iter() {
-            let action_chain = action_chain
-                .into_iter()
-                .filter_map(|(action, arg)| {
-                    parse_action(action).map(|act| (act, arg.map(ActionArg::String).unwrap_or(ActionArg::None)))
-                })
-                .collect();
-            self.bind(key, action_chain);
+            self.bind(key, to_action_chain(action_chain));
         }
     }
 
     pub fn parse_expect_keys(&mut self, keys: Option<&str>) {
         if let Some(keys) = keys {
-            self.bind("enter", vec![(Event::EvActAccept, ActionArg::String("".to_string()))]);
+            self.bind_expect_key("enter", "");
             for key in keys.split(',') {
-                self.bind(key, vec![(Event::EvActAccept, ActionArg::String(key.to_string()))]);
+                self.bind_expect_key(key, key);
             }
         }
     }
+
+    // the key accepts with its name. If it was bound to accept already, the actions before it
+    // still run, e.g. the command of `enter:execute-silent(...)+accept`
+    fn bind_expect_key(&mut self, key: &str, name: &str) {
+        let mut action_chain: ActionChain = parse_key(key)
+            .and_then(|key| self.keymap.get(&key))
+            .and_then(|chain| {
+                let accept = chain.iter().position(|(action, _)| *action == Event::EvActAccept)?;
+                Some(chain[..accept].to_vec())
+            })
+            .unwrap_or_default();
+        action_chain.push((Event::EvActAccept, ActionArg::String(name.to_string())));
+        self.bind(key, action_chain);
+    }
+}
+
+/// a single character is bound as it is typed (tuikit would lowercase it), e.g. `/` or `A`
+fn parse_key(key: &str) -> Option<Key> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if !ch.is_control() => Some(Key::Char(ch)),
+        _ => from_keyname(key),
+    }
+}
+
+fn to_action_chain(actions: Vec<(&str, Option<String>)>) -> ActionChain {
+    actions
+        .into_iter()
+        .filter_map(|(action, arg)| {
+            parse_action(action).map(|act| (act, arg.map(ActionArg::String).unwrap_or(ActionArg::None)))
+        })
+        .collect()
+}
+
+/// the action chain bound to an event that is not a key, e.g. `change:reload(rg {q})`
+/// like for keys, the last binding wins
+pub fn get_event_binding(maps: &[&str], event: &str) -> Option<ActionChain> {
+    let mut ret = None;
+    for (key, action_chain) in maps.iter().flat_map(|map| parse_key_action(map)) {
+        let action_chain = to_action_chain(action_chain);
+        if key == event && !action_chain.is_empty() {
+            ret = Some(action_chain);
+        }
+    }
+    ret
 }
 
 /// parse key action string to `(key, action, argument)` tuple
@@ -109,7 +151,7 @@ pub fn parse_key_action(key_action: &str) -> Vec<(&str, Vec<(&str, Option<String
     lazy_static! {
         // match `key:action` or `key:action:arg` or `key:action(arg)` etc.
         static ref RE: Regex =
-            Regex::new(r#"(?si)([^:]+?):((?:\+?[a-z-]+?(?:"[^"]*?"|'[^']*?'|\([^\)]*?\)|\[[^\]]*?\]|:[^:]*?)?\s*)+)(?:,|$)"#)
+            Regex::new(r#"(?si)(:|[^:]+?):((?:\+?[a-z-]+?(?:"[^"]*?"|'[^']*?'|\([^\)]*?\)|\[[^\]]*?\]|:[^:]*?)?\s*)+)(?:,|$)"#)
                 .unwrap();
         // grab key, action and arg out.
         static ref RE_BIND: Regex = Regex::new(r#"(?si)([a-z-]+)(?:[:\(\["'](.+?)[\)"'\]]?)?(?:\+|$)"#).unwrap();
@@ -187,6 +229,7 @@ fn get_default_key_map() -> HashMap<Key, ActionChain> {
     ret.insert(Key::Alt('l'),     vec![(Event::EvActScrollRight,        None)]);
     ret.insert(Key::Tab,          vec![(Event::EvActToggle,             None), (Event::EvActDown, None)]);
     ret.insert(Key::Ctrl('q'),    vec![(Event::EvActToggleInteractive,  None)]);
+    ret.insert(Key::Ctrl('s'),    vec![(Event::EvActToggleSort,         None)]);
     ret.insert(Key::BackTab,      vec![(Event::EvActToggle,             None), (Event::EvActUp,   None)]);
     ret.insert(Key::Ctrl('u'),    vec![(Event::EvActUnixLineDiscard,    None)]);
     ret.insert(Key::Ctrl('w'),    vec![(Event::EvActUnixWordRubout,     None)]);
@@ -201,6 +244,7 @@ fn get_default_key_map() -> HashMap<Key, ActionChain> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use tuikit::key::MouseButton;
 
     #[test]
     fn execute_should_be_parsed_correctly() {
@@ -246,4 +290,206 @@ mod test {
             key_action[1]
         );
     }
+
+    #[test]
+    fn reload_should_be_parsed() {
+        let key_action = parse_key_action("ctrl-r:reload(rg --column {q}),f5:reload");
+        assert_eq!(
+            ("ctrl-r", vec![("reload", Some("rg --column {q}".to_string()))]),
+            key_action[0]
+        );
+        assert_eq!(("f5", vec![("reload", None)]), key_action[1]);
+
+        let (event, arg) = parse_action_arg("reload(rg {q})").unwrap();
+        assert_eq!(Event::EvActReload, event);
+        assert_eq!(Some(&Some("rg {q}".to_string())), arg.downcast_ref::<Option<String>>());
+    }
+
+    #[test]
+    fn clear_actions_should_be_parsed() {
+        let key_action = parse_key_action("ctrl-u:clear-query,ctrl-x:clear-selection");
+        assert_eq!(("ctrl-u", vec![("clear-query", None)]), key_action[0]);
+        assert_eq!(("ctrl-x", vec![("clear-selection", None)]), key_action[1]);
+
+        assert_eq!(Some(Event::EvActClearQuery), parse_action("clear-query"));
+        assert_eq!(Some(Event::EvActClearSelection), parse_action("clear-selection"));
+    }
+
+    #[test]
+    fn half_page_actions_should_be_parsed() {
+        assert_eq!(Some(Event::EvActHalfPageDown), parse_action("half-page-down"));
+        assert_eq!(Some(Event::EvActHalfPageUp), parse_action("half-page-up"));
+    }
+
+    #[test]
+    fn jump_actions_should_be_parsed() {
+        let key_action = parse_key_action("ctrl-j:jump,ctrl-k:jump-accept");
+        assert_eq!(("ctrl-j", vec![("jump", None)]), key_action[0]);
+        assert_eq!(("ctrl-k", vec![("jump-accept", None)]), key_action[1]);
+
+        assert_eq!(Some(Event::EvActJump), parse_action("jump"));
+        assert_eq!(Some(Event::EvActJumpAccept), parse_action("jump-accept"));
+    }
+
+    #[test]
+    fn position_actions_should_be_parsed() {
+        let key_action = parse_key_action("ctrl-p:pos(3),home:first,end:last");
+        assert_eq!(("ctrl-p", vec![("pos", Some("3".to_string()))]), key_action[0]);
+        assert_eq!(("home", vec![("first", None)]), key_action[1]);
+        assert_eq!(("end", vec![("last", None)]), key_action[2]);
+
+        assert_eq!(Some(Event::EvActFirst), parse_action("first"));
+        assert_eq!(Some(Event::EvActLast), parse_action("last"));
+        assert_eq!(Some(Event::EvActPos), parse_action("pos"));
+    }
+
+    #[test]
+    fn abort_print_query_should_be_parsed() {
+        let key_action = parse_key_action("esc:abort-print-query");
+        assert_eq!(("esc", vec![("abort-print-query", None)]), key_action[0]);
+        assert_eq!(Some(Event::EvActAbortPrintQuery), parse_action("abort-print-query"));
+    }
+
+    #[test]
+    fn action_chain_should_run_in_order() {
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-r:clear-query+reload(rg {q})+beginning-of-line");
+        let events: Vec<(Event, Option<String>)> = input
+            .translate_event(TermEvent::Key(Key::Ctrl('r')))
+            .into_iter()
+            .map(|(event, arg)| (event, arg.downcast_ref::<Option<String>>().cloned().flatten()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Event::EvActClearQuery, None),
+                (Event::EvActReload, Some("rg {q}".to_string())),
+                (Event::EvActBeginningOfLine, None),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn expect_key_should_keep_action_chain() {
+        let mut input = Input::new();
+        input.parse_keymap("enter:execute-silent(touch {})+accept,ctrl-x:toggle+accept");
+        input.parse_expect_keys(Some("ctrl-x,ctrl-y"));
+        let translate = |key| -> Vec<(Event, Option<String>)> {
+            input
+                .translate_event(TermEvent::Key(key))
+                .into_iter()
+                .map(|(event, arg)| (event, arg.downcast_ref::<Option<String>>().cloned().flatten()))
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                (Event::EvActExecuteSilent, Some("touch {}".to_string())),
+                (Event::EvActAccept, Some("".to_string())),
+            ],
+            translate(Key::Enter)
+        );
+        assert_eq!(
+            vec![
+                (Event::EvActToggle, None),
+                (Event::EvActAccept, Some("ctrl-x".to_string()))
+            ],
+            translate(Key::Ctrl('x'))
+        );
+        assert_eq!(
+            vec![(Event::EvActAccept, Some("ctrl-y".to_string()))],
+            translate(Key::Ctrl('y'))
+        );
+
+        // the keys not bound to accept only accept
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-x:toggle");
+        input.parse_expect_keys(Some("ctrl-x"));
+        let events: Vec<Event> = input
+            .translate_event(TermEvent::Key(Key::Ctrl('x')))
+            .into_iter()
+            .map(|(event, _)| event)
+            .collect();
+        assert_eq!(vec![Event::EvActAccept], events);
+    }
+
+    #[test]
+    fn printable_char_should_be_bindable() {
+        let mut input = Input::new();
+        input.parse_keymap("/:toggle-preview,A:up,::down,,:toggle");
+        let translate = |ch| -> Vec<Event> {
+            input
+                .translate_event(TermEvent::Key(Key::Char(ch)))
+                .into_iter()
+                .map(|(event, _)| event)
+                .collect()
+        };
+        // the bound characters are not added to the query
+        assert_eq!(vec![Event::EvActTogglePreview], translate('/'));
+        assert_eq!(vec![Event::EvActUp], translate('A'));
+        assert_eq!(vec![Event::EvActDown], translate(':'));
+        assert_eq!(vec![Event::EvActToggle], translate(','));
+        assert_eq!(vec![Event::EvActAddChar], translate('a'));
+    }
+
+    #[test]
+    fn event_binding_should_be_parsed() {
+        let maps = [
+            "ctrl-r:reload,change:reload(rg {q})",
+            "change:clear-screen+reload(ls {q})",
+        ];
+        assert_eq!(
+            Some(vec![
+                (Event::EvActClearScreen, ActionArg::None),
+                (Event::EvActReload, ActionArg::String("ls {q}".to_string()))
+            ]),
+            get_event_binding(&maps, "change")
+        );
+        assert_eq!(None, get_event_binding(&maps[..1], "start"));
+
+        assert_eq!(
+            Some(vec![(
+                Event::EvActIfSingleMatch,
+                ActionArg::String("accept".to_string())
+            )]),
+            get_event_binding(&["result:if-single-match(accept)"], "result")
+        );
+    }
+
+    #[test]
+    fn preview_scroll_should_be_bindable() {
+        let mut input = Input::new();
+        input.parse_keymap("ctrl-f:preview-page-down,ctrl-b:preview-page-up,alt-j:preview-down,alt-k:preview-up");
+        let translate = |key| -> Vec<Event> {
+            input
+                .translate_event(TermEvent::Key(key))
+                .into_iter()
+                .map(|(event, _)| event)
+                .collect()
+        };
+        assert_eq!(vec![Event::EvActPreviewPageDown], translate(Key::Ctrl('f')));
+        assert_eq!(vec![Event::EvActPreviewPageUp], translate(Key::Ctrl('b')));
+        assert_eq!(vec![Event::EvActPreviewDown], translate(Key::Alt('j')));
+        assert_eq!(vec![Event::EvActPreviewUp], translate(Key::Alt('k')));
+    }
+
+    #[test]
+    fn mouse_events_should_carry_the_position() {
+        let input = Input::new();
+        for &(button, col, row) in &[
+            (MouseButton::Left, 3, 4),
+            (MouseButton::WheelUp, 1, 2),
+            (MouseButton::WheelDown, 1, 2),
+        ] {
+            let events = input.translate_event(TermEvent::Key(Key::MousePress(button, col, row)));
+            assert_eq!(1, events.len());
+            let (event, arg) = events.into_iter().next().unwrap();
+            assert_eq!(Event::EvInputMouse, event);
+            assert_eq!(Some(&Key::MousePress(button, col, row)), arg.downcast_ref::<Key>());
+        }
+
+        // the release of a click is not an action
+        assert!(input
+            .translate_event(TermEvent::Key(Key::MouseRelease(3, 4)))
+            .is_empty());
+    }
 }
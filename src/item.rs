@@ -29,6 +29,9 @@ pub struct Item {
     // The text that will be ouptut when user press `enter`
     orig_text: String,
 
+    // The raw input bytes, only kept when they are not valid UTF-8 and `orig_text` is lossy
+    orig_bytes: Option<Vec<u8>>,
+
     // The text that will shown into the screen. Can be transformed.
     text: AnsiString,
 
@@ -79,6 +82,7 @@ impl<'a> Item {
         let mut ret = Item {
             index,
             orig_text: orig_text.into_owned(),
+            orig_bytes: None,
             text,
             using_transform_fields: !trans_fields.is_empty(),
             matching_ranges: Vec::new(),
@@ -95,6 +99,12 @@ impl<'a> Item {
         ret
     }
 
+    /// Keep the raw bytes the (lossy) text was decoded from, so that they can be output as is.
+    pub fn raw_bytes(mut self, bytes: Vec<u8>) -> Self {
+        self.orig_bytes = Some(bytes);
+        self
+    }
+
     pub fn get_text(&self) -> &str {
         if !self.using_transform_fields && !self.ansi_enabled {
             &self.orig_text
@@ -123,6 +133,18 @@ impl<'a> Item {
         }
     }
 
+    /// The exact bytes to output. Differs from `get_output_text` only for input that is not valid
+    /// UTF-8, in which case the original bytes are returned unless ANSI codes had to be stripped.
+    pub fn get_output_bytes(&'a self) -> Cow<'a, [u8]> {
+        match self.orig_bytes {
+            Some(ref bytes) if !self.ansi_enabled => Cow::Borrowed(bytes),
+            _ => match self.get_output_text() {
+                Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+                Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+            },
+        }
+    }
+
     pub fn get_index(&self) -> usize {
         self.index.1
     }
@@ -141,6 +163,7 @@ impl Clone for Item {
         Item {
             index: self.index,
             orig_text: self.orig_text.clone(),
+            orig_bytes: self.orig_bytes.clone(),
             text: self.text.clone(),
             using_transform_fields: self.using_transform_fields,
             matching_ranges: self.matching_ranges.clone(),
@@ -155,6 +178,9 @@ pub struct Rank {
     pub index: i64,
     pub begin: i64,
     pub end: i64,
+    // the frecency bonus (--frecency), negated as the score. It is included in the score unless the
+    // tiebreak ranks by it
+    pub frecency: i64,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -197,8 +223,10 @@ impl MatchedItem {
     pub fn to_chars(&self) -> Option<Vec<usize>> {
         self.matched_range.as_ref().map(|r| match r {
             MatchedRange::ByteRange(start, end) => {
-                let first = self.item.orig_text[..*start].chars().count();
-                let last = first + self.item.orig_text[*start..*end].chars().count();
+                // the byte range is relative to the text being matched, not the original one
+                let text = self.item.get_text();
+                let first = text[..*start].chars().count();
+                let last = first + text[*start..*end].chars().count();
                 (first..last).collect()
             }
             MatchedRange::Chars(vec) => vec.clone(),
@@ -273,6 +301,19 @@ impl ItemPool {
         self.length.store(pool.len(), Ordering::SeqCst);
     }
 
+    /// drop the oldest items to keep at most `max_items` (the header lines are kept), the remaining
+    /// ones are to be matched again. Returns the number of items dropped.
+    pub fn drop_oldest(&self, max_items: usize) -> usize {
+        let mut pool = self.pool.lock();
+        let num_dropped = pool.len().saturating_sub(max_items);
+        if num_dropped > 0 {
+            pool.drain(..num_dropped);
+            self.taken.store(0, Ordering::SeqCst);
+            self.length.store(pool.len(), Ordering::SeqCst);
+        }
+        num_dropped
+    }
+
     pub fn take(&self) -> ItemPoolGuard<Arc<Item>> {
         let guard = self.pool.lock();
         let taken = self.taken.swap(guard.len(), Ordering::SeqCst);
@@ -305,10 +346,12 @@ pub enum RankCriteria {
     Index,
     Begin,
     End,
+    Frecency,
     NegScore,
     NegIndex,
     NegBegin,
     NegEnd,
+    NegFrecency,
 }
 
 pub fn parse_criteria(text: &str) -> Option<RankCriteria> {
@@ -317,10 +360,67 @@ pub fn parse_criteria(text: &str) -> Option<RankCriteria> {
         "index" => Some(RankCriteria::Index),
         "begin" => Some(RankCriteria::Begin),
         "end" => Some(RankCriteria::End),
+        "frecency" => Some(RankCriteria::Frecency),
         "-score" => Some(RankCriteria::NegScore),
         "-index" => Some(RankCriteria::NegIndex),
         "-begin" => Some(RankCriteria::NegBegin),
         "-end" => Some(RankCriteria::NegEnd),
+        "-frecency" => Some(RankCriteria::NegFrecency),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items(texts: &[&str]) -> Vec<Arc<Item>> {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        texts
+            .iter()
+            .enumerate()
+            .map(|(idx, &text)| Arc::new(Item::new(text.into(), false, &[], &[], &delimiter, (0, idx))))
+            .collect()
+    }
+
+    fn texts(items: &[Arc<Item>]) -> Vec<String> {
+        items.iter().map(|item| item.get_output_text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_reserved_lines() {
+        let pool = ItemPool::new().lines_to_reserve(2);
+
+        // fewer lines than reserved so far
+        pool.append(items(&["h1"]));
+        assert_eq!(texts(&pool.reserved()), vec!["h1"]);
+        assert_eq!(pool.len(), 0);
+        assert!(pool.take().is_empty());
+
+        pool.append(items(&["h2", "a"]));
+        pool.append(items(&["b"]));
+        assert_eq!(texts(&pool.reserved()), vec!["h1", "h2"]);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(texts(&pool.take()), vec!["a", "b"]);
+
+        pool.clear();
+        pool.append(items(&["h3", "h4", "c"]));
+        assert_eq!(texts(&pool.reserved()), vec!["h3", "h4"]);
+        assert_eq!(texts(&pool.take()), vec!["c"]);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let pool = ItemPool::new().lines_to_reserve(1);
+        pool.append(items(&["h", "a", "b"]));
+        let _ = pool.take();
+        assert_eq!(pool.drop_oldest(2), 0);
+        assert_eq!(pool.num_not_taken(), 0);
+
+        pool.append(items(&["c", "d"]));
+        assert_eq!(pool.drop_oldest(3), 1);
+        assert_eq!(texts(&pool.reserved()), vec!["h"]);
+        // the remaining items are all taken again
+        assert_eq!(texts(&pool.take()), vec!["b", "c", "d"]);
+    }
+}
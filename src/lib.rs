@@ -3,9 +3,11 @@ extern crate log;
 #[macro_use]
 extern crate lazy_static;
 mod ansi;
+mod border;
 mod engine;
 mod event;
 mod field;
+mod frecency;
 mod header;
 mod input;
 mod item;
@@ -20,21 +22,28 @@ mod reader;
 mod score;
 mod selection;
 mod spinlock;
+#[cfg(test)]
+mod test_canvas;
 mod theme;
 mod util;
 
+use crate::border::BorderStyle;
 use crate::event::Event::*;
 use crate::event::{EventReceiver, EventSender};
+pub use crate::item::Item;
 use crate::model::Model;
 pub use crate::options::{SkimOptions, SkimOptionsBuilder};
-pub use crate::output::SkimOutput;
+pub use crate::output::{MatchResult, SkimOutput};
+pub use crate::previewer::PreviewCallback;
+use crate::query::Query;
 use crate::reader::Reader;
 use nix::unistd::isatty;
-use std::env;
+use std::cmp::min;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::{self, Write};
 use std::os::unix::io::AsRawFd;
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{channel, Receiver};
 use std::sync::Arc;
 use std::thread;
 use tuikit::prelude::{Event as TermEvent, *};
@@ -44,20 +53,70 @@ pub struct Skim {}
 impl Skim {
     // TODO main関数から呼ばれるときsourceはNone
     pub fn run_with(options: &SkimOptions, source: Option<Box<dyn BufRead + Send>>) -> Option<SkimOutput> {
-        let min_height = options
+        Skim::run_with_reader(options, Reader::with_options(options).source(source))
+    }
+
+    /// Like `run_with`, but the items are sent over the channel, e.g. by a thread that produces
+    /// them over time. They are matched as they arrive, until all the senders are dropped. The
+    /// channel can't be read again, `reload` and the interactive mode run the command instead.
+    pub fn run_with_receiver(options: &SkimOptions, receiver: Receiver<String>) -> Option<SkimOutput> {
+        Skim::run_with_reader(options, Reader::with_options(options).source_receiver(Some(receiver)))
+    }
+
+    fn run_with_reader(options: &SkimOptions, mut reader: Reader) -> Option<SkimOutput> {
+        let mut min_height = options
             .min_height
             .map(Skim::parse_height_string)
             .expect("min_height should have default values");
-        let height = options
-            .height
-            .map(Skim::parse_height_string)
-            .expect("height should have default values");
+        // --no-height draws in full screen whatever the height is
+        let height_option = options.height.filter(|_| !options.no_height);
+        let height = if options.no_height {
+            TermHeight::Percent(100)
+        } else {
+            height_option
+                .map(|height| Skim::parse_height_string(height.trim_start_matches('~')))
+                .expect("height should have default values")
+        };
+
+        // a popup may be smaller than the terminal tuikit measures, `$LINES` is its height then
+        let (_, mut max_height) = util::env_terminal_size();
+
+        // --height ~HEIGHT: read the input before the window is drawn, so that it shrinks to fit
+        // the items, but is never shorter than the query and info lines
+        let mut reader_control = None;
+        if height_option.map(|height| height.starts_with('~')).unwrap_or(false) {
+            let chrome_height = Skim::chrome_height(options);
+            let max_items = match &height {
+                TermHeight::Fixed(rows) => Some(*rows),
+                TermHeight::Percent(percent) => util::terminal_size().map(|(_, rows)| rows * percent / 100),
+            }
+            .map(|rows| rows.saturating_sub(chrome_height));
+
+            let cmd = Query::from_options(options)
+                .replace_base_cmd_if_not_set(&util::default_command())
+                .get_cmd();
+            let ctrl = reader.run(&cmd);
+            let num_items = ctrl.wait(max_items);
+            let fit_height = chrome_height + num_items;
+            max_height = Some(min(max_height.unwrap_or(fit_height), fit_height));
+            // never hide the --header-lines that were read
+            min_height = TermHeight::Fixed(chrome_height + min(options.header_lines, num_items));
+            reader_control = Some(ctrl);
+        }
+
+        let mut term_options = TermOptions::default().min_height(min_height).height(height);
+        if let Some(max_height) = max_height {
+            term_options = term_options.max_height(TermHeight::Fixed(max_height));
+        }
 
         // std::sync系パッケージを使った処理
         let (tx, rx): (EventSender, EventReceiver) = channel(); // なぜ型注釈をつけているのか？ここは型推論で問題なさそう
 
         // Arc型
-        let term = Arc::new(Term::with_options(TermOptions::default().min_height(min_height).height(height)).unwrap());
+        let term = Arc::new(Term::with_options(term_options).unwrap());
+        if options.mouse {
+            let _ = term.enable_mouse_support();
+        }
 
         //------------------------------------------------------------------------------
         // input
@@ -85,8 +144,6 @@ impl Skim {
             }
         });
 
-        let reader = Reader::with_options(&options); // sourceを削除
-
         //------------------------------------------------------------------------------
         // start a timer for notifying refresh(リフレッシュを通知するためのtimerを開始する)
         // 上記同様送信している
@@ -99,26 +156,44 @@ impl Skim {
         // queryやmatcherという各責務を持つコンポーネントのコンフィグクラスに設定を入れ直す
         // 例えばoption.cmdはquery.base_cmdに入れ直される
         // そのqueryはModel.queryに設定される
-        let mut model = Model::new(rx, tx, reader, term.clone(), &options);
+        let mut model = Model::new(rx, tx, reader, term.clone(), options).reader_control(reader_control);
         let ret = model.start(); // イベントレシーバ処理の根幹がここっぽい
 
         // println!("model.start()が無限ループ");
         // thread::sleep_ms(5000);
         let _ = term.send_event(TermEvent::User1); // interrupt the input thread
         let _ = input_thread.join(); // スレッド終了まで待機
+        if options.mouse {
+            let _ = term.disable_mouse_support();
+        }
         let _ = term.pause();
+
+        if let Some(err) = model.get_error() {
+            eprintln!("sk: {}", err);
+        }
+
+        // the accepted items rank higher next time (--frecency)
+        if let (Some(path), Some(output)) = (options.frecency, ret.as_ref()) {
+            if !output.is_abort {
+                let texts: Vec<_> = output
+                    .selected_items
+                    .iter()
+                    .map(|item| item.get_output_text())
+                    .collect();
+                if let Err(err) = frecency::record(path, texts.iter().map(|text| text.as_ref())) {
+                    warn!("frecency: failed to update {:?}: {}", path, err);
+                }
+            }
+        }
         ret
     }
 
     pub fn filter(options: &SkimOptions, source: Option<Box<dyn BufRead + Send>>) -> i32 {
-        use crate::engine::{EngineFactory, MatcherMode};
+        use crate::engine::{EngineFactory, FuzzyAlgorithm, MatcherMode};
 
         let output_ending = if options.print0 { "\0" } else { "\n" };
         let query = options.filter;
-        let default_command = match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
-            Ok("") | Err(_) => "find .".to_owned(),
-            Ok(val) => val.to_owned(),
-        };
+        let default_command = util::default_command();
 
         let cmd = options.cmd.unwrap_or(&default_command);
 
@@ -156,17 +231,39 @@ impl Skim {
             MatcherMode::Fuzzy
         };
 
-        let engine = EngineFactory::build(query, matcher_mode);
+        let algorithm = options.algorithm.and_then(FuzzyAlgorithm::from_str).unwrap_or_default();
+        let engine = if options.literal {
+            EngineFactory::build_literal(query, matcher_mode, algorithm)
+        } else {
+            EngineFactory::build(query, matcher_mode, algorithm)
+        };
 
         //------------------------------------------------------------------------------
         // start
         let reader_control = reader.run(cmd);
 
+        let stdout = io::stdout();
         let mut match_count = 0;
+        let mut header_lines = options.header_lines;
         while !reader_control.is_done() {
             for item in reader_control.take().into_iter() {
+                // the header lines (--header-lines) are never matched
+                if header_lines > 0 {
+                    header_lines -= 1;
+                    continue;
+                }
+
+                // the items are matched against the `--nth` fields as in the interactive mode
                 if let Some(matched) = engine.match_item(item) {
-                    println!("{}\t{}", -matched.rank.score, matched.item.get_output_text());
+                    let mut stdout = stdout.lock();
+                    let _ = write!(stdout, "{}\t", -matched.rank.score);
+                    if options.print_transformed {
+                        // the text as shown by the finder: the `--with-nth` fields, without ANSI codes
+                        let _ = stdout.write_all(matched.item.get_text().as_bytes());
+                    } else {
+                        let _ = stdout.write_all(&matched.item.get_output_bytes());
+                    }
+                    let _ = stdout.write_all(output_ending.as_bytes());
                     match_count += 1;
                 }
             }
@@ -179,6 +276,15 @@ impl Skim {
         }
     }
 
+    /// the rows that are not items: the query, the info line (unless inline) and the `--header`
+    /// lines. The `--header-lines` are counted among the items read.
+    fn chrome_height(options: &SkimOptions) -> usize {
+        let info_height = if options.inline_info { 0 } else { 1 };
+        let header_height = options.header.map(|header| header.lines().count()).unwrap_or(0);
+        let border_height = 2 * BorderStyle::from_option(options.border).thickness();
+        1 + info_height + header_height + border_height
+    }
+
     // 10 -> TermHeight::Fixed(10)
     // 10% -> TermHeight::Percent(10)
     fn parse_height_string(string: &str) -> TermHeight {
@@ -189,3 +295,24 @@ impl Skim {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::SkimOptionsBuilder;
+
+    #[test]
+    fn test_chrome_height() {
+        let chrome_height = |builder: &mut SkimOptionsBuilder| Skim::chrome_height(&builder.build().unwrap());
+        // the query and the info line, an empty header takes no room
+        assert_eq!(chrome_height(&mut SkimOptionsBuilder::default()), 2);
+        assert_eq!(chrome_height(SkimOptionsBuilder::default().header(Some(""))), 2);
+        assert_eq!(
+            chrome_height(SkimOptionsBuilder::default().header(Some("a\nb")).inline_info(true)),
+            3
+        );
+        // the header lines are items
+        assert_eq!(chrome_height(SkimOptionsBuilder::default().header_lines(3)), 2);
+        assert_eq!(chrome_height(SkimOptionsBuilder::default().border(Some("rounded"))), 4);
+    }
+}
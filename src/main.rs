@@ -1,5 +1,6 @@
 extern crate clap;
 extern crate env_logger;
+#[macro_use]
 extern crate log;
 extern crate shlex;
 extern crate skim;
@@ -20,34 +21,75 @@ Usage: sk [options]
 
   Search
     --tac                reverse the order of input
+    --no-sort            Do not sort the result, keep the input order
+    --max-matches=N      Keep only the best N matched items (default: 0, all)
+    --frecency=FILE      Rank the items accepted before higher, the database
+                         of the accepts is kept in FILE
     -t, --tiebreak [score,index,begin,end,-score,...]
                          comma seperated criteria
     -n, --nth 1,2..5     specify the fields to be matched
     --with-nth 1,2..5    specify the fields to be transformed
+    --accept-nth 1,2..5  specify the fields to be output on accept
     -d, --delimiter \\t  specify the delimiter(in REGEX) for fields
     -e, --exact          start skim in exact mode
     --regex              use regex instead of fuzzy match
+    --literal            Take the query as a single term, without the
+                         extended search syntax (e.g. !, ^, |)
+    --algo=TYPE          Fuzzy matching algorithm: [v1|v2] (default: v2)
+                         v1 is faster but may miss the best match
+    --matcher-threads=N  Number of threads used for matching
+                         (default: 0, one per CPU)
 
   Interface
     -b, --bind KEYBINDS  comma seperated keybindings, in KEY:ACTION
                          such as 'ctrl-j:accept,ctrl-k:kill-line'
     -m, --multi          Enable Multiple Selection
     --no-multi           Disable Multiple Selection
+    --pre-select-n=INDEXES
+                         Select the items of the given input indexes on
+                         startup, e.g. 0,2,5 (needs --multi)
+    --pre-select-pat=REGEX
+                         Select the items matching REGEX on startup
+                         (needs --multi)
     -c, --cmd ag         command to invoke dynamically
     -I replstr           replace `replstr` with the selected item
     -i, --interactive    Start skim in interactive(command) mode
-    --color [BASE][,COLOR:ANSI]
+    --disabled           Do not filter the items by the query, e.g. to
+                         pass it to a command with change:reload(...)
+    --min-query-length=N[:empty|:all]
+                         Match only once the query has N characters,
+                         show no items (default) or all of them before
+    --cache              Reuse the output of a command that was already
+                         run with the same arguments in this session
+    --color [BASE][,COLOR:ANSI[:EFFECT]]
                          change color theme
+    --no-bold            Do not use bold text anywhere
     --no-hscroll         Disable horizontal scroll
+    --skip-to-pattern=REGEX
+                         Start long items at the first match of REGEX
+    --wrap               Show long items on several rows, not cut
+    --tabular            Align the fields (split by --delimiter) of the
+                         items on screen in columns
+    --ellipsis=STR       Ellipsis to show on truncation (default: ..)
+    --mouse              Enable mouse: click to move, double-click to
+                         accept, wheel to scroll the list or the preview
+    --no-mouse           Disable mouse (default)
+    --jump-labels=CHARS  Label characters for jump and jump-accept
+                         (default: a-z)
+    --filepath-word      Make word-wise movements respect path separators
 
   Layout
     --layout=LAYOUT      Choose layout: [default|reverse|reverse-list]
     --height=HEIGHT      Height of skim's window (--height 40%)
-    --no-height          Disable height feature
+                         prefix with ~ to fit the items (--height ~40%)
+    --no-height          Use the full screen, overriding --height
     --min-height=HEIGHT  Minimum height when --height is given by percent
                          (default: 10)
     --margin=MARGIN      Screen Margin (TRBL / TB,RL / T,RL,B / T,R,B,L)
-                         e.g. (sk --margin 1,10%)
+                         or named sides, e.g. (sk --margin 1,10%)
+                         (sk --margin t:1,l:10%)
+    --border[=STYLE]     Draw a box around the finder, inside the margins
+                         [rounded|sharp|double|none] (default: rounded)
     -p, --prompt '> '    prompt string for query mode
     --cmd-prompt '> '    prompt string for command mode
 
@@ -55,24 +97,40 @@ Usage: sk [options]
     --ansi               parse ANSI color codes for input strings
     --tabstop=SPACES     Number of spaces for a tab character (default: 8)
     --inline-info        Display info next to query
+    --info-command=CMD   Show the first line of the output of CMD in the
+                         info line instead of the numbers of items
+    --spinner=STR        Frames of the spinner shown while reading (default: -\\|/)
+    --refresh=MS         Interval between two redraws (default: 100, minimum: 20)
     --header=STR         Display STR next to info
     --header-lines=N     The first N lines of the input are treated as header
+    --header-position=POS
+                         Put the header above or below the header lines
 
   Preview
     --preview=COMMAND    command to preview current highlighted line ({})
                          We can specify the fields. e.g. ({1}, {..3}, {0..})
     --preview-window=OPT Preview window layout (default: right:50%)
-                         [up|down|left|right][:SIZE[%]][:hidden]
+                         [up|down|left|right][:SIZE[%]][:hidden][:wrap][:follow]
+                         [:border-sharp|border-rounded|border-double|border-none]
+                         [:+SCROLL[-OFFSET][/DENOM]]
 
   Scripting
     -q, --query \"\"       specify the initial query
+    --cursor-on-exact    Start on the first item containing the query as is
     --cmd-query \"\"       specify the initial query for interactive mode
     --expect KEYS        comma seperated keys that can be used to complete skim
     --read0              Read input delimited by ASCII NUL(\\0) characters
+    --reader-timeout=MS  Stop reading input after MS milliseconds without
+                         new data (default: 0, wait forever)
+    --max-items=N[:stop|:drop]
+                         Stop reading after N items (default), or drop
+                         the oldest ones to keep the last N
+    --exit-term-on-empty Exit without showing the finder if the input is empty
     --print0             Print output delimited by ASCII NUL(\\0) characters
     --print-query        Print query as the first line
     --print-cmd          Print command query as the first line (after --print-query)
     -f, --filter=STR     Filter mode. Do not start interactive finder.
+    --print-transformed  Print the --with-nth fields of the matches in filter mode
 
   Environment variables
     SKIM_DEFAULT_COMMAND Default command to use when input is tty
@@ -82,19 +140,11 @@ Usage: sk [options]
 
   Reserved (not used for now)
     --extended
-    --algo=TYPE
-    --literal
-    --no-mouse
     --cycle
     --hscroll-off=COL
-    --filepath-word
-    --jump-labels=CHARS
-    --border
-    --no-bold
     --history=FILE
     --history-size=N
     --sync
-    --no-sort
     --select-1
     --exit-0
 ";
@@ -136,7 +186,6 @@ fn real_main() -> i32 {
 
     // 最初の引数がない場合が、文字列を出力してpanic
     args.push(env::args().next().expect("there should be at least one arg: the application name"));
-    println!("args(before) {:?}", args);
 
     args.extend(env::var("SKIM_DEFAULT_OPTIONS")
                 .ok()
@@ -146,7 +195,7 @@ fn real_main() -> i32 {
     for arg in env::args().skip(1) {
         args.push(arg);
     }
-    println!("args(after ) {:?}", args);
+    debug!("args: {:?}", args);
 
 
     //------------------------------------------------------------------------------
@@ -158,6 +207,8 @@ fn real_main() -> i32 {
         .arg(Arg::with_name("bind").long("bind").short("b").multiple(true).takes_value(true))
         .arg(Arg::with_name("multi").long("multi").short("m").multiple(true))
         .arg(Arg::with_name("no-multi").long("no-multi").multiple(true))
+        .arg(Arg::with_name("pre-select-n").long("pre-select-n").multiple(true).takes_value(true))
+        .arg(Arg::with_name("pre-select-pat").long("pre-select-pat").multiple(true).takes_value(true))
         .arg(Arg::with_name("prompt").long("prompt").short("p").multiple(true).takes_value(true).default_value("> "))
         .arg(Arg::with_name("cmd-prompt").long("cmd-prompt").multiple(true).takes_value(true).default_value("c> "))
         .arg(Arg::with_name("expect").long("expect").multiple(true).takes_value(true))
@@ -167,12 +218,20 @@ fn real_main() -> i32 {
         .arg(Arg::with_name("exact").long("exact").short("e").multiple(true))
         .arg(Arg::with_name("cmd").long("cmd").short("cmd").multiple(true).takes_value(true)) // TODO short("c")じゃなくていいのはなぜ??
         .arg(Arg::with_name("interactive").long("interactive").short("i").multiple(true))
+        .arg(Arg::with_name("disabled").long("disabled").multiple(true))
+        .arg(Arg::with_name("min-query-length").long("min-query-length").multiple(true).takes_value(true))
+        .arg(Arg::with_name("max-matches").long("max-matches").multiple(true).takes_value(true))
+        .arg(Arg::with_name("frecency").long("frecency").multiple(true).takes_value(true))
+        .arg(Arg::with_name("cache").long("cache").multiple(true))
         .arg(Arg::with_name("query").long("query").short("q").multiple(true).takes_value(true))
+        .arg(Arg::with_name("cursor-on-exact").long("cursor-on-exact").multiple(true))
         .arg(Arg::with_name("cmd-query").long("cmd-query").multiple(true).takes_value(true))
         .arg(Arg::with_name("regex").long("regex").multiple(true))
+        .arg(Arg::with_name("matcher-threads").long("matcher-threads").multiple(true).takes_value(true).default_value("0"))
         .arg(Arg::with_name("delimiter").long("delimiter").short("d").multiple(true).takes_value(true))
         .arg(Arg::with_name("nth").long("nth").short("n").multiple(true).takes_value(true))
         .arg(Arg::with_name("with-nth").long("with-nth").multiple(true).takes_value(true))
+        .arg(Arg::with_name("accept-nth").long("accept-nth").multiple(true).takes_value(true))
         .arg(Arg::with_name("replstr").short("I").multiple(true).takes_value(true))
         .arg(Arg::with_name("color").long("color").multiple(true).takes_value(true))
         .arg(Arg::with_name("margin").long("margin").multiple(true).takes_value(true).default_value("0,0,0,0"))
@@ -185,23 +244,36 @@ fn real_main() -> i32 {
 
         .arg(Arg::with_name("algorithm").long("algo").multiple(true).takes_value(true).default_value(""))
         .arg(Arg::with_name("literal").long("literal").multiple(true))
+        .arg(Arg::with_name("mouse").long("mouse").multiple(true))
         .arg(Arg::with_name("no-mouse").long("no-mouse").multiple(true))
         .arg(Arg::with_name("cycle").long("cycle").multiple(true))
         .arg(Arg::with_name("no-hscroll").long("no-hscroll").multiple(true))
+        .arg(Arg::with_name("skip-to-pattern").long("skip-to-pattern").multiple(true).takes_value(true))
+        .arg(Arg::with_name("wrap").long("wrap").multiple(true))
+        .arg(Arg::with_name("tabular").long("tabular").multiple(true))
+        .arg(Arg::with_name("ellipsis").long("ellipsis").multiple(true).takes_value(true))
         .arg(Arg::with_name("hscroll-off").long("hscroll-off").multiple(true).takes_value(true).default_value("10"))
         .arg(Arg::with_name("filepath-word").long("filepath-word").multiple(true))
         .arg(Arg::with_name("jump-labels").long("jump-labels").multiple(true).takes_value(true).default_value("abcdefghijklmnopqrstuvwxyz"))
-        .arg(Arg::with_name("border").long("border").multiple(true))
+        .arg(Arg::with_name("border").long("border").multiple(true).takes_value(true).min_values(0).require_equals(true))
         .arg(Arg::with_name("inline-info").long("inline-info").multiple(true))
+        .arg(Arg::with_name("info-command").long("info-command").multiple(true).takes_value(true))
+        .arg(Arg::with_name("spinner").long("spinner").multiple(true).takes_value(true))
+        .arg(Arg::with_name("refresh").long("refresh").multiple(true).takes_value(true))
         .arg(Arg::with_name("header").long("header").multiple(true).takes_value(true).default_value(""))
         .arg(Arg::with_name("header-lines").long("header-lines").multiple(true).takes_value(true).default_value("0"))
+        .arg(Arg::with_name("header-position").long("header-position").multiple(true).takes_value(true))
         .arg(Arg::with_name("tabstop").long("tabstop").multiple(true).takes_value(true).default_value("8"))
         .arg(Arg::with_name("no-bold").long("no-bold").multiple(true))
         .arg(Arg::with_name("history").long("history").multiple(true).takes_value(true).default_value(""))
         .arg(Arg::with_name("history-size").long("history-size").multiple(true).takes_value(true).default_value("500"))
         .arg(Arg::with_name("print-query").long("print-query").multiple(true))
         .arg(Arg::with_name("print-cmd").long("print-cmd").multiple(true))
+        .arg(Arg::with_name("print-transformed").long("print-transformed").multiple(true))
         .arg(Arg::with_name("read0").long("read0").multiple(true))
+        .arg(Arg::with_name("reader-timeout").long("reader-timeout").multiple(true).takes_value(true))
+        .arg(Arg::with_name("max-items").long("max-items").multiple(true).takes_value(true))
+        .arg(Arg::with_name("exit-term-on-empty").long("exit-term-on-empty").multiple(true))
         .arg(Arg::with_name("print0").long("print0").multiple(true))
         .arg(Arg::with_name("sync").long("sync").multiple(true))
         .arg(Arg::with_name("extended").long("extended").short("x").multiple(true))
@@ -222,7 +294,13 @@ fn real_main() -> i32 {
         return 0;
     }
 
-    let options = parse_options(&opts); // clap::AppからSkimOptionに変換
+    let options = match parse_options(&opts) { // clap::AppからSkimOptionに変換
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("sk: {}", err);
+            return 2;
+        }
+    };
     // println!("{:?}", options.cmd); // -c オプションで指定した値
 
     if opts.is_present("filter") {
@@ -232,14 +310,19 @@ fn real_main() -> i32 {
     let output_ending = if options.print0 {"\0"} else {"\n"};
 
     let output = Skim::run_with(&options, None); // ここがUI部分も含めて重要な処理
-    /*
     if output.is_none() {
         return 130;
     }
 
     let output = output.unwrap();
 
-    // output query
+    // abort-print-query: nothing is selected, the query is kept for the shell
+    if output.is_abort {
+        print!("{}{}", output.query, output_ending);
+        return 130;
+    }
+
+    // the same order as the filter mode: query, cmd, then the accept key and the items
     if options.print_query {
         print!("{}{}", output.query, output_ending);
     }
@@ -252,36 +335,74 @@ fn real_main() -> i32 {
         print!("{}{}", key, output_ending);
     }
 
-    for item in output.selected_items.iter() {
-        print!("{}{}", item.get_output_text(), output_ending);
+    // write the raw bytes, so that non UTF-8 file names survive e.g. `| xargs`
+    use std::io::Write;
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    match output.selected_fields {
+        Some(ref selected_fields) => {
+            for fields in selected_fields.iter() {
+                let _ = stdout.write_all(fields.as_bytes());
+                let _ = stdout.write_all(output_ending.as_bytes());
+            }
+        }
+        None => {
+            for item in output.selected_items.iter() {
+                let _ = stdout.write_all(&item.get_output_bytes());
+                let _ = stdout.write_all(output_ending.as_bytes());
+            }
+        }
     }
 
     if output.selected_items.is_empty() {1} else {0}
-    */
-    return 0
 }
 
-fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
+/// whether the flag `on` was given after the last `off`, so that the last of the pair wins
+fn last_of(options: &ArgMatches, on: &str, off: &str) -> bool {
+    let last_index = |name| options.indices_of(name).and_then(|indices| indices.max());
+    last_index(on) > last_index(off)
+}
+
+fn parse_options<'a>(options: &'a ArgMatches) -> Result<SkimOptions<'a>, String> {
     SkimOptionsBuilder::default()
         .color(options.values_of("color").and_then(|vals| vals.last()))
+        .no_bold(options.is_present("no-bold"))
         .min_height(options.values_of("min-height").and_then(|vals| vals.last()))
         .no_height(options.is_present("no-height"))
         .height(options.values_of("height").and_then(|vals| vals.last()))
         .margin(options.values_of("margin").and_then(|vals| vals.last()))
+        .border(if options.is_present("border") {
+            Some(
+                options
+                    .values_of("border")
+                    .and_then(|vals| vals.last())
+                    .unwrap_or("rounded"),
+            )
+        } else {
+            None
+        })
         .preview(options.values_of("preview").and_then(|vals| vals.last()))
         .preview_window(options.values_of("preview-window").and_then(|vals| vals.last()))
         .cmd(options.values_of("cmd").and_then(|vals| vals.last()))
         .query(options.values_of("query").and_then(|vals| vals.last()))
+        .cursor_on_exact(options.is_present("cursor-on-exact"))
         .cmd_query(options.values_of("cmd-query").and_then(|vals| vals.last()))
         .replstr(options.values_of("replstr").and_then(|vals| vals.last()))
         .interactive(options.is_present("interactive"))
+        .disabled(options.is_present("disabled"))
+        .min_query_length(options.values_of("min-query-length").and_then(|vals| vals.last()))
+        .cache(options.is_present("cache"))
         .prompt(options.values_of("prompt").and_then(|vals| vals.last()))
         .cmd_prompt(options.values_of("cmd-prompt").and_then(|vals| vals.last()))
         .ansi(options.is_present("ansi"))
         .delimiter(options.values_of("delimiter").and_then(|vals| vals.last()))
         .with_nth(options.values_of("with-nth").and_then(|vals| vals.last()))
         .nth(options.values_of("nth").and_then(|vals| vals.last()))
+        .accept_nth(options.values_of("accept-nth").and_then(|vals| vals.last()))
         .read0(options.is_present("read0"))
+        .reader_timeout(options.values_of("reader-timeout").and_then(|vals| vals.last()))
+        .max_items(options.values_of("max-items").and_then(|vals| vals.last()))
+        .exit_term_on_empty(options.is_present("exit-term-on-empty"))
         .bind(
             options
                 .values_of("bind")
@@ -294,18 +415,37 @@ fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
         } else {
             options.is_present("multi")
         })
+        .pre_select_n(options.values_of("pre-select-n").and_then(|vals| vals.last()))
+        .pre_select_pat(options.values_of("pre-select-pat").and_then(|vals| vals.last()))
         .layout(options.values_of("layout").and_then(|vals| vals.last()).unwrap_or(""))
         .reverse(options.is_present("reverse"))
         .print0(options.is_present("print0"))
         .print_query(options.is_present("print-query"))
         .print_cmd(options.is_present("print-cmd"))
+        .print_transformed(options.is_present("print-transformed"))
         .no_hscroll(options.is_present("no-hscroll"))
+        .skip_to_pattern(options.values_of("skip-to-pattern").and_then(|vals| vals.last()))
+        .wrap(options.is_present("wrap"))
+        .tabular(options.is_present("tabular"))
+        .ellipsis(options.values_of("ellipsis").and_then(|vals| vals.last()))
+        .mouse(last_of(options, "mouse", "no-mouse"))
+        .jump_labels(options.values_of("jump-labels").and_then(|vals| vals.last()))
+        .filepath_word(options.is_present("filepath-word"))
         .tabstop(options.values_of("tabstop").and_then(|vals| vals.last()))
         .tiebreak(options.values_of("tiebreak").map(|x| x.collect::<Vec<_>>().join(",")))
         .tac(options.is_present("tac"))
+        .no_sort(options.is_present("no-sort"))
+        .max_matches(options.values_of("max-matches").and_then(|vals| vals.last()))
+        .frecency(options.values_of("frecency").and_then(|vals| vals.last()))
         .exact(options.is_present("exact"))
         .regex(options.is_present("regex"))
+        .literal(options.is_present("literal"))
+        .algorithm(options.values_of("algorithm").and_then(|vals| vals.last()))
+        .matcher_threads(options.values_of("matcher-threads").and_then(|vals| vals.last()))
         .inline_info(options.is_present("inline-info"))
+        .info_command(options.values_of("info-command").and_then(|vals| vals.last()))
+        .spinner(options.values_of("spinner").and_then(|vals| vals.last()))
+        .refresh(options.values_of("refresh").and_then(|vals| vals.last()))
         .header(options.values_of("header").and_then(|vals| vals.last()))
         .header_lines(
             options
@@ -314,8 +454,8 @@ fn parse_options<'a>(options: &'a ArgMatches) -> SkimOptions<'a> {
                 .map(|s| s.parse::<usize>().unwrap_or(0))
                 .unwrap_or(0),
         )
+        .header_position(options.values_of("header-position").and_then(|vals| vals.last()))
         .layout(options.values_of("layout").and_then(|vals| vals.last()).unwrap_or(""))
         .filter(options.values_of("filter").and_then(|vals| vals.last()).unwrap_or(""))
         .build()
-        .unwrap()
 }
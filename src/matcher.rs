@@ -1,20 +1,32 @@
-use crate::engine::EngineFactory;
 pub use crate::engine::MatcherMode;
+use crate::engine::{EngineFactory, FuzzyAlgorithm, MatchEngine};
 use crate::item::{ItemPool, MatchedItem};
 use crate::options::SkimOptions;
 use crate::spinlock::SpinLock;
+use crate::util::panic_message;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
 
+lazy_static! {
+    // the pools of --matcher-threads by the number of threads. They live as long as the process,
+    // dropping a rayon pool may abort while its threads are still being torn down.
+    static ref THREAD_POOLS: Mutex<HashMap<usize, Arc<ThreadPool>>> = Mutex::new(HashMap::new());
+}
+
 pub struct MatcherControl {
     stopped: Arc<AtomicBool>,
     processed: Arc<AtomicUsize>,
     matched: Arc<AtomicUsize>,
     items: Arc<SpinLock<Vec<MatchedItem>>>,
     thread_matcher: JoinHandle<()>,
+    // set if the matcher thread panicked
+    error: Arc<SpinLock<Option<String>>>,
 }
 
 impl MatcherControl {
@@ -35,6 +47,10 @@ impl MatcherControl {
         self.stopped.load(Ordering::Relaxed)
     }
 
+    pub fn get_error(&self) -> Option<String> {
+        self.error.lock().clone()
+    }
+
     pub fn into_items(self) -> Arc<SpinLock<Vec<MatchedItem>>> {
         while !self.stopped.load(Ordering::Relaxed) {}
         self.items.clone()
@@ -43,18 +59,25 @@ impl MatcherControl {
 
 pub struct Matcher {
     mode: MatcherMode,
+    algorithm: FuzzyAlgorithm,
+    literal: bool,
+    // None: use rayon's global pool, i.e. one thread per CPU
+    thread_pool: Option<Arc<ThreadPool>>,
 }
 
 impl Matcher {
     pub fn new() -> Self {
         Matcher {
             mode: MatcherMode::Fuzzy,
+            algorithm: FuzzyAlgorithm::default(),
+            literal: false,
+            thread_pool: None,
         }
     }
 
     pub fn with_options(options: &SkimOptions) -> Self {
         let mut matcher = Self::new();
-        matcher.parse_options(&options);
+        matcher.parse_options(options);
         matcher
     }
 
@@ -66,6 +89,34 @@ impl Matcher {
         if options.regex {
             self.mode = MatcherMode::Regex;
         }
+
+        self.literal = options.literal;
+
+        if let Some(algorithm) = options.algorithm {
+            self.algorithm = FuzzyAlgorithm::from_str(algorithm).unwrap_or_default();
+        }
+
+        // already validated by `SkimOptionsBuilder::build`, 0 is the default pool
+        let threads = options
+            .matcher_threads
+            .and_then(|threads| threads.parse::<usize>().ok());
+        if let Some(threads) = threads.filter(|&threads| threads > 0) {
+            self.thread_pool = thread_pool(threads);
+        }
+    }
+
+    /// the mode `run` would use given the `mode` passed in
+    pub fn get_mode(&self, mode: Option<MatcherMode>) -> MatcherMode {
+        mode.unwrap_or(self.mode)
+    }
+
+    /// the engine `run` would use for the query, to match items outside of the matcher thread
+    pub fn build_engine(&self, query: &str, mode: Option<MatcherMode>) -> Box<dyn MatchEngine> {
+        if self.literal {
+            EngineFactory::build_literal(query, mode.unwrap_or(self.mode), self.algorithm)
+        } else {
+            EngineFactory::build(query, mode.unwrap_or(self.mode), self.algorithm)
+        }
     }
 
     pub fn run<C>(
@@ -83,7 +134,7 @@ impl Matcher {
         // matcher_modeは,Defualt FazzyMode
         //
         // engin指定、デフォルトはMatcherMode::Fuzzy
-        let matcher_engine = EngineFactory::build(&query, mode.unwrap_or(self.mode));
+        let matcher_engine = self.build_engine(query, mode);
 
         let stopped = Arc::new(AtomicBool::new(false));
         let stopped_clone = stopped.clone();
@@ -96,44 +147,72 @@ impl Matcher {
         let matched_items = Arc::new(SpinLock::new(Vec::new()));
         let matched_items_clone = matched_items.clone();
 
-        let thread_matcher = thread::spawn(move || {
-            let items = item_pool.take();
-            // ItemPoolGuard<Arc<Item>>型で返却される
-            // pub struct ItemPoolGuard<'a, T: Sized + 'a> {
-            //     guard: SpinLockGuard<'a, Vec<T>>,
-            //     start: usize,
-            // }
-
-            // 1. use rayon for parallel
-            // 2. return Err to skip iteration
-            //    check https://doc.rust-lang.org/std/result/enum.Result.html#method.from_iter
-
-            let result: Result<Vec<_>, _> = items
-                .par_iter()
-                .filter_map(|item| {
-                    processed.fetch_add(1, Ordering::Relaxed);
-                    if stopped.load(Ordering::Relaxed) {
-                        Some(Err("matcher killed"))
-                    } else if let Some(item) = matcher_engine.match_item(item.clone()) {
-                        // println!("{:?}", item);
-                        // thread::sleep_ms(1000); // Fuzzyモードの結果を収集している?
-
-                        matched.fetch_add(1, Ordering::Relaxed);
-                        Some(Ok(item))
-                    } else {
-                        None
+        let error = Arc::new(SpinLock::new(None));
+        let error_clone = error.clone();
+
+        let thread_pool = self.thread_pool.clone();
+
+        let thread_matcher = thread::Builder::new()
+            .name("matcher".to_string())
+            .spawn(move || {
+                let stopped_on_panic = stopped.clone();
+                // a panic would otherwise leave the matcher "running" and `into_items` spinning forever
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let items = item_pool.take();
+                    // ItemPoolGuard<Arc<Item>>型で返却される
+                    // pub struct ItemPoolGuard<'a, T: Sized + 'a> {
+                    //     guard: SpinLockGuard<'a, Vec<T>>,
+                    //     start: usize,
+                    // }
+
+                    // 1. use rayon for parallel
+                    // 2. return Err to skip iteration
+                    //    check https://doc.rust-lang.org/std/result/enum.Result.html#method.from_iter
+
+                    // the items are split into chunks for the threads of the pool, `collect` puts the
+                    // results back in the order of the items, so the thread count doesn't matter
+                    let scan = || -> Result<Vec<_>, _> {
+                        items
+                            .par_iter()
+                            .filter_map(|item| {
+                                processed.fetch_add(1, Ordering::Relaxed);
+                                if stopped.load(Ordering::Relaxed) {
+                                    Some(Err("matcher killed"))
+                                } else if let Some(item) = matcher_engine.match_item(item.clone()) {
+                                    // println!("{:?}", item);
+                                    // thread::sleep_ms(1000); // Fuzzyモードの結果を収集している?
+
+                                    matched.fetch_add(1, Ordering::Relaxed);
+                                    Some(Ok(item))
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect()
+                    };
+
+                    let result = match thread_pool {
+                        Some(pool) => pool.install(scan),
+                        None => scan(),
+                    };
+
+                    if let Ok(items) = result {
+                        let mut pool = matched_items.lock();
+                        *pool = items;
                     }
-                })
-                .collect();
 
-            if let Ok(items) = result {
-                let mut pool = matched_items.lock();
-                *pool = items;
-            }
+                    callback(matched_items.clone());
+                    stopped.store(true, Ordering::Relaxed);
+                }));
 
-            callback(matched_items.clone());
-            stopped.store(true, Ordering::Relaxed);
-        });
+                if let Err(payload) = result {
+                    let msg = panic_message(payload.as_ref());
+                    error!("matcher: thread panicked: {}", msg);
+                    error.lock().replace(msg);
+                    stopped_on_panic.store(true, Ordering::SeqCst);
+                }
+            })
+            .expect("matcher: failed to spawn matcher thread");
 
         MatcherControl {
             stopped: stopped_clone,
@@ -141,6 +220,89 @@ impl Matcher {
             processed: processed_clone,
             items: matched_items_clone,
             thread_matcher,
+            error: error_clone,
         }
     }
 }
+
+// the pool with `threads` threads, shared by all the matchers that ask for as many
+fn thread_pool(threads: usize) -> Option<Arc<ThreadPool>> {
+    let mut pools = THREAD_POOLS.lock().expect("matcher: failed to lock the thread pools");
+    if let Some(pool) = pools.get(&threads) {
+        return Some(pool.clone());
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|idx| format!("matcher-{}", idx))
+        .build()
+        .map_err(|err| warn!("matcher: failed to build thread pool, use the default one: {}", err))
+        .ok()
+        .map(Arc::new)?;
+    pools.insert(threads, pool.clone());
+    Some(pool)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::Item;
+    use regex::Regex;
+
+    fn run_with_threads(threads: usize, lines: &[String]) -> Vec<(usize, i64)> {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let item_pool = Arc::new(ItemPool::new());
+        item_pool.append(
+            lines
+                .iter()
+                .enumerate()
+                .map(|(idx, line)| Arc::new(Item::new(line.as_str().into(), false, &[], &[], &delimiter, (0, idx))))
+                .collect(),
+        );
+
+        let threads = threads.to_string();
+        let options = SkimOptions {
+            matcher_threads: Some(&threads),
+            ..SkimOptions::default()
+        };
+        let ctrl = Matcher::with_options(&options).run("a1", item_pool, None, |_| {});
+        let items = ctrl.into_items();
+        let items = items.lock();
+        items
+            .iter()
+            .map(|matched| (matched.item.get_index(), matched.rank.score))
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let lines: Vec<_> = (0..10_000).map(|idx| format!("a{}b{}", idx % 7, idx)).collect();
+
+        let serial = run_with_threads(1, &lines);
+        assert!(!serial.is_empty());
+        assert_eq!(serial, run_with_threads(4, &lines));
+        assert_eq!(serial, run_with_threads(0, &lines));
+        // the pool is built once and kept, not dropped with the matcher
+        assert_eq!(serial, run_with_threads(4, &lines));
+        assert!(Arc::ptr_eq(&thread_pool(4).unwrap(), &thread_pool(4).unwrap()));
+    }
+
+    #[test]
+    fn test_reserved_lines_are_not_matched() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let item_pool = Arc::new(ItemPool::new().lines_to_reserve(1));
+        item_pool.append(
+            ["a header", "a1", "b"]
+                .iter()
+                .enumerate()
+                .map(|(idx, &line)| Arc::new(Item::new(line.into(), false, &[], &[], &delimiter, (0, idx))))
+                .collect(),
+        );
+
+        let ctrl = Matcher::with_options(&SkimOptions::default()).run("a", item_pool, None, |_| {});
+        let items = ctrl.into_items();
+        let items = items.lock();
+        let matched: Vec<_> = items.iter().map(|matched| matched.item.get_output_text()).collect();
+        assert_eq!(matched, vec!["a1"]);
+    }
+}
@@ -1,6 +1,8 @@
+use std::cmp::{max, min};
 use std::env;
 use std::mem;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -8,30 +10,73 @@ use std::time::{Duration, Instant};
 use chrono::Duration as TimerDuration;
 use regex::Regex;
 use timer::{Guard as TimerGuard, Timer};
+use tuikit::key::MouseButton;
 use tuikit::prelude::*;
 
+use crate::border::{BorderBox, BorderStyle};
+use crate::engine::query_narrows;
 use crate::event::{Event, EventArg, EventHandler, EventReceiver, EventSender};
+use crate::field::{parse_accept_fields, FieldRange};
 use crate::header::Header;
-use crate::input::parse_action_arg;
+use crate::input::{get_event_binding, parse_action_arg, ActionChain};
 use crate::item::{Item, ItemPool};
 use crate::matcher::{Matcher, MatcherControl, MatcherMode};
 use crate::options::SkimOptions;
-use crate::output::SkimOutput;
+use crate::output::{MatchResult, SkimOutput};
 use crate::previewer::Previewer;
 use crate::query::Query;
 use crate::reader::{Reader, ReaderControl};
 use crate::selection::Selection;
 use crate::spinlock::SpinLock;
-use crate::theme::ColorTheme;
-use crate::util::{inject_command, margin_string_to_size, parse_margin, InjectContext};
+use crate::theme::{ColorTheme, NoBoldCanvas};
+use crate::util::{
+    clamp_margin, command_env, default_command, env_terminal_size, inject_command, margin_fits, margin_string_to_size,
+    parse_margin, parse_max_items, parse_min_query_length, split_preview, terminal_size, tty_size, InjectContext,
+};
 
 const REFRESH_DURATION: i64 = 100;
+// a lower refresh rate would redraw in a busy loop
+const MIN_REFRESH_DURATION: i64 = 20;
+const CHANGE_DEBOUNCE_DURATION: i64 = 50;
+// the least time between the starts of two runs of `--info-command`
+const INFO_COMMAND_INTERVAL: Duration = Duration::from_millis(500);
 const SPINNER_DURATION: u32 = 200;
 const SPINNERS: [char; 8] = ['-', '\\', '|', '/', '-', '\\', '|', '/'];
-const DELIMITER_STR: &str = r"[\t\n ]+";
+// the space kept for the main window when the preview window is too large
+const MIN_MAIN_WIDTH: usize = 20;
+const MIN_MAIN_HEIGHT: usize = 3;
+// two clicks on the same position within this time make a double click
+const DOUBLE_CLICK_DURATION: Duration = Duration::from_millis(500);
 
 lazy_static! {
     static ref RE_FIELDS: Regex = Regex::new(r"\\?(\{-?[0-9.,q]*?})").unwrap();
+    static ref RE_PREVIEW_ALTERNATIVE: Regex = Regex::new(r"<([0-9]+)\(([^)]*)\)").unwrap();
+}
+
+/// The terminal the model is drawn on, the tests draw it on a canvas instead
+pub trait Screen {
+    fn draw(&self, draw: &dyn Draw) -> Result<()>;
+    fn present(&self) -> Result<()>;
+    fn pause(&self) -> Result<()>;
+    fn restart(&self) -> Result<()>;
+}
+
+impl Screen for Term {
+    fn draw(&self, draw: &dyn Draw) -> Result<()> {
+        Term::draw(self, draw)
+    }
+
+    fn present(&self) -> Result<()> {
+        Term::present(self)
+    }
+
+    fn pause(&self) -> Result<()> {
+        Term::pause(self)
+    }
+
+    fn restart(&self) -> Result<()> {
+        Term::restart(self)
+    }
 }
 
 pub struct Model {
@@ -40,14 +85,25 @@ pub struct Model {
     selection: Selection,
     num_options: usize,
     matcher: Matcher,
-    term: Arc<Term>,
+    term: Arc<dyn Screen>,
 
     item_pool: Arc<ItemPool>,
+    // keep only the last N items read, the older ones are dropped (--max-items=N:drop)
+    max_items_dropping: Option<usize>,
+    // whether items were dropped since the reader started
+    items_dropped: bool,
 
     rx: EventReceiver,
     tx: EventSender,
 
     matcher_mode: Option<MatcherMode>,
+    // the query doesn't filter the items (--disabled)
+    disabled: bool,
+    // shorter queries don't run the matcher, they show no items or all of them (--min-query-length)
+    min_query_length: usize,
+    short_query_shows_all: bool,
+    // abort if the initial input turns out to be empty, cleared once decided (--exit-term-on-empty)
+    exit_on_empty: bool,
     reader_timer: Instant,
     matcher_timer: Instant,
     reader_control: Option<ReaderControl>,
@@ -59,30 +115,78 @@ pub struct Model {
     previewer: Option<Previewer>,
     preview_direction: Direction,
     preview_size: Size,
+    preview_border: BorderStyle,
+    // the layout of the preview window on screens narrower than the threshold, e.g. `<80(up:30%)`
+    preview_alternative: Option<(usize, Direction, Size, BorderStyle)>,
 
     margin_top: Size,
     margin_right: Size,
     margin_bottom: Size,
     margin_left: Size,
+    // the box around the main window and the preview, inside the margins (--border)
+    border: BorderStyle,
+    // shown in the info line instead of the numbers of items (--info-command)
+    info_command: Option<InfoCommand>,
 
     layout: String,
-    delimiter: Regex,
+    // size of the last drawn screen, to find what a mouse event points to
+    screen_width: AtomicUsize,
+    screen_height: AtomicUsize,
+    // the width of the popup sk runs in (`$COLUMNS`), if narrower than the terminal
+    max_width: Option<usize>,
+    delimiter: Arc<Regex>,
+    // the fields output on accept (--accept-nth), the whole item if empty
+    accept_fields: Vec<FieldRange>,
     inline_info: bool,
     theme: Arc<ColorTheme>,
+    // --no-bold
+    no_bold: bool,
+    spinner: Arc<Vec<char>>,
+    refresh_duration: i64,
 
     // timer thread for scheduled events
     timer: Timer,
     hb_timer_guard: Option<TimerGuard>,
 
+    // actions bound to `start`, run once before the first draw
+    start_actions: Option<ActionChain>,
+
+    // actions bound to `result`, and the (query, matched, total) they were last run for
+    result_actions: Option<ActionChain>,
+    last_result: Option<(String, usize, usize)>,
+
+    // actions bound to `change`, run once the query stops changing for a moment
+    change_actions: Option<ActionChain>,
+    change_timer_guard: Option<TimerGuard>,
+
+    // actions bound to `click-header`, and the (line, column) of the last click on the header
+    click_header_actions: Option<ActionChain>,
+    header_click: Option<(usize, usize)>,
+    // the time and (row, column) of the last left click, to tell a double click
+    last_click: Option<(Instant, usize, usize)>,
+
     next_idx_to_append: usize, // for AppendAndSelect action
+
+    // `jump` or `jump-accept` waiting for the label key
+    jump: Option<Event>,
+
+    // query and mode the matched items in `selection` were matched with
+    matched_query: String,
+    matched_mode: Option<MatcherMode>,
+
+    // set when the reader or matcher thread died
+    error: Option<String>,
 }
 
 impl Model {
-    pub fn new(rx: EventReceiver, tx: EventSender, reader: Reader, term: Arc<Term>, options: &SkimOptions) -> Self {
-        let default_command = match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
-            Ok("") | Err(_) => "find .".to_owned(),
-            Ok(val) => val.to_owned(),
-        };
+    pub fn new(
+        rx: EventReceiver,
+        tx: EventSender,
+        reader: Reader,
+        term: Arc<dyn Screen>,
+        options: &SkimOptions,
+    ) -> Self {
+        let default_command = default_command();
         // ColorThemを初期化
         let theme = Arc::new(ColorTheme::init_from_options(options));
 
@@ -92,8 +196,12 @@ impl Model {
             .theme(theme.clone())
             .build();
 
+        let delimiter = reader.delimiter();
+
         // selectionにthemeを代入
-        let selection = Selection::with_options(options).theme(theme.clone());
+        let selection = Selection::with_options(options)
+            .theme(theme.clone())
+            .delimiter(delimiter.clone());
 
         // matcher
         let matcher = Matcher::with_options(options);
@@ -106,8 +214,23 @@ impl Model {
         let margins = options
             .margin
             .map(parse_margin) // Someを取り除く
-            .expect("option margin is should be specified (by default)");
+            .expect("option margin is should be specified (by default)")
+            .unwrap_or_else(|err| {
+                warn!("{}, ignore the margin", err);
+                (Size::Fixed(0), Size::Fixed(0), Size::Fixed(0), Size::Fixed(0))
+            });
         let (margin_top, margin_right, margin_bottom, margin_left) = margins;
+        // warned once here, the margins are shrunk to fit the screen on every draw
+        if let Some((width, height)) = terminal_size() {
+            if !margin_fits(height, margin_top, margin_bottom) || !margin_fits(width, margin_left, margin_right) {
+                warn!(
+                    "margin {:?} exceeds screen size {}x{}, shrink to fit",
+                    options.margin.unwrap_or_default(),
+                    width,
+                    height
+                );
+            }
+        }
 
         let mut ret = Model {
             reader,
@@ -117,6 +240,8 @@ impl Model {
             matcher,
             term,
             item_pool,
+            max_items_dropping: None,
+            items_dropped: false,
 
             rx,
             tx,
@@ -126,75 +251,165 @@ impl Model {
             reader_control: None,
             matcher_control: None,
             matcher_mode: None,
+            disabled: false,
+            min_query_length: 0,
+            short_query_shows_all: false,
+            exit_on_empty: false,
 
             header,
             preview_hidden: true,
             previewer: None,
             preview_direction: Direction::Right,
             preview_size: Size::Default,
+            preview_border: BorderStyle::Line,
+            preview_alternative: None,
 
             margin_top,
             margin_right,
             margin_bottom,
             margin_left,
+            border: BorderStyle::None,
+            info_command: None,
 
             layout: "default".to_string(),
-            delimiter: Regex::new(DELIMITER_STR).unwrap(),
+            screen_width: AtomicUsize::new(0),
+            screen_height: AtomicUsize::new(0),
+            max_width: None,
+            delimiter,
+            accept_fields: Vec::new(),
             inline_info: false,
             theme,
+            no_bold: false,
+            spinner: Arc::new(SPINNERS.to_vec()),
+            refresh_duration: REFRESH_DURATION,
             timer: Timer::new(),
             hb_timer_guard: None,
+            start_actions: None,
+            result_actions: None,
+            last_result: None,
+            change_actions: None,
+            change_timer_guard: None,
+            click_header_actions: None,
+            header_click: None,
+            last_click: None,
 
             next_idx_to_append: 0,
+            jump: None,
+            matched_query: String::new(),
+            matched_mode: None,
+            error: None,
         };
         ret.parse_options(options);
         ret
     }
 
+    /// use the reader that was already started, instead of starting it in `start`
+    pub fn reader_control(mut self, reader_control: Option<ReaderControl>) -> Self {
+        self.reader_control = reader_control;
+        self
+    }
+
     fn parse_options(&mut self, options: &SkimOptions) {
-        if let Some(delimiter) = options.delimiter {
-            self.delimiter = Regex::new(delimiter).unwrap_or_else(|_| Regex::new(DELIMITER_STR).unwrap());
+        if let Some(accept_nth) = options.accept_nth {
+            self.accept_fields = accept_nth.split(',').filter_map(FieldRange::from_str).collect();
         }
 
         self.layout = options.layout.to_string();
 
+        let (max_width, _) = env_terminal_size();
+        self.max_width = max_width;
+
         if options.inline_info {
             self.inline_info = true;
         }
 
+        self.no_bold = options.no_bold;
+
+        if let Some(spinner) = options.spinner.filter(|spinner| !spinner.is_empty()) {
+            self.spinner = Arc::new(spinner.chars().collect());
+        }
+
+        if let Some(refresh) = options.refresh.and_then(|refresh| refresh.parse::<i64>().ok()) {
+            self.refresh_duration = max(refresh, MIN_REFRESH_DURATION);
+        }
+
         if options.regex {
             self.matcher_mode = Some(MatcherMode::Regex);
         }
 
+        self.disabled = options.disabled;
+        self.exit_on_empty = options.exit_term_on_empty;
+
+        // without `:drop` the reader stops at the maximum instead
+        if let Some(Ok((max_items, true))) = options.max_items.map(parse_max_items) {
+            self.max_items_dropping = Some(max_items);
+        }
+
+        if let Some(Ok((length, shows_all))) = options.min_query_length.map(parse_min_query_length) {
+            self.min_query_length = length;
+            self.short_query_shows_all = shows_all;
+        }
+
+        self.border = BorderStyle::from_option(options.border);
+        self.info_command = options.info_command.map(InfoCommand::new);
+
+        self.start_actions = get_event_binding(&options.bind, "start");
+        self.result_actions = get_event_binding(&options.bind, "result");
+        self.change_actions = get_event_binding(&options.bind, "change");
+        self.click_header_actions = get_event_binding(&options.bind, "click-header");
+
         // preview related
-        let (preview_direction, preview_size, preview_wrap, preview_shown) = options
+        let (
+            preview_direction,
+            preview_size,
+            preview_border,
+            preview_wrap,
+            preview_shown,
+            preview_follow,
+            preview_scroll,
+        ) = options
             .preview_window
             .map(Self::parse_preview)
             .expect("option 'preview-window' should be set (by default)");
         self.preview_direction = preview_direction;
         self.preview_size = preview_size;
+        self.preview_border = preview_border;
         self.preview_hidden = !preview_shown;
+        self.preview_alternative = options.preview_window.and_then(Self::parse_preview_alternative);
 
-        if let Some(preview_cmd) = options.preview {
+        if options.preview.is_some() || options.preview_fn.is_some() {
+            let tabstop = options
+                .tabstop
+                .and_then(|tabstop| tabstop.parse::<usize>().ok())
+                .unwrap_or(8);
             let tx = Arc::new(SpinLock::new(self.tx.clone()));
             self.previewer = Some(
-                Previewer::new(Some(preview_cmd.to_string()), move || {
+                Previewer::new(options.preview.map(str::to_string), move || {
                     let _ = tx.lock().send((Event::EvHeartBeat, Box::new(true)));
                 })
                 .wrap(preview_wrap)
-                .delimiter(self.delimiter.clone()),
+                .follow(preview_follow)
+                .scroll(preview_scroll)
+                .tabstop(tabstop)
+                .preview_fn(options.preview_fn.clone())
+                .delimiter(self.delimiter.clone())
+                .theme(self.theme.clone()),
             );
         }
     }
 
-    // -> (direction, size, wrap, shown)
-    fn parse_preview(preview_option: &str) -> (Direction, Size, bool, bool) {
-        let options = preview_option.split(':').collect::<Vec<&str>>();
+    // -> (direction, size, border, wrap, shown, follow, scroll)
+    fn parse_preview(preview_option: &str) -> (Direction, Size, BorderStyle, bool, bool, bool, Option<String>) {
+        let preview_option = RE_PREVIEW_ALTERNATIVE.replace(preview_option, "");
+        let options = preview_option.split(&[':', ','][..]).collect::<Vec<&str>>();
 
         let mut direction = Direction::Right;
         let mut shown = true;
         let mut wrap = false;
+        let mut follow = false;
         let mut size = Size::Percent(50);
+        let mut border = BorderStyle::Line;
+        let mut scroll = None;
 
         for option in options {
             // mistake
@@ -207,6 +422,8 @@ impl Model {
             // raw string
             if first_char.is_digit(10) {
                 size = margin_string_to_size(option);
+            } else if first_char == '+' {
+                scroll = Some(option.to_string());
             } else {
                 match option.to_uppercase().as_str() {
                     "UP" => direction = Direction::Up,
@@ -215,12 +432,35 @@ impl Model {
                     "RIGHT" => direction = Direction::Right,
                     "HIDDEN" => shown = false,
                     "WRAP" => wrap = true,
+                    "FOLLOW" => follow = true,
+                    "BORDER-SHARP" => border = BorderStyle::Sharp,
+                    "BORDER-ROUNDED" => border = BorderStyle::Rounded,
+                    "BORDER-DOUBLE" => border = BorderStyle::Double,
+                    "BORDER-NONE" => border = BorderStyle::None,
                     _ => {}
                 }
             }
         }
 
-        (direction, size, wrap, shown)
+        (direction, size, border, wrap, shown, follow, scroll)
+    }
+
+    // `<THRESHOLD(OPTIONS)` -> (threshold, direction, size, border), the options not given in the
+    // parens are the same as the default layout
+    fn parse_preview_alternative(preview_option: &str) -> Option<(usize, Direction, Size, BorderStyle)> {
+        let caps = RE_PREVIEW_ALTERNATIVE.captures(preview_option)?;
+        let threshold = caps[1].parse::<usize>().ok()?;
+        let base = RE_PREVIEW_ALTERNATIVE.replace(preview_option, "");
+        let (direction, size, border, _, _, _, _) = Self::parse_preview(&format!("{}:{}", base, &caps[2]));
+        Some((threshold, direction, size, border))
+    }
+
+    /// the direction, size and border of the preview window on a screen this wide
+    fn preview_layout(&self, screen_width: usize) -> (Direction, Size, BorderStyle) {
+        match self.preview_alternative {
+            Some((threshold, direction, size, border)) if screen_width < threshold => (direction, size, border),
+            _ => (self.preview_direction, self.preview_size, self.preview_border),
+        }
     }
 
     // HEART_BEATはほぼずっと送信されているイベント
@@ -260,11 +500,17 @@ impl Model {
         let reader_stopped = self.reader_control.as_ref().map(|c| c.is_done()).unwrap_or(true);
         let processed = reader_stopped && items_consumed;
 
+        if processed && self.matcher_control.is_none() {
+            self.selection.stop_pre_select();
+            self.selection.move_to_exact_match(&env.query);
+            self.send_result_actions(&env.query);
+        }
+
         // run matcher if matcher had been stopped and reader had new items.
         // もしmatcherが終了しており、readerが新しいアイテムを持っている場合
         // items_consumedとreder_sttopedの両方がtrueだと実行されない
         if !processed && self.matcher_control.is_none() {
-            self.restart_matcher(); // コメントアウトする結果が出力されない
+            self.restart_matcher(env); // コメントアウトする結果が出力されない
         }
 
         // send next heart beat if matcher is still running or there are items not been processed.
@@ -272,7 +518,7 @@ impl Model {
             let tx = self.tx.clone();
             let hb_timer_guard =
                 self.timer
-                    .schedule_with_delay(TimerDuration::milliseconds(REFRESH_DURATION), move || {
+                    .schedule_with_delay(TimerDuration::milliseconds(self.refresh_duration), move || {
                         let _ = tx.send((Event::EvHeartBeat, Box::new(true))); // poring的に送信しているハートビート?
                     });
             self.hb_timer_guard.replace(hb_timer_guard);
@@ -280,11 +526,8 @@ impl Model {
     }
 
     fn act_rotate_mode(&mut self, env: &mut ModelEnv) {
-        if self.matcher_mode.is_none() {
-            self.matcher_mode = Some(MatcherMode::Regex);
-        } else {
-            self.matcher_mode = None;
-        }
+        let mode = self.matcher.get_mode(self.matcher_mode);
+        self.matcher_mode = Some(mode.next());
 
         // restart matcher
         if let Some(ctrl) = self.matcher_control.take() {
@@ -294,10 +537,25 @@ impl Model {
         env.clear_selection = ClearStrategy::Clear;
         self.item_pool.reset();
         self.num_options = 0;
-        self.restart_matcher();
+        self.restart_matcher(env);
     }
 
     fn on_cmd_query_change(&mut self, env: &mut ModelEnv) {
+        let cmd = env.cmd.clone();
+        self.restart_reader(env, &cmd);
+    }
+
+    /// re-run the reader with `cmd`, or the current command if it is empty, e.g. `reload(rg {q})`
+    fn act_reload(&mut self, env: &mut ModelEnv, cmd: &str) {
+        let cmd = if cmd.is_empty() {
+            env.cmd.clone()
+        } else {
+            self.fill_command(cmd).0
+        };
+        self.restart_reader(env, &cmd);
+    }
+
+    fn restart_reader(&mut self, env: &mut ModelEnv, cmd: &str) {
         // stop matcher
         if let Some(ctrl) = self.reader_control.take() {
             ctrl.kill();
@@ -306,25 +564,91 @@ impl Model {
             ctrl.kill();
         }
 
+        // only the initial input may abort, not a reload or a new interactive command
+        self.exit_on_empty = false;
         env.clear_selection = ClearStrategy::ClearIfNotNull;
         self.item_pool.clear();
+        self.items_dropped = false;
         self.num_options = 0;
+        // the new items may be as many as the old ones, `result` runs for them all the same
+        self.last_result = None;
 
         // restart reader
-        self.reader_control.replace(self.reader.run(&env.cmd));
-        self.restart_matcher();
+        self.reader_control.replace(self.reader.run(cmd));
+        self.restart_matcher(env);
         self.reader_timer = Instant::now();
     }
 
     fn on_query_change(&mut self, env: &mut ModelEnv) {
+        // the query is only passed to the commands, e.g. `change:reload(rg {q})`
+        if self.disabled {
+            return;
+        }
+
+        // When the query is only extended, the items it matches are among the ones matched now, so
+        // match against those instead of the whole pool. That needs the current matches to be
+        // complete, i.e. no matcher is running and the selection was not cleared since.
+        let query = self.query.get_query();
+        let narrowing = self.matcher_control.is_none()
+            && env.clear_selection == ClearStrategy::DontClear
+            && !self.matches_nothing(&self.matched_query)
+            && !self.selection.is_truncated()
+            && self.matched_mode == self.matcher_mode
+            && query_narrows(&self.matched_query, &query, self.matcher.get_mode(self.matcher_mode));
+
         // restart matcher
         if let Some(ctrl) = self.matcher_control.take() {
             ctrl.kill();
         }
         env.clear_selection = ClearStrategy::Clear;
-        self.item_pool.reset();
         self.num_options = 0;
-        self.restart_matcher();
+
+        if narrowing {
+            // items not taken yet are matched by the following runs over `item_pool`
+            let candidates = ItemPool::new();
+            candidates.append(self.selection.get_matched_items());
+            self.run_matcher(Arc::new(candidates));
+        } else {
+            self.item_pool.reset();
+            self.restart_matcher(env);
+        }
+    }
+
+    // Send the actions bound to `result` once the matcher is done with the query, but not again for
+    // the same query and items, the heart beat keeps coming after that.
+    fn send_result_actions(&mut self, query: &str) {
+        if self.result_actions.is_none() {
+            return;
+        }
+
+        let result = (query.to_string(), self.num_options, self.item_pool.len());
+        if self.last_result.as_ref() == Some(&result) {
+            return;
+        }
+        self.last_result = Some(result);
+
+        for (event, arg) in self.result_actions.iter().flatten().cloned() {
+            let _ = self.tx.send((event, arg.into()));
+        }
+    }
+
+    // Send the actions bound to `change`. Rapid keystrokes are debounced: scheduling again drops the
+    // previous guard, which cancels the pending send, so e.g. `reload` spawns one process per pause.
+    fn schedule_change_actions(&mut self) {
+        let actions = match self.change_actions.as_ref() {
+            Some(actions) => actions.clone(),
+            None => return,
+        };
+
+        let tx = self.tx.clone();
+        let guard = self
+            .timer
+            .schedule_with_delay(TimerDuration::milliseconds(CHANGE_DEBOUNCE_DURATION), move || {
+                for (event, arg) in actions.iter().cloned() {
+                    let _ = tx.send((event, arg.into()));
+                }
+            });
+        self.change_timer_guard.replace(guard);
     }
 
     fn act_execute(&mut self, cmd: &str) {
@@ -334,8 +658,16 @@ impl Model {
     }
 
     fn act_execute_silent(&mut self, cmd: &str) {
+        let (cmd, env) = self.fill_command(cmd);
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let _ = Command::new(shell).envs(env).arg("-c").arg(cmd).status();
+    }
+
+    /// inject the current item, query, etc. into the placeholders of `cmd`, and return them as
+    /// environment variables for the command as well
+    fn fill_command(&self, cmd: &str) -> (String, Vec<(&'static str, String)>) {
         let item = self.selection.get_current_item();
-        let current_selection = item.as_ref().map(|item| item.get_output_text()).unwrap();
+        let current_selection = item.as_ref().map(|item| item.get_output_text()).unwrap_or_default();
         let query = self.query.get_query();
         let cmd_query = self.query.get_cmd_query();
 
@@ -347,9 +679,178 @@ impl Model {
             cmd_query: &cmd_query,
         };
 
-        let cmd = inject_command(cmd, context).to_string();
-        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-        let _ = Command::new(shell).arg("-c").arg(cmd).status();
+        let preview_lines = self
+            .previewer
+            .as_ref()
+            .filter(|_| !self.preview_hidden)
+            .map(Previewer::get_height);
+        let mut env = command_env(context, preview_lines);
+        if let Some((line, column)) = self.header_click {
+            env.push(("SKIM_CLICK_HEADER_LINE", (line + 1).to_string()));
+            env.push(("SKIM_CLICK_HEADER_COLUMN", (column + 1).to_string()));
+        }
+        env.push(("SKIM_MATCHED", self.num_matched().to_string()));
+        env.push(("SKIM_TOTAL", self.item_pool.len().to_string()));
+        (inject_command(cmd, context).to_string(), env)
+    }
+
+    /// the number of items matched so far
+    fn num_matched(&self) -> usize {
+        self.num_options + self.matcher_control.as_ref().map(|c| c.get_num_matched()).unwrap_or(0)
+    }
+
+    /// re-run `--info-command` in the background if the command or its environment (the query,
+    /// the current item, the numbers of items) changed since the last run
+    fn refresh_info_command(&mut self) {
+        let cmd = match self.info_command.as_ref() {
+            Some(info) if !info.running.load(Ordering::Relaxed) => info.cmd.clone(),
+            _ => return,
+        };
+
+        let (cmd, env) = self.fill_command(&cmd);
+        let info = self.info_command.as_mut().unwrap();
+        let state = (cmd, env);
+        if info.last_state.as_ref() != Some(&state) {
+            info.run(&state.0, &state.1, self.tx.clone());
+            info.last_state = Some(state);
+        }
+    }
+
+    /// click moves the cursor, double click accepts, wheel scrolls the list or the preview.
+    /// A click on the header runs the actions bound to `click-header`.
+    fn act_mouse(&mut self, key: &Key) -> Option<(Event, EventArg)> {
+        // tuikit reports the column and the row (starting from 1) on the whole terminal
+        let (button, col, row) = match *key {
+            Key::MousePress(button, col, row) => (button, col as usize, row as usize),
+            _ => return None,
+        };
+        // sk is drawn at the bottom of the terminal unless it takes all of it
+        let screen_height = self.screen_height.load(Ordering::Relaxed);
+        let top = tty_size()
+            .map(|(_, height)| height.saturating_sub(screen_height))
+            .unwrap_or(0);
+        if row <= top || col == 0 {
+            return None;
+        }
+        let (row, col) = (row - top - 1, col - 1);
+
+        match button {
+            MouseButton::Left => {
+                let now = Instant::now();
+                let double_click = match self.last_click {
+                    Some((time, last_row, last_col)) => {
+                        (last_row, last_col) == (row, col) && now.duration_since(time) < DOUBLE_CLICK_DURATION
+                    }
+                    None => false,
+                };
+                self.last_click = if double_click { None } else { Some((now, row, col)) };
+
+                match self.mouse_target(row, col) {
+                    MouseTarget::Selection(line_no) => {
+                        let moved = self.selection.act_move_to_line(line_no);
+                        if moved && double_click {
+                            return Some((Event::EvActAccept, Box::new(None as Option<String>)));
+                        }
+                    }
+                    MouseTarget::Header(line_no, col) => {
+                        if let Some(actions) = self.click_header_actions.as_ref() {
+                            self.header_click = Some((line_no, col));
+                            for (event, arg) in actions.iter().cloned() {
+                                let _ = self.tx.send((event, arg.into()));
+                            }
+                            // the position is only for these actions, not for the later ones
+                            let _ = self.tx.send((Event::EvClickHeaderDone, Box::new(true)));
+                        }
+                    }
+                    MouseTarget::Preview | MouseTarget::Other => {}
+                }
+            }
+            MouseButton::WheelUp | MouseButton::WheelDown => {
+                let up = button == MouseButton::WheelUp;
+                match self.mouse_target(row, col) {
+                    MouseTarget::Selection(_) => {
+                        self.selection.act_move_line_cursor(if up { 1 } else { -1 });
+                    }
+                    MouseTarget::Preview => {
+                        let scroll = if up {
+                            Event::EvActPreviewUp
+                        } else {
+                            Event::EvActPreviewDown
+                        };
+                        if let Some(previewer) = self.previewer.as_mut() {
+                            previewer.handle(scroll, &(Box::new(true) as EventArg));
+                        }
+                    }
+                    MouseTarget::Header(..) | MouseTarget::Other => {}
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// find the part of the screen (as laid out by `draw`) the position points to
+    fn mouse_target(&self, row: usize, col: usize) -> MouseTarget {
+        let screen_width = self.screen_width.load(Ordering::Relaxed);
+        let screen_height = self.screen_height.load(Ordering::Relaxed);
+        let (margin_top, margin_bottom) = clamp_margin(screen_height, self.margin_top, self.margin_bottom);
+        let (margin_left, margin_right) = clamp_margin(screen_width, self.margin_left, self.margin_right);
+        if row < margin_top
+            || row + margin_bottom >= screen_height
+            || col < margin_left
+            || col + margin_right >= screen_width
+        {
+            return MouseTarget::Other;
+        }
+
+        let mut row = row - margin_top;
+        let mut col = col - margin_left;
+        let mut height = screen_height - margin_top - margin_bottom;
+        let mut width = screen_width - margin_left - margin_right;
+
+        let border = self.border.thickness();
+        if row < border || row + border >= height || col < border || col + border >= width {
+            return MouseTarget::Other;
+        }
+        row -= border;
+        col -= border;
+        height -= 2 * border;
+        width -= 2 * border;
+
+        if !self.preview_hidden && self.previewer.is_some() {
+            let (preview_direction, preview_size, _) = self.preview_layout(screen_width);
+            match preview_direction {
+                Direction::Up | Direction::Down => {
+                    let (main, preview) = split_preview(height, preview_size, MIN_MAIN_HEIGHT);
+                    let preview_start = if preview_direction == Direction::Up { 0 } else { main };
+                    if row >= preview_start && row < preview_start + preview {
+                        return MouseTarget::Preview;
+                    }
+                    if preview_direction == Direction::Up {
+                        row -= preview;
+                    }
+                    height = main;
+                }
+                Direction::Left | Direction::Right => {
+                    let (main, preview) = split_preview(width, preview_size, MIN_MAIN_WIDTH);
+                    let preview_start = if preview_direction == Direction::Left { 0 } else { main };
+                    if col >= preview_start && col < preview_start + preview {
+                        return MouseTarget::Preview;
+                    }
+                    if preview_direction == Direction::Left {
+                        col -= preview;
+                    }
+                    width = main;
+                }
+            }
+        }
+
+        if col >= width {
+            return MouseTarget::Other;
+        }
+
+        let header_lines = self.header.size_hint().1.unwrap_or(0);
+        main_window_target(row, col, height, header_lines, self.inline_info, &self.layout)
     }
 
     fn act_append_and_select(&mut self, env: &mut ModelEnv) {
@@ -375,6 +876,11 @@ impl Model {
         self.act_heart_beat(env);
     }
 
+    /// the reason the last run was stopped, if the reader or matcher thread died
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
     pub fn start(&mut self) -> Option<SkimOutput> {
         let mut env = ModelEnv {
             cmd: self.query.get_cmd(),
@@ -384,11 +890,23 @@ impl Model {
         };
 
         // -cでまたは環境変数コマンドを実行している?
-        self.reader_control = Some(self.reader.run(&env.cmd)); // Option<ReaderControl>
+        // the reader may have been started already to size the window (--height ~HEIGHT)
+        if self.reader_control.is_none() {
+            self.reader_control = Some(self.reader.run(&env.cmd)); // Option<ReaderControl>
+        }
 
         // 中身が遠い.. Arc<SpinLock<Vec<Arc<Item>>>>
         // println!("{:?}", self.reader_control.as_ref().unwrap().items);
 
+        // the actions bound to `start` come before any other event, the screen is drawn after them
+        let mut start_actions = self.start_actions.take().unwrap_or_default().into_iter().peekable();
+
+        // paint the prompt right away instead of waiting for the first heart beat & matcher result
+        if start_actions.peek().is_none() {
+            let _ = self.term.draw(self);
+            let _ = self.term.present();
+        }
+
         // In tee event loop, thhere might need
         let mut next_event = None;
         loop {
@@ -397,6 +915,8 @@ impl Model {
             // println!("{:?}", self.reader_control); // Debugトレイトをつけて見えるようにした
             let (ev, arg) = if next_event.is_some() {
                 next_event.take().unwrap()
+            } else if let Some((ev, arg)) = start_actions.next() {
+                (ev, arg.into())
             // イベント受信
             } else if let Ok((ev, arg)) = self.rx.recv() {
                 // println!("{:?}, {:?}", ev, arg);
@@ -406,6 +926,20 @@ impl Model {
             };
             // thread::sleep_ms(1000);
 
+            // the key pressed after `jump` is taken as the label, any other key only cancels it
+            if let Some(jump) = self.jump.filter(|_| !is_internal_event(ev)) {
+                self.jump = None;
+                let jumped = ev == Event::EvActAddChar
+                    && matches!(arg.downcast_ref::<char>(), Some(&label) if self.selection.act_jump(label));
+                self.selection.stop_jump();
+                next_event = if jumped && jump == Event::EvActJumpAccept {
+                    Some((Event::EvActAccept, Box::new(None as Option<String>)))
+                } else {
+                    Some((Event::EvActRedraw, Box::new(true)))
+                };
+                continue;
+            }
+
             match ev {
                 // TODO 一定間隔でEvEeartBeatをどれが送っているのか不明
                 Event::EvHeartBeat => {
@@ -413,6 +947,22 @@ impl Model {
                     // println!("Beat!");
                     next_event = self.consume_additional_event(Event::EvHeartBeat);
                     self.act_heart_beat(&mut env);
+
+                    // a dead reader/matcher will never finish, stop instead of hanging the UI
+                    let error = control_error(self.reader_control.as_ref(), self.matcher_control.as_ref());
+                    if error.is_some() {
+                        self.error = error;
+                        next_event = Some((Event::EvActAbort, Box::new(true)));
+                        continue;
+                    }
+
+                    // the reader is done once all of its items were moved into the pool
+                    if self.exit_on_empty && self.reader_control.as_ref().map(ReaderControl::is_done).unwrap_or(true) {
+                        self.exit_on_empty = false;
+                        if self.item_pool.len() == 0 {
+                            return None;
+                        }
+                    }
                 }
 
                 Event::EvActIfQueryEmpty => {
@@ -435,10 +985,28 @@ impl Model {
                     }
                 }
 
+                // only a final result counts, not one item matched so far
+                Event::EvActIfSingleMatch if self.matcher_control.is_none() && self.num_matched() == 1 => {
+                    next_event = arg
+                        .downcast_ref::<Option<String>>()
+                        .and_then(|os| os.as_ref().cloned())
+                        .and_then(|arg_str| parse_action_arg(&arg_str));
+                    continue;
+                }
+
                 Event::EvActTogglePreview => {
                     self.preview_hidden = !self.preview_hidden;
                 }
 
+                Event::EvActCyclePreview => {
+                    self.preview_direction = self.preview_direction.next();
+                }
+
+                Event::EvActJump | Event::EvActJumpAccept => {
+                    self.selection.start_jump();
+                    self.jump = Some(ev);
+                }
+
                 Event::EvActRotateMode => {
                     self.act_rotate_mode(&mut env);
                 }
@@ -453,22 +1021,50 @@ impl Model {
                         ctrl.kill();
                     }
 
+                    let selected_items = self.selection.get_selected_items();
+                    let selected_matches = self.get_match_results(&self.get_matcher_query(), &selected_items);
+                    let selected_fields = if self.accept_fields.is_empty() {
+                        None
+                    } else {
+                        let fields = selected_items
+                            .iter()
+                            .map(|item| {
+                                parse_accept_fields(&self.delimiter, &item.get_output_text(), &self.accept_fields)
+                            })
+                            .collect();
+                        Some(fields)
+                    };
                     return Some(SkimOutput {
+                        is_abort: false,
                         accept_key,
                         query: self.query.get_query(),
                         cmd: self.query.get_cmd_query(),
-                        selected_items: self.selection.get_selected_items(),
+                        selected_items,
+                        selected_matches,
+                        selected_fields,
                     });
                 }
 
-                Event::EvActAbort => {
+                Event::EvActAbort | Event::EvActAbortPrintQuery => {
                     if let Some(ctrl) = self.reader_control.take() {
                         ctrl.kill();
                     }
                     if let Some(ctrl) = self.matcher_control.take() {
                         ctrl.kill();
                     }
-                    return None;
+
+                    if ev == Event::EvActAbort {
+                        return None;
+                    }
+                    return Some(SkimOutput {
+                        is_abort: true,
+                        accept_key: None,
+                        query: self.query.get_query(),
+                        cmd: self.query.get_cmd_query(),
+                        selected_items: Vec::new(),
+                        selected_matches: Vec::new(),
+                        selected_fields: None,
+                    });
                 }
 
                 Event::EvActDeleteCharEOF => {
@@ -494,10 +1090,28 @@ impl Model {
                     self.act_execute_silent(&cmd);
                 }
 
+                Event::EvActReload => {
+                    let cmd = arg
+                        .downcast_ref::<Option<String>>()
+                        .and_then(|os| os.as_ref().cloned())
+                        .unwrap_or_default();
+                    self.act_reload(&mut env, &cmd);
+                }
+
                 Event::EvActAppendAndSelect => {
                     self.act_append_and_select(&mut env);
                 }
 
+                Event::EvInputMouse => {
+                    if let Some(key) = arg.downcast_ref::<Key>() {
+                        next_event = self.act_mouse(key);
+                    }
+                }
+
+                Event::EvClickHeaderDone => {
+                    self.header_click.take();
+                }
+
                 _ => {}
             }
 
@@ -520,9 +1134,11 @@ impl Model {
                 if new_cmd != env.cmd {
                     env.cmd = new_cmd;
                     self.on_cmd_query_change(&mut env);
+                    self.schedule_change_actions();
                 } else if new_query != env.query {
                     env.query = new_query;
                     self.on_query_change(&mut env);
+                    self.schedule_change_actions();
                 }
             }
 
@@ -544,13 +1160,39 @@ impl Model {
                 }
             }
 
+            if start_actions.peek().is_some() {
+                continue;
+            }
+
+            self.refresh_info_command();
             let _ = self.term.draw(self); // selfはModelで、ModelはDrawを実装している
+
+            // the drawing updates the heights of the list, if the terminal was resized the
+            // cursor might be out of the screen, scroll and draw again.
+            if self.selection.fit_to_height() {
+                let _ = self.term.draw(self);
+            }
             let _ = self.term.present();
         } // loop 終了
 
         None
     }
 
+    // re-match the selected items, they might have been selected under a different query
+    fn get_match_results(&self, query: &str, items: &[Arc<Item>]) -> Vec<MatchResult> {
+        let engine = self.matcher.build_engine(query, self.matcher_mode);
+        items
+            .iter()
+            .map(|item| MatchResult {
+                item: item.clone(),
+                matched_indices: engine
+                    .match_item(item.clone())
+                    .and_then(|matched| matched.to_chars())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
     fn consume_additional_event(&self, target_event: Event) -> Option<(Event, EventArg)> {
         // 引数で受け取ったイベントとrxで受けったイベントが一致することを確認
         // 一致したら、rxのイベントのイテレータを１つ進める。一致しないならbreak
@@ -571,15 +1213,7 @@ impl Model {
     }
 
     // matcherが初回実行されるのものここ?
-    fn restart_matcher(&mut self) {
-        self.matcher_timer = Instant::now();
-        let query = self.query.get_query();
-
-        // kill existing matcher if exits
-        if let Some(ctrl) = self.matcher_control.take() {
-            ctrl.kill();
-        }
-
+    fn restart_matcher(&mut self, env: &mut ModelEnv) {
         // ここで、-cオプションで実行したコマンドの結果が移動されている
         // if there are new items, move them to item pool
         // self.stopped.load(Ordering::Relaxed) &&
@@ -594,20 +1228,133 @@ impl Model {
             self.item_pool.append(new_items);
         };
 
+        // the dropped items may be among the matches, so all the remaining ones are matched again
+        let num_dropped = self
+            .max_items_dropping
+            .map(|max_items| self.item_pool.drop_oldest(max_items))
+            .unwrap_or(0);
+        if num_dropped > 0 {
+            self.items_dropped = true;
+            self.num_options = 0;
+            if env.clear_selection == ClearStrategy::DontClear {
+                env.clear_selection = ClearStrategy::Clear;
+            }
+        }
+
+        self.run_matcher(self.item_pool.clone());
+    }
+
+    /// the query the items are matched against, empty if the query is disabled or too short
+    fn get_matcher_query(&self) -> String {
+        let query = self.query.get_query();
+        if self.disabled || (self.short_query_shows_all && self.is_short_query(&query)) {
+            String::new()
+        } else {
+            query
+        }
+    }
+
+    fn is_short_query(&self, query: &str) -> bool {
+        query.chars().count() < self.min_query_length
+    }
+
+    /// whether the query is too short to show any items
+    fn matches_nothing(&self, query: &str) -> bool {
+        !self.disabled && !self.short_query_shows_all && self.is_short_query(query)
+    }
+
+    /// start matching the items not taken from `item_pool` against the current query
+    fn run_matcher(&mut self, item_pool: Arc<ItemPool>) {
+        self.matcher_timer = Instant::now();
+        let query = self.get_matcher_query();
+
+        // kill existing matcher if exits
+        if let Some(ctrl) = self.matcher_control.take() {
+            ctrl.kill();
+        }
+
+        // skip the scan of the items until the query is long enough
+        if self.matches_nothing(&query) {
+            let _ = item_pool.take();
+            self.selection.clear();
+            self.num_options = 0;
+            self.matched_query = query;
+            self.matched_mode = self.matcher_mode;
+            return;
+        }
+
         // send heart beat (so that heartbeat/refresh is triggered)
         let _ = self.tx.send((Event::EvHeartBeat, Box::new(true)));
 
         let tx = self.tx.clone();
 
         // ここでmatcherが初めて実行される?
-        let new_matcher_control = self
-            .matcher
-            .run(&query, self.item_pool.clone(), self.matcher_mode, move |_| {
-                // notify refresh immediately
-                let _ = tx.send((Event::EvHeartBeat, Box::new(true)));
-            });
+        let new_matcher_control = self.matcher.run(&query, item_pool, self.matcher_mode, move |_| {
+            // notify refresh immediately
+            let _ = tx.send((Event::EvHeartBeat, Box::new(true)));
+        });
 
         self.matcher_control.replace(new_matcher_control);
+        self.matched_query = query;
+        self.matched_mode = self.matcher_mode;
+    }
+}
+
+// the error of a reader or matcher thread that died, it will never finish
+fn control_error(reader_control: Option<&ReaderControl>, matcher_control: Option<&MatcherControl>) -> Option<String> {
+    reader_control
+        .and_then(ReaderControl::get_error)
+        .or_else(|| matcher_control.and_then(MatcherControl::get_error))
+}
+
+/// the events sk sends itself (the reader, matcher and timers), not triggered by a key
+fn is_internal_event(ev: Event) -> bool {
+    matches!(
+        ev,
+        Event::EvHeartBeat
+            | Event::EvActRedraw
+            | Event::EvPreviewRequest
+            | Event::EvInputInvalid
+            | Event::EvClickHeaderDone
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum MouseTarget {
+    Preview,
+    // the line No. within the list
+    Selection(usize),
+    // the line No. and column within the header
+    Header(usize, usize),
+    Other,
+}
+
+/// find the part of the main window (i.e. without margins and preview) the position points to
+fn main_window_target(
+    row: usize,
+    col: usize,
+    height: usize,
+    header_lines: usize,
+    inline_info: bool,
+    layout: &str,
+) -> MouseTarget {
+    // query, status and header lines take the rest of the main window
+    let fixed_lines = if inline_info { 1 } else { 2 } + header_lines;
+    let list_height = height.saturating_sub(fixed_lines);
+    // `reverse-list` keeps the query at the bottom, only the items are listed top-down, which is
+    // up to the selection to map the line to
+    let (list_start, header_start) = if layout == "reverse" {
+        (fixed_lines, fixed_lines - header_lines)
+    } else {
+        (0, list_height)
+    };
+
+    if row >= list_start && row < list_start + list_height {
+        MouseTarget::Selection(row - list_start)
+    } else if row >= header_start && row < header_start + header_lines {
+        MouseTarget::Header(row - header_start, col)
+    } else {
+        MouseTarget::Other
     }
 }
 
@@ -620,18 +1367,20 @@ struct ModelEnv {
 
 impl Draw for Model {
     fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
-        let (_screen_width, _screen_height) = canvas.size()?;
+        let (canvas_width, screen_height) = canvas.size()?;
+        let screen_width = self
+            .max_width
+            .map(|width| min(width, canvas_width))
+            .unwrap_or(canvas_width);
+        self.screen_width.store(screen_width, Ordering::Relaxed);
+        self.screen_height.store(screen_height, Ordering::Relaxed);
         // println!("{:?} ,{:?}", _screen_width, _screen_height);
 
         let total = self.item_pool.len();
         // Fuzzyがデフォルト、他にMatcherMode::Exact;とMatcherMode::Regex;が存在する
-        let matcher_mode = if self.matcher_mode.is_none() {
-            "".to_string()
-        } else {
-            "RE".to_string()
-        };
+        let matcher_mode = self.matcher.get_mode(self.matcher_mode).label().to_string();
 
-        let matched = self.num_options + self.matcher_control.as_ref().map(|c| c.get_num_matched()).unwrap_or(0);
+        let matched = self.num_matched();
         let matcher_running = self.item_pool.num_not_taken() != 0 || matched != self.num_options;
         let processed = self
             .matcher_control // fuzzyengin -> mather -> modelでデータが遷移
@@ -648,11 +1397,16 @@ impl Draw for Model {
             selected: self.selection.get_num_selected(),
             current_item_idx: self.selection.get_current_item_idx(),
             reading: !self.reader_control.as_ref().map(|c| c.is_done()).unwrap_or(true),
+            reader_timed_out: self.reader_control.as_ref().map(|c| c.is_timed_out()).unwrap_or(false),
+            items_capped: self.items_dropped || self.reader_control.as_ref().map(|c| c.is_capped()).unwrap_or(false),
             time_since_read: self.reader_timer.elapsed(),
             time_since_match: self.matcher_timer.elapsed(),
             matcher_mode,
+            sorted: self.selection.sort_state(),
             theme: self.theme.clone(),
+            spinner: self.spinner.clone(),
             inline_info: self.inline_info,
+            info: self.info_command.as_ref().and_then(|info| info.output.lock().clone()),
         };
 
         let win_selection = Win::new(&self.selection);
@@ -701,22 +1455,43 @@ impl Draw for Model {
                 .split(&win_query_status),
         };
 
-        let screen: Box<dyn Draw> = if !self.preview_hidden && self.previewer.is_some() {
-            let previewer = self.previewer.as_ref().unwrap();
-            let win = Win::new(previewer)
-                .basis(self.preview_size)
+        // margins that are too large would leave no room for the content
+        let (margin_top, margin_bottom) = clamp_margin(screen_height, self.margin_top, self.margin_bottom);
+        let (margin_left, margin_right) = clamp_margin(screen_width, self.margin_left, self.margin_right);
+
+        // the room inside the margins and the border
+        let border = self.border.thickness();
+        let inner_height = (screen_height - margin_top - margin_bottom).saturating_sub(2 * border);
+        let inner_width = (screen_width - margin_left - margin_right).saturating_sub(2 * border);
+
+        let (preview_direction, preview_size, preview_border) = self.preview_layout(screen_width);
+        let preview_box = match self.previewer.as_ref() {
+            Some(previewer) if !self.preview_hidden => {
+                Some(BorderBox::new(previewer, preview_border, self.theme.border()))
+            }
+            _ => None,
+        };
+
+        let screen: Box<dyn Draw> = if let Some(preview_box) = preview_box.as_ref() {
+            let (_, preview_size) = match preview_direction {
+                Direction::Up | Direction::Down => split_preview(inner_height, preview_size, MIN_MAIN_HEIGHT),
+                Direction::Left | Direction::Right => split_preview(inner_width, preview_size, MIN_MAIN_WIDTH),
+            };
+            let win = Win::new(preview_box)
+                .basis(Size::Fixed(preview_size))
                 .grow(0)
                 .shrink(0)
                 .border_attr(self.theme.border());
 
-            let win_preview = match self.preview_direction {
-                Direction::Up => win.border_bottom(true),
-                Direction::Right => win.border_left(true),
-                Direction::Down => win.border_top(true),
-                Direction::Left => win.border_right(true),
+            let win_preview = match (preview_border, preview_direction) {
+                (BorderStyle::Line, Direction::Up) => win.border_bottom(true),
+                (BorderStyle::Line, Direction::Right) => win.border_left(true),
+                (BorderStyle::Line, Direction::Down) => win.border_top(true),
+                (BorderStyle::Line, Direction::Left) => win.border_right(true),
+                _ => win,
             };
 
-            match self.preview_direction {
+            match preview_direction {
                 Direction::Up => Box::new(VSplit::default().split(win_preview).split(win_main)),
                 Direction::Right => Box::new(HSplit::default().split(win_main).split(win_preview)),
                 Direction::Down => Box::new(VSplit::default().split(win_main).split(win_preview)),
@@ -727,12 +1502,18 @@ impl Draw for Model {
             Box::new(win_main)
         };
 
-        Win::new(screen.as_ref())
-            .margin_top(self.margin_top)
-            .margin_right(self.margin_right)
-            .margin_bottom(self.margin_bottom)
-            .margin_left(self.margin_left)
-            .draw(canvas)
+        let screen_box = BorderBox::new(screen.as_ref(), self.border, self.theme.border());
+        let win = Win::new(&screen_box)
+            .margin_top(Size::Fixed(margin_top))
+            .margin_right(Size::Fixed(margin_right + canvas_width - screen_width))
+            .margin_bottom(Size::Fixed(margin_bottom))
+            .margin_left(Size::Fixed(margin_left));
+
+        if self.no_bold {
+            win.draw(&mut NoBoldCanvas::new(canvas))
+        } else {
+            win.draw(canvas)
+        }
     }
 }
 
@@ -746,11 +1527,19 @@ struct Status {
     selected: usize,
     current_item_idx: usize,
     reading: bool,
+    reader_timed_out: bool,
+    // not all the input is kept (--max-items)
+    items_capped: bool,
     time_since_read: Duration,
     time_since_match: Duration,
     matcher_mode: String,
+    // shown once toggle-sort was used
+    sorted: Option<bool>,
     theme: Arc<ColorTheme>,
+    spinner: Arc<Vec<char>>,
     inline_info: bool,
+    // the output of `--info-command`, shown instead of the numbers of items
+    info: Option<String>,
 }
 
 //
@@ -761,10 +1550,7 @@ impl Draw for Status {
         let (screen_width, _) = canvas.size()?;
 
         let info_attr = self.theme.info();
-        let info_attr_bold = Attr {
-            effect: Effect::BOLD,
-            ..self.theme.info()
-        };
+        let info_attr_bold = self.theme.info_emphasis();
 
         let a_while_since_read = self.time_since_read > Duration::from_millis(50);
         let a_while_since_match = self.time_since_match > Duration::from_millis(50);
@@ -778,24 +1564,31 @@ impl Draw for Status {
             if self.reading && a_while_since_read {
                 // durationミリセカンド(u32)に変換している
                 let mills = (self.time_since_read.as_secs() * 1000) as u32 + self.time_since_read.subsec_millis();
-                let index = (mills / SPINNER_DURATION) % (SPINNERS.len() as u32);
-                let ch = SPINNERS[index as usize];
+                let index = (mills / SPINNER_DURATION) % (self.spinner.len() as u32);
+                let ch = self.spinner[index as usize];
                 col += canvas.put_char_with_attr(0, col, ch, self.theme.spinner())?;
             } else {
                 col += canvas.put_char_with_attr(0, col, ' ', info_attr)?;
             }
         }
 
-        // display matched/total number
-        col += canvas.print_with_attr(0, col, format!(" {}/{}", self.matched, self.total).as_ref(), info_attr)?;
+        if let Some(info) = self.info.as_ref() {
+            col += canvas.print_with_attr(0, col, format!(" {}", info).as_ref(), info_attr)?;
+        } else {
+            // display matched/total number
+            col += canvas.print_with_attr(0, col, format!(" {}/{}", self.matched, self.total).as_ref(), info_attr)?;
+            if self.items_capped {
+                col += canvas.print_with_attr(0, col, "+", info_attr)?;
+            }
 
-        // display the matcher mode
-        if !self.matcher_mode.is_empty() {
-            col += canvas.print_with_attr(0, col, format!("/{}", &self.matcher_mode).as_ref(), info_attr)?;
+            // display the matcher mode
+            if !self.matcher_mode.is_empty() {
+                col += canvas.print_with_attr(0, col, format!("/{}", &self.matcher_mode).as_ref(), info_attr)?;
+            }
         }
 
-        // display the percentage of the number of processed items
-        if self.matcher_running && a_while_since_match {
+        // display the percentage of the number of processed items, if there are any
+        if self.info.is_none() && self.matcher_running && a_while_since_match && self.total > 0 {
             col += canvas.print_with_attr(
                 0,
                 col,
@@ -804,23 +1597,88 @@ impl Draw for Status {
             )?;
         }
 
+        // the reader gave up waiting for input
+        if self.reader_timed_out {
+            col += canvas.print_with_attr(0, col, " (timeout)", info_attr)?;
+        }
+
         // selected number
         if self.multi_selection && self.selected > 0 {
             col += canvas.print_with_attr(0, col, format!(" [{}]", self.selected).as_ref(), info_attr_bold)?;
         }
 
-        // item cursor
+        // sort state (toggle-sort) and item cursor
         let line_num_str = format!(
-            " {}{}",
+            "{} {}{}",
+            match self.sorted {
+                Some(true) => " +S",
+                Some(false) => " -S",
+                None => "",
+            },
             self.current_item_idx,
             if self.matcher_running { '.' } else { ' ' }
         );
-        canvas.print_with_attr(0, screen_width - line_num_str.len(), &line_num_str, info_attr_bold)?;
+        // skipped if the screen is too narrow
+        if screen_width >= line_num_str.len() {
+            canvas.print_with_attr(0, screen_width - line_num_str.len(), &line_num_str, info_attr_bold)?;
+        }
 
         Ok(())
     }
 }
 
+/// `--info-command`: the first line of its last output, at most one run at a time
+struct InfoCommand {
+    cmd: String,
+    output: Arc<SpinLock<Option<String>>>,
+    running: Arc<AtomicBool>,
+    // the command with its placeholders filled and the environment of the last run
+    last_state: Option<(String, Vec<(&'static str, String)>)>,
+}
+
+impl InfoCommand {
+    fn new(cmd: &str) -> Self {
+        InfoCommand {
+            cmd: cmd.to_string(),
+            output: Arc::new(SpinLock::new(None)),
+            running: Arc::new(AtomicBool::new(false)),
+            last_state: None,
+        }
+    }
+
+    // the info line is redrawn with the output, and once more after the interval so that the
+    // changes made meanwhile run the command again
+    fn run(&self, cmd: &str, env: &[(&'static str, String)], tx: EventSender) {
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut command = Command::new(shell);
+        command
+            .envs(env.iter().cloned())
+            .arg("-c")
+            .arg(cmd)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null());
+
+        let output = self.output.clone();
+        let running = self.running.clone();
+        running.store(true, Ordering::Relaxed);
+        thread::spawn(move || {
+            let started = Instant::now();
+            if let Ok(out) = command.output() {
+                let text = String::from_utf8_lossy(&out.stdout);
+                let first_line = text.lines().next().unwrap_or("").to_string();
+                output.lock().replace(first_line);
+                let _ = tx.send((Event::EvActRedraw, Box::new(true)));
+            }
+
+            if let Some(rest) = INFO_COMMAND_INTERVAL.checked_sub(started.elapsed()) {
+                thread::sleep(rest);
+            }
+            running.store(false, Ordering::Relaxed);
+            let _ = tx.send((Event::EvActRedraw, Box::new(true)));
+        });
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Copy)]
 enum Direction {
     Up,
@@ -829,9 +1687,325 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    // clockwise: up -> right -> down -> left -> up
+    fn next(self) -> Self {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Copy)]
 enum ClearStrategy {
     DontClear,
     Clear,
     ClearIfNotNull,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::test::PanicSource;
+    use crate::test_canvas::TestCanvas;
+    use std::io::{BufRead, BufReader, Cursor};
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_parse_preview_size() {
+        let (direction, size, _, _, _, _, _) = Model::parse_preview("right:80");
+        assert_eq!(direction, Direction::Right);
+        assert!(matches!(size, Size::Fixed(80)));
+
+        let (direction, size, _, _, _, _, _) = Model::parse_preview("up:30%");
+        assert_eq!(direction, Direction::Up);
+        assert!(matches!(size, Size::Percent(30)));
+
+        let (_, size, _, _, _, _, _) = Model::parse_preview("wrap");
+        assert!(matches!(size, Size::Percent(50)));
+    }
+
+    #[test]
+    fn test_parse_preview_scroll() {
+        let (direction, size, _, _, _, _, scroll) = Model::parse_preview("right:50%:+{2}-5");
+        assert_eq!(direction, Direction::Right);
+        assert!(matches!(size, Size::Percent(50)));
+        assert_eq!(scroll.as_deref(), Some("+{2}-5"));
+
+        let (_, _, _, _, _, _, scroll) = Model::parse_preview("up:wrap");
+        assert_eq!(scroll, None);
+    }
+
+    #[test]
+    fn test_parse_preview_alternative() {
+        let (direction, size, border, wrap, _, _, _) = Model::parse_preview("right:60%:<80(up:30%):wrap");
+        assert_eq!(direction, Direction::Right);
+        assert!(matches!(size, Size::Percent(60)));
+        assert_eq!(border, BorderStyle::Line);
+        assert!(wrap);
+
+        let (threshold, direction, size, border) =
+            Model::parse_preview_alternative("right:60%:border-sharp:<80(up:30%)").unwrap();
+        assert_eq!(threshold, 80);
+        assert_eq!(direction, Direction::Up);
+        assert!(matches!(size, Size::Percent(30)));
+        // not given in the parens, same as the default layout
+        assert_eq!(border, BorderStyle::Sharp);
+
+        // fzf style separators
+        let (threshold, direction, size, _) = Model::parse_preview_alternative("50%,<40(up,10)").unwrap();
+        assert_eq!(threshold, 40);
+        assert_eq!(direction, Direction::Up);
+        assert!(matches!(size, Size::Fixed(10)));
+
+        assert!(Model::parse_preview_alternative("right:50%").is_none());
+    }
+
+    #[test]
+    fn test_main_window_target() {
+        // list(5), header(2), status, query
+        assert_eq!(main_window_target(0, 3, 9, 2, false, ""), MouseTarget::Selection(0));
+        assert_eq!(main_window_target(4, 3, 9, 2, false, ""), MouseTarget::Selection(4));
+        assert_eq!(main_window_target(5, 3, 9, 2, false, ""), MouseTarget::Header(0, 3));
+        assert_eq!(main_window_target(6, 0, 9, 2, false, ""), MouseTarget::Header(1, 0));
+        assert_eq!(main_window_target(7, 3, 9, 2, false, ""), MouseTarget::Other);
+        assert_eq!(main_window_target(8, 3, 9, 2, false, ""), MouseTarget::Other);
+
+        // query with the info, header(2), list(6)
+        assert_eq!(main_window_target(0, 3, 9, 2, true, "reverse"), MouseTarget::Other);
+        assert_eq!(
+            main_window_target(1, 3, 9, 2, true, "reverse"),
+            MouseTarget::Header(0, 3)
+        );
+        assert_eq!(
+            main_window_target(2, 3, 9, 2, true, "reverse"),
+            MouseTarget::Header(1, 3)
+        );
+        assert_eq!(
+            main_window_target(3, 3, 9, 2, true, "reverse"),
+            MouseTarget::Selection(0)
+        );
+
+        // reverse-list draws the list, header, status and query as the default layout does
+        assert_eq!(
+            main_window_target(0, 3, 9, 2, false, "reverse-list"),
+            MouseTarget::Selection(0)
+        );
+        assert_eq!(
+            main_window_target(5, 3, 9, 2, false, "reverse-list"),
+            MouseTarget::Header(0, 3)
+        );
+        assert_eq!(
+            main_window_target(8, 3, 9, 2, false, "reverse-list"),
+            MouseTarget::Other
+        );
+
+        // no header
+        assert_eq!(main_window_target(7, 3, 9, 0, false, ""), MouseTarget::Other);
+    }
+
+    #[test]
+    fn test_parse_preview_border() {
+        let (direction, size, border, _, _, _, _) = Model::parse_preview("right:50%:border-rounded");
+        assert_eq!(direction, Direction::Right);
+        assert!(matches!(size, Size::Percent(50)));
+        assert_eq!(border, BorderStyle::Rounded);
+
+        let (_, _, border, wrap, _, _, _) = Model::parse_preview("border-double:wrap");
+        assert_eq!(border, BorderStyle::Double);
+        assert!(wrap);
+
+        let (_, _, border, _, _, _, _) = Model::parse_preview("up:border-none");
+        assert_eq!(border, BorderStyle::None);
+
+        let (_, _, border, _, _, _, _) = Model::parse_preview("up:border-unknown");
+        assert_eq!(border, BorderStyle::Line);
+    }
+
+    fn status(total: usize) -> Status {
+        Status {
+            total,
+            matched: 0,
+            processed: 0,
+            matcher_running: true,
+            multi_selection: false,
+            selected: 0,
+            current_item_idx: 0,
+            reading: true,
+            reader_timed_out: false,
+            items_capped: false,
+            time_since_read: Duration::from_secs(1),
+            time_since_match: Duration::from_secs(1),
+            matcher_mode: "".to_string(),
+            sorted: None,
+            theme: Arc::new(ColorTheme::init_from_options(&SkimOptions::default())),
+            spinner: Arc::new(SPINNERS.to_vec()),
+            inline_info: false,
+            info: None,
+        }
+    }
+
+    #[test]
+    fn test_info_command_keeps_first_line() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let info = InfoCommand::new("echo \"$SKIM_QUERY\"; echo second");
+        info.run(&info.cmd, &[("SKIM_QUERY", "query".to_string())], tx);
+
+        let (event, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, Event::EvActRedraw);
+        assert_eq!(info.output.lock().clone(), Some("query".to_string()));
+        // no other run until the interval passed
+        assert!(info.running.load(Ordering::Relaxed));
+
+        let (event, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, Event::EvActRedraw);
+        assert!(!info.running.load(Ordering::Relaxed));
+    }
+
+    // draws on a canvas, the rows of every presented frame are kept
+    struct TestScreen {
+        canvas: Mutex<TestCanvas>,
+        frames: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl TestScreen {
+        fn new(width: usize, height: usize) -> Arc<Self> {
+            Arc::new(TestScreen {
+                canvas: Mutex::new(TestCanvas::new(width, height)),
+                frames: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn frames(&self) -> Vec<Vec<String>> {
+            self.frames.lock().unwrap().clone()
+        }
+    }
+
+    impl Screen for TestScreen {
+        fn draw(&self, draw: &dyn Draw) -> Result<()> {
+            let mut canvas = self.canvas.lock().unwrap();
+            canvas.clear()?;
+            draw.draw(&mut *canvas)
+        }
+
+        fn present(&self) -> Result<()> {
+            let rows = self.canvas.lock().unwrap().rows();
+            self.frames.lock().unwrap().push(rows);
+            Ok(())
+        }
+
+        fn pause(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn restart(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    // the model reading from `source`, started by a heart beat as `Skim::run_with` does
+    fn start_model(source: impl BufRead + Send + 'static, screen: Arc<TestScreen>) -> (Model, EventSender) {
+        let options = SkimOptions::default();
+        let reader = Reader::with_options(&options).source(Some(Box::new(source)));
+        let (tx, rx): (EventSender, EventReceiver) = channel();
+        let _ = tx.send((Event::EvHeartBeat, Box::new(true)));
+        (Model::new(rx, tx.clone(), reader, screen, &options), tx)
+    }
+
+    // aborts the model once `done` holds for its frames, or after a while if it never does
+    fn abort_when(screen: Arc<TestScreen>, tx: EventSender, done: impl Fn(&[Vec<String>]) -> bool + Send + 'static) {
+        thread::spawn(move || {
+            let start = Instant::now();
+            while !done(&screen.frames()) && start.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            let _ = tx.send((Event::EvActAbort, Box::new(true)));
+        });
+    }
+
+    #[test]
+    fn test_delimiter_is_shared() {
+        let options = SkimOptions {
+            delimiter: Some(":"),
+            preview: Some("echo {2}"),
+            ..SkimOptions::default()
+        };
+        let reader = Reader::with_options(&options);
+        let (tx, rx): (EventSender, EventReceiver) = channel();
+        let model = Model::new(rx, tx, reader, TestScreen::new(40, 5), &options);
+
+        // the reader splits `--nth`, the others `{2}`, `--accept-nth` and `--tabular` with its regex
+        let delimiter = model.reader.delimiter();
+        assert_eq!(delimiter.as_str(), ":");
+        assert!(Arc::ptr_eq(&delimiter, &model.delimiter));
+        assert!(Arc::ptr_eq(&delimiter, model.selection.get_delimiter()));
+        assert!(Arc::ptr_eq(
+            &delimiter,
+            model.previewer.as_ref().unwrap().get_delimiter()
+        ));
+    }
+
+    #[test]
+    fn test_first_frame_before_matcher_result() {
+        let screen = TestScreen::new(40, 5);
+        let (mut model, tx) = start_model(Cursor::new("a1\na2\n"), screen.clone());
+        let has_items = |frames: &[Vec<String>]| frames.iter().any(|rows| rows.iter().any(|row| row.ends_with("a1")));
+        abort_when(screen.clone(), tx, has_items);
+        assert!(model.start().is_none());
+
+        let frames = screen.frames();
+        assert!(has_items(&frames), "the items were never drawn: {:?}", frames);
+        // the prompt is painted before the first heart beat takes the matcher result
+        assert_eq!(frames[0].last().unwrap(), ">");
+        assert!(!has_items(&frames[..1]));
+    }
+
+    #[test]
+    fn test_panicked_reader_ends_the_model() {
+        assert_eq!(control_error(None, None), None);
+
+        let screen = TestScreen::new(40, 5);
+        let (mut model, tx) = start_model(BufReader::new(PanicSource), screen.clone());
+        // only a fallback, the heart beat aborts on the error long before
+        abort_when(screen, tx, |_| false);
+        let start = Instant::now();
+        assert!(model.start().is_none());
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "the model didn't stop on the error"
+        );
+        assert_eq!(model.get_error(), Some("source exploded"));
+    }
+
+    #[test]
+    fn test_draw_status_without_items() {
+        let mut canvas = TestCanvas::new(80, 1);
+        status(0).draw(&mut canvas).unwrap();
+        status(10).draw(&mut canvas).unwrap();
+    }
+
+    #[test]
+    fn test_draw_status_sort_state() {
+        let draw = |sorted| {
+            let mut canvas = TestCanvas::new(40, 1);
+            Status { sorted, ..status(10) }.draw(&mut canvas).unwrap();
+            canvas.rows().remove(0)
+        };
+        // the item cursor is aligned to the right, the sort state is one space before it
+        assert!(draw(None).ends_with("  0."));
+        assert!(draw(Some(true)).ends_with("  +S 0."));
+        assert!(draw(Some(false)).ends_with("  -S 0."));
+    }
+
+    #[test]
+    fn test_draw_status_on_narrow_screen() {
+        for width in 0..4 {
+            let mut canvas = TestCanvas::new(width, 1);
+            status(0).draw(&mut canvas).unwrap();
+        }
+    }
+}
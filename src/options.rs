@@ -1,4 +1,8 @@
+use crate::engine::FuzzyAlgorithm;
+use crate::previewer::PreviewCallback;
+use crate::util::{parse_margin, parse_max_items, parse_min_query_length};
 use derive_builder::Builder;
+use regex::Regex;
 
 #[derive(Debug, Builder)]
 #[builder(build_fn(name = "final_build"))]
@@ -6,39 +10,71 @@ use derive_builder::Builder;
 pub struct SkimOptions<'a> {
     pub bind: Vec<&'a str>,
     pub multi: bool,
+    pub pre_select_n: Option<&'a str>,
+    pub pre_select_pat: Option<&'a str>,
+    pub cursor_on_exact: bool,
     pub prompt: Option<&'a str>,
     pub cmd_prompt: Option<&'a str>,
     pub expect: Option<String>,
     pub tac: bool,
+    pub no_sort: bool,
+    pub max_matches: Option<&'a str>,
+    pub frecency: Option<&'a str>,
     pub tiebreak: Option<String>,
     pub ansi: bool,
     pub exact: bool,
     pub cmd: Option<&'a str>,
     pub interactive: bool,
+    pub disabled: bool,
+    pub min_query_length: Option<&'a str>,
     pub query: Option<&'a str>,
     pub cmd_query: Option<&'a str>,
     pub regex: bool,
+    pub literal: bool,
+    pub algorithm: Option<&'a str>,
+    pub matcher_threads: Option<&'a str>,
     pub delimiter: Option<&'a str>,
     pub nth: Option<&'a str>,
     pub with_nth: Option<&'a str>,
+    pub accept_nth: Option<&'a str>,
     pub replstr: Option<&'a str>,
     pub color: Option<&'a str>,
+    pub no_bold: bool,
     pub margin: Option<&'a str>,
     pub no_height: bool,
     pub min_height: Option<&'a str>,
     pub height: Option<&'a str>,
     pub preview: Option<&'a str>,
+    /// preview in process instead of running `preview`, for library users
+    pub preview_fn: Option<PreviewCallback>,
     pub preview_window: Option<&'a str>,
     pub reverse: bool,
     pub read0: bool,
+    pub cache: bool,
+    pub reader_timeout: Option<&'a str>,
+    pub max_items: Option<&'a str>,
+    pub exit_term_on_empty: bool,
     pub print0: bool,
     pub tabstop: Option<&'a str>,
     pub print_query: bool,
     pub print_cmd: bool,
+    pub print_transformed: bool,
     pub no_hscroll: bool,
+    pub skip_to_pattern: Option<&'a str>,
+    pub border: Option<&'a str>,
+    pub info_command: Option<&'a str>,
+    pub wrap: bool,
+    pub tabular: bool,
+    pub ellipsis: Option<&'a str>,
+    pub mouse: bool,
+    pub jump_labels: Option<&'a str>,
+    pub filepath_word: bool,
     pub inline_info: bool,
+    pub spinner: Option<&'a str>,
+    pub refresh: Option<&'a str>,
     pub header: Option<&'a str>,
     pub header_lines: usize,
+    pub header_position: Option<&'a str>,
     pub layout: &'a str,
     pub filter: &'a str,
 }
@@ -48,39 +84,70 @@ impl<'a> Default for SkimOptions<'a> {
         Self {
             bind: vec![],
             multi: false,
+            pre_select_n: None,
+            pre_select_pat: None,
+            cursor_on_exact: false,
             prompt: Some("> "),
             cmd_prompt: Some("c> "),
             expect: None,
             tac: false,
+            no_sort: false,
+            max_matches: None,
+            frecency: None,
             tiebreak: None,
             ansi: false,
             exact: false,
             cmd: None,
             interactive: false,
+            disabled: false,
+            min_query_length: None,
             query: None,
             cmd_query: None,
             regex: false,
+            literal: false,
+            algorithm: None,
+            matcher_threads: None,
             delimiter: None,
             nth: None,
             with_nth: None,
+            accept_nth: None,
             replstr: Some("{}"),
             color: None,
+            no_bold: false,
             margin: Some("0,0,0,0"),
             no_height: false,
             min_height: Some("10"),
             height: Some("100%"),
             preview: None,
+            preview_fn: None,
             preview_window: Some("right:50%"),
             reverse: false,
             read0: false,
+            cache: false,
+            reader_timeout: None,
+            max_items: None,
+            exit_term_on_empty: false,
             print0: false,
             tabstop: None,
             print_query: false,
             print_cmd: false,
+            print_transformed: false,
             no_hscroll: false,
+            skip_to_pattern: None,
+            border: None,
+            info_command: None,
+            wrap: false,
+            tabular: false,
+            ellipsis: None,
+            mouse: false,
+            jump_labels: None,
+            filepath_word: false,
             inline_info: false,
+            spinner: None,
+            refresh: None,
             header: None,
             header_lines: 0,
+            header_position: None,
             layout: "",
             filter: "",
         }
@@ -97,6 +164,94 @@ impl<'a> SkimOptionsBuilder<'a> {
             self.layout = Some("reverse");
         }
 
+        if let Some(Some(margin)) = self.margin {
+            parse_margin(margin)?;
+        }
+
+        if let Some(Some(algorithm)) = self.algorithm {
+            FuzzyAlgorithm::from_str(algorithm)
+                .ok_or_else(|| format!("invalid algorithm: {:?}, expect v1 or v2", algorithm))?;
+        }
+
+        if let Some(Some(max_matches)) = self.max_matches {
+            max_matches
+                .parse::<usize>()
+                .map_err(|_| format!("invalid max matches: {:?}, expect a number", max_matches))?;
+        }
+
+        if let Some(Some(threads)) = self.matcher_threads {
+            threads
+                .parse::<usize>()
+                .map_err(|_| format!("invalid matcher threads: {:?}, expect a number", threads))?;
+        }
+
+        if let Some(Some(timeout)) = self.reader_timeout {
+            timeout
+                .parse::<u64>()
+                .map_err(|_| format!("invalid reader timeout: {:?}, expect milliseconds", timeout))?;
+        }
+
+        if let Some(Some(max_items)) = self.max_items {
+            parse_max_items(max_items)?;
+        }
+
+        if let Some(Some(refresh)) = self.refresh {
+            refresh
+                .parse::<u64>()
+                .map_err(|_| format!("invalid refresh rate: {:?}, expect milliseconds", refresh))?;
+        }
+
+        if let Some(Some(min_query_length)) = self.min_query_length {
+            parse_min_query_length(min_query_length)?;
+        }
+
+        if let Some(Some(indices)) = self.pre_select_n {
+            for index in indices.split(',') {
+                index
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid pre-select index: {:?}, expect 0,2,5", index))?;
+            }
+        }
+
+        if let Some(Some(pattern)) = self.pre_select_pat {
+            Regex::new(pattern).map_err(|err| format!("invalid pre-select pattern: {}", err))?;
+        }
+
+        if let Some(Some(position)) = self.header_position {
+            if position != "above" && position != "below" {
+                return Err(format!(
+                    "invalid header position: {:?}, expect above or below",
+                    position
+                ));
+            }
+        }
+
         self.final_build()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_max_matches() {
+        assert!(SkimOptionsBuilder::default().max_matches(Some("10")).build().is_ok());
+        assert!(SkimOptionsBuilder::default().max_matches(Some("-1")).build().is_err());
+        assert!(SkimOptionsBuilder::default().max_matches(Some("ten")).build().is_err());
+    }
+
+    #[test]
+    fn test_build_matcher_threads() {
+        assert!(SkimOptionsBuilder::default().matcher_threads(Some("4")).build().is_ok());
+        assert!(SkimOptionsBuilder::default()
+            .matcher_threads(Some("-1"))
+            .build()
+            .is_err());
+        assert!(SkimOptionsBuilder::default()
+            .matcher_threads(Some("four"))
+            .build()
+            .is_err());
+    }
+}
@@ -11,6 +11,10 @@ const ORDERED_SIZE: usize = 300;
 pub struct OrderedVec<T: Send> {
     vec: Vec<T>,
     compare: CompareFunction<T>,
+    // keep only the first `max_len` items, 0 for no limit
+    max_len: usize,
+    // whether items were dropped because of `max_len`
+    truncated: bool,
 }
 
 impl<T: Send> OrderedVec<T> {
@@ -18,11 +22,34 @@ impl<T: Send> OrderedVec<T> {
         OrderedVec {
             vec: Vec::with_capacity(ORDERED_SIZE),
             compare,
+            max_len: 0,
+            truncated: false,
         }
     }
 
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
     pub fn append_ordered(&mut self, mut items: Vec<T>) {
         self.vec.append(&mut items);
+        if self.max_len > 0 && self.vec.len() > self.max_len {
+            // only the first `max_len` need to be sorted
+            self.vec.select_nth_unstable_by(self.max_len, self.compare.as_ref());
+            self.vec.truncate(self.max_len);
+            self.truncated = true;
+        }
+        self.vec.par_sort_unstable_by(self.compare.as_ref());
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// sort the items with the new compare function, the new items will be sorted with it too
+    pub fn set_compare(&mut self, compare: CompareFunction<T>) {
+        self.compare = compare;
         self.vec.par_sort_unstable_by(self.compare.as_ref());
     }
 
@@ -36,6 +63,7 @@ impl<T: Send> OrderedVec<T> {
 
     pub fn clear(&mut self) {
         self.vec.clear();
+        self.truncated = false;
     }
 
     pub fn is_empty(&self) -> bool {
@@ -46,3 +74,30 @@ impl<T: Send> OrderedVec<T> {
         Box::new(self.vec.iter())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_len() {
+        // larger numbers first, appended in batches like the matcher does
+        let mut vec = OrderedVec::new(Box::new(|a: &usize, b: &usize| b.cmp(a))).max_len(100);
+        let items: Vec<usize> = (0..100_000).map(|i| (i * 7919) % 100_000).collect();
+        for batch in items.chunks(3000) {
+            vec.append_ordered(batch.to_vec());
+            assert!(vec.len() <= 100);
+        }
+
+        assert!(vec.is_truncated());
+        let kept: Vec<usize> = vec.iter().cloned().collect();
+        let expected: Vec<usize> = (99_900..100_000).rev().collect();
+        assert_eq!(kept, expected);
+
+        vec.clear();
+        assert!(!vec.is_truncated());
+        vec.append_ordered(vec![1, 3, 2]);
+        assert_eq!(vec.iter().cloned().collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert!(!vec.is_truncated());
+    }
+}
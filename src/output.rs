@@ -3,8 +3,23 @@ use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct SkimOutput {
+    /// aborted by `abort-print-query`, nothing is selected but the query is kept
+    pub is_abort: bool,
     pub accept_key: Option<String>,
     pub query: String,
     pub cmd: String,
     pub selected_items: Vec<Arc<Item>>,
+    /// the matched positions of `selected_items`, in the same order
+    pub selected_matches: Vec<MatchResult>,
+    /// the `--accept-nth` fields of `selected_items`, in the same order, if the option is given
+    pub selected_fields: Option<Vec<String>>,
+}
+
+/// The characters of a selected item that matched the final query, for frontends that want to
+/// highlight them the way skim does.
+#[derive(Debug)]
+pub struct MatchResult {
+    pub item: Arc<Item>,
+    /// char indices into the displayed text (`Item::get_text`), empty if the item does not match
+    pub matched_indices: Vec<usize>,
 }
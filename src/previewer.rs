@@ -1,13 +1,18 @@
-use crate::ansi::AnsiString;
+use crate::ansi::{ANSIParser, AnsiString};
 use crate::event::{Event, EventArg, EventHandler, UpdateScreen};
+use crate::field::parse_delimiter;
 use crate::item::Item;
 use crate::spinlock::SpinLock;
-use crate::util::{inject_command, InjectContext};
+use crate::theme::{ColorTheme, DEFAULT_THEME};
+use crate::util::{command_env, eval_scroll_offset, inject_command, InjectContext};
 use derive_builder::Builder;
 use nix::libc;
 use regex::Regex;
+use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::env;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
@@ -15,9 +20,32 @@ use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
 use tuikit::prelude::*;
+use unicode_width::UnicodeWidthStr;
 
 const TAB_STOP: usize = 8;
-const DELIMITER_STR: &str = r"[\t\n ]+";
+
+/// Renders the preview of an item in process, instead of running the preview command
+#[derive(Clone)]
+pub struct PreviewCallback(Arc<dyn Fn(&Item) -> String + Send + Sync>);
+
+impl PreviewCallback {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&Item) -> String + Send + Sync + 'static,
+    {
+        PreviewCallback(Arc::new(callback))
+    }
+
+    fn call(&self, item: &Item) -> String {
+        (self.0)(item)
+    }
+}
+
+impl fmt::Debug for PreviewCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PreviewCallback")
+    }
+}
 
 pub struct Previewer {
     tx_preview: Sender<(Event, PreviewInput)>,
@@ -28,12 +56,23 @@ pub struct Previewer {
     hscroll_offset: usize,
     vscroll_offset: usize,
     wrap: bool,
+    tabstop: usize,
+    // keep the last line in view as the output grows (`--preview-window follow`)
+    follow: bool,
+    // whether the view is pinned to the bottom now, i.e. `follow` and the user didn't scroll up
+    following: bool,
+    // the vscroll offset that shows the last line, as of the last draw
+    bottom_offset: AtomicUsize,
+    // the scroll expression of `--preview-window`, e.g. `+{2}-5`
+    scroll: Option<String>,
 
     prev_item: Option<Arc<Item>>,
     prev_query: Option<String>,
     prev_cmd_query: Option<String>,
     preview_cmd: Option<String>,
-    delimiter: Regex,
+    preview_fn: Option<PreviewCallback>,
+    delimiter: Arc<Regex>,
+    theme: Arc<ColorTheme>,
     thread_previewer: Option<JoinHandle<()>>,
 }
 
@@ -46,8 +85,14 @@ impl Previewer {
         let (tx_preview, rx_preview) = channel();
         let content_clone = content_lines.clone();
         let thread_previewer = thread::spawn(move || {
-            run(rx_preview, move |lines| {
-                *content_clone.lock() = lines;
+            run(rx_preview, move |lines, append| {
+                let mut content = content_clone.lock();
+                if append {
+                    content.extend(lines);
+                } else {
+                    *content = lines;
+                }
+                drop(content);
                 callback();
             })
         });
@@ -61,12 +106,19 @@ impl Previewer {
             hscroll_offset: 0,
             vscroll_offset: 0,
             wrap: false,
+            tabstop: TAB_STOP,
+            follow: false,
+            following: false,
+            bottom_offset: AtomicUsize::new(0),
+            scroll: None,
 
             prev_item: None,
             prev_query: None,
             prev_cmd_query: None,
             preview_cmd,
-            delimiter: Regex::new(DELIMITER_STR).unwrap(),
+            preview_fn: None,
+            delimiter: parse_delimiter(None),
+            theme: Arc::new(*DEFAULT_THEME),
             thread_previewer: Some(thread_previewer),
         }
     }
@@ -76,11 +128,49 @@ impl Previewer {
         self
     }
 
-    pub fn delimiter(mut self, delimiter: Regex) -> Self {
+    pub fn tabstop(mut self, tabstop: usize) -> Self {
+        self.tabstop = max(1, tabstop);
+        self
+    }
+
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self.following = follow;
+        self
+    }
+
+    /// scroll to the line given by the expression for each item
+    pub fn scroll(mut self, scroll: Option<String>) -> Self {
+        self.scroll = scroll;
+        self
+    }
+
+    /// preview with the callback instead of the command
+    pub fn preview_fn(mut self, preview_fn: Option<PreviewCallback>) -> Self {
+        self.preview_fn = preview_fn;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: Arc<Regex>) -> Self {
         self.delimiter = delimiter;
         self
     }
 
+    pub fn theme(mut self, theme: Arc<ColorTheme>) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn get_delimiter(&self) -> &Arc<Regex> {
+        &self.delimiter
+    }
+
+    /// the number of lines the preview window had on the last draw
+    pub fn get_height(&self) -> usize {
+        self.height.load(Ordering::Relaxed)
+    }
+
     pub fn on_item_change(
         &mut self,
         new_item: impl Into<Option<Arc<Item>>>,
@@ -122,6 +212,24 @@ impl Previewer {
         self.prev_query = new_query;
         self.prev_cmd_query = new_cmd_query;
 
+        self.hscroll_offset = 0;
+        self.vscroll_offset = match (self.scroll.as_ref(), self.prev_item.as_ref()) {
+            (Some(scroll), Some(item)) => {
+                eval_scroll_offset(scroll, &self.delimiter, &item.get_output_text(), self.get_height())
+            }
+            _ => 0,
+        };
+        self.following = self.follow;
+
+        if let Some(preview_fn) = self.preview_fn.as_ref() {
+            let request = PreviewInput {
+                callback: Some((preview_fn.clone(), self.prev_item.clone())),
+                ..PreviewInput::default()
+            };
+            let _ = self.tx_preview.send((Event::EvPreviewRequest, request));
+            return;
+        }
+
         let cmd = self.preview_cmd.as_ref().expect("previewer: invalid preview command");
         let current_selection = self
             .prev_item
@@ -143,21 +251,38 @@ impl Previewer {
 
         let columns = self.width.load(Ordering::Relaxed);
         let lines = self.height.load(Ordering::Relaxed);
-        let request = PreviewInput { cmd, columns, lines };
+        let env = command_env(context, Some(lines));
+        let request = PreviewInput {
+            cmd,
+            columns,
+            lines,
+            env,
+            error_attr: self.theme.error(),
+            callback: None,
+        };
         let _ = self.tx_preview.send((Event::EvPreviewRequest, request));
-
-        self.hscroll_offset = 0;
-        self.vscroll_offset = 0;
     }
 
     fn act_scroll_down(&mut self, diff: i32) {
+        let bottom_offset = self.bottom_offset.load(Ordering::Relaxed);
+        if self.following {
+            self.vscroll_offset = bottom_offset;
+        }
+
         if diff > 0 {
             self.vscroll_offset += diff as usize;
         } else {
             self.vscroll_offset -= min((-diff) as usize, self.vscroll_offset);
         }
 
-        self.vscroll_offset = min(self.vscroll_offset, max(self.content_lines.lock().len(), 1) - 1);
+        // stop once the last line is at the bottom, the content may have grown since the last draw
+        let last_line = max(self.content_lines.lock().len(), 1) - 1;
+        self.vscroll_offset = min(self.vscroll_offset, min(bottom_offset, last_line));
+
+        // scrolling up stops following, scrolling back to the bottom resumes it
+        if self.follow {
+            self.following = self.vscroll_offset >= bottom_offset;
+        }
     }
 
     fn act_scroll_right(&mut self, diff: i32) {
@@ -175,12 +300,7 @@ impl Previewer {
 
 impl Drop for Previewer {
     fn drop(&mut self) {
-        let request = PreviewInput {
-            cmd: "".to_string(),
-            columns: 0,
-            lines: 0,
-        };
-        let _ = self.tx_preview.send((Event::EvActAbort, request));
+        let _ = self.tx_preview.send((Event::EvActAbort, PreviewInput::default()));
         self.thread_previewer.take().map(|handle| handle.join());
     }
 }
@@ -231,18 +351,27 @@ impl Draw for Previewer {
 
         let content = self.content_lines.lock();
 
+        let bottom_offset = get_bottom_offset(&content, screen_width, screen_height, self.wrap);
+        self.bottom_offset.store(bottom_offset, Ordering::Relaxed);
+        let vscroll_offset = if self.following {
+            bottom_offset
+        } else {
+            self.vscroll_offset
+        };
+
         let mut printer = PrinterBuilder::default()
             .width(screen_width)
             .height(screen_height)
-            .skip_rows(self.vscroll_offset)
+            .skip_rows(vscroll_offset)
             .skip_cols(self.hscroll_offset)
             .wrap(self.wrap)
+            .tabstop(self.tabstop)
             .build()
             .unwrap();
         printer.print_lines(canvas, &content);
 
         // print the vscroll info
-        let status = format!("{}/{}", self.vscroll_offset + 1, content.len());
+        let status = format!("{}/{}", vscroll_offset + 1, content.len());
         let col = max(status.len() + 1, self.width.load(Ordering::Relaxed)) - status.len() - 1;
         canvas.print_with_attr(
             0,
@@ -258,11 +387,17 @@ impl Draw for Previewer {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct PreviewInput {
     pub cmd: String,
     pub lines: usize,
     pub columns: usize,
+    // SKIM_QUERY, etc.
+    pub env: Vec<(&'static str, String)>,
+    // the attribute of the banner shown when `cmd` fails
+    pub error_attr: Attr,
+    // preview the item with the callback instead of `cmd`
+    pub callback: Option<(PreviewCallback, Option<Arc<Item>>)>,
 }
 
 struct PreviewThread {
@@ -282,7 +417,7 @@ impl PreviewThread {
 
 pub fn run<C>(rx_preview: Receiver<(Event, PreviewInput)>, on_return: C)
 where
-    C: Fn(Vec<AnsiString>) + Send + Sync + 'static,
+    C: Fn(Vec<AnsiString>, bool) + Send + Sync + 'static,
 {
     let callback = Arc::new(on_return);
     let mut preview_thread: Option<PreviewThread> = None;
@@ -304,6 +439,14 @@ where
             new_prv = new_prv1;
         }
 
+        if let Some((preview_fn, item)) = new_prv.callback {
+            let text = item.map(|item| preview_fn.call(&item)).unwrap_or_default();
+            let mut parser = ANSIParser::default().strip_unsupported(true);
+            let lines = text.lines().map(|line| parser.parse_ansi(line)).collect();
+            callback(lines, false);
+            continue;
+        }
+
         let cmd = &new_prv.cmd;
         if cmd == "" {
             continue;
@@ -313,6 +456,7 @@ where
         let spawned = Command::new(shell)
             .env("LINES", new_prv.lines.to_string())
             .env("COLUMNS", new_prv.columns.to_string())
+            .envs(new_prv.env.iter().cloned())
             .arg("-c")
             .arg(&cmd)
             .stdout(Stdio::piped())
@@ -322,7 +466,7 @@ where
         match spawned {
             Err(err) => {
                 let astdout = AnsiString::from_str(format!("Failed to spawn: {} / {}", cmd, err).as_str());
-                callback(vec![astdout]);
+                callback(vec![astdout], false);
                 preview_thread = None;
             }
             Ok(spawned) => {
@@ -330,11 +474,10 @@ where
                 let stopped = Arc::new(AtomicBool::new(false));
                 let stopped_clone = stopped.clone();
                 let callback_clone = callback.clone();
+                let error_attr = new_prv.error_attr;
                 let thread = thread::spawn(move || {
-                    wait(spawned, move |lines| {
-                        stopped_clone.store(true, Ordering::SeqCst);
-                        callback_clone(lines);
-                    })
+                    wait(spawned, error_attr, move |lines, append| callback_clone(lines, append));
+                    stopped_clone.store(true, Ordering::SeqCst);
                 });
                 preview_thread = Some(PreviewThread { pid, thread, stopped });
             }
@@ -342,27 +485,101 @@ where
     }
 }
 
-fn wait<C>(spawned: std::process::Child, callback: C)
+// Read the output as it comes so that commands like `tail -f` show up. The first lines replace
+// the previous content, the following ones are appended (`append` is true). A failed command
+// shows a banner in `error_attr` and its stderr instead.
+fn wait<C>(mut spawned: std::process::Child, error_attr: Attr, callback: C)
 where
-    C: Fn(Vec<AnsiString>),
+    C: Fn(Vec<AnsiString>, bool),
 {
-    let output = spawned.wait_with_output();
+    // drain stderr aside, a full stderr pipe would block the command
+    let stderr_reader = spawned.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        })
+    });
 
-    if output.is_err() {
-        return;
+    // share the parser between lines, colors may span multiple lines (e.g. `git diff --color`)
+    let mut parser = ANSIParser::default().strip_unsupported(true);
+    let mut append = false;
+    if let Some(stdout) = spawned.stdout.take() {
+        let mut stdout = BufReader::new(stdout);
+        let mut pending = Vec::new();
+        loop {
+            let consumed = match stdout.fill_buf() {
+                Ok(buf) if !buf.is_empty() => {
+                    pending.extend_from_slice(buf);
+                    buf.len()
+                }
+                _ => break,
+            };
+            stdout.consume(consumed);
+
+            // only complete lines, the rest waits for the next read
+            let complete = match pending.iter().rposition(|&b| b == b'\n') {
+                Some(pos) => pos + 1,
+                None => continue,
+            };
+            let chunk: Vec<u8> = pending.drain(..complete).collect();
+            let lines = String::from_utf8_lossy(&chunk)
+                .lines()
+                .map(|line| parser.parse_ansi(line))
+                .collect();
+            callback(lines, append);
+            append = true;
+        }
+
+        if !pending.is_empty() {
+            let line = parser.parse_ansi(&String::from_utf8_lossy(&pending));
+            callback(vec![line], append);
+            append = true;
+        }
     }
 
-    let output = output.unwrap();
+    let status = spawned.wait();
+    let stderr = stderr_reader.and_then(|reader| reader.join().ok()).unwrap_or_default();
+
+    match status.ok().and_then(|status| status.code()) {
+        // no output at all, still clear the previous one
+        Some(0) if !append => callback(Vec::new(), false),
+        Some(0) => {}
+        // show why it failed instead of a blank window, e.g. a typo in the command
+        Some(code) => {
+            let banner = format!("[preview command failed with exit code {}]", code);
+            let mut lines = vec![AnsiString::new(banner.clone(), vec![(error_attr, Cow::Owned(banner))])];
+
+            let mut parser = ANSIParser::default().strip_unsupported(true);
+            lines.extend(
+                String::from_utf8_lossy(&stderr)
+                    .lines()
+                    .map(|line| parser.parse_ansi(line)),
+            );
+            callback(lines, false);
+        }
+        // killed, i.e. replaced by the preview of another item
+        None => {}
+    }
+}
 
-    // Capture stderr in case users want to debug ...
-    let out_str = String::from_utf8_lossy(if output.status.success() {
-        &output.stdout
-    } else {
-        &output.stderr
-    });
+// The offset (in lines) to skip so that the last line is at the bottom of the view. Wrapped lines
+// take as many rows as needed to fit the width.
+fn get_bottom_offset(content: &[AnsiString], width: usize, height: usize, wrap: bool) -> usize {
+    let mut rows = 0;
+    for (idx, line) in content.iter().enumerate().rev() {
+        rows += if wrap && width > 0 {
+            (max(line.get_stripped().width(), 1) - 1) / width + 1
+        } else {
+            1
+        };
 
-    let lines = out_str.lines().map(AnsiString::from_str).collect();
-    callback(lines);
+        if rows > height {
+            // the last line is shown even if it doesn't fit
+            return min(idx + 1, content.len() - 1);
+        }
+    }
+    0
 }
 
 #[derive(Builder, Default, Debug)]
@@ -375,6 +592,8 @@ struct Printer {
     skip_rows: usize,
     skip_cols: usize,
     wrap: bool,
+    #[builder(default = "TAB_STOP")]
+    tabstop: usize,
     width: usize,
     height: usize,
 }
@@ -416,7 +635,7 @@ impl Printer {
             '\n' | '\r' | '\0' => {}
             '\t' => {
                 // handle tabstop
-                let rest = TAB_STOP - self.col % TAB_STOP;
+                let rest = self.tabstop - self.col % self.tabstop;
                 let rest = min(rest, max(self.col, self.width) - self.col);
                 for _ in 0..rest {
                     self.print_char_raw(canvas, ' ', attr)?;
@@ -460,3 +679,201 @@ impl Printer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::SkimOptions;
+    use crate::test_canvas::TestCanvas;
+    use std::cell::RefCell;
+
+    fn lines(texts: &[&str]) -> Vec<AnsiString> {
+        texts.iter().map(|text| AnsiString::from_str(text)).collect()
+    }
+
+    fn print_with_tabstop(text: &str, tabstop: usize) -> Vec<(usize, char)> {
+        let mut canvas = TestCanvas::new(80, 10);
+        let mut printer = PrinterBuilder::default()
+            .width(80)
+            .height(10)
+            .tabstop(tabstop)
+            .build()
+            .unwrap();
+        printer.print_lines(&mut canvas, &lines(&[text]));
+        canvas.row(0).chars().enumerate().filter(|&(_, ch)| ch != ' ').collect()
+    }
+
+    #[test]
+    fn test_tabstop() {
+        assert_eq!(print_with_tabstop("a\tb", 8), vec![(0, 'a'), (8, 'b')]);
+        assert_eq!(print_with_tabstop("a\tb", 4), vec![(0, 'a'), (4, 'b')]);
+        assert_eq!(
+            print_with_tabstop("abcd\tb", 4),
+            vec![(0, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (8, 'b')]
+        );
+        // wide chars take two columns
+        assert_eq!(print_with_tabstop("中\tb", 4), vec![(0, '中'), (4, 'b')]);
+        assert_eq!(print_with_tabstop("中国\tb", 4), vec![(0, '中'), (2, '国'), (8, 'b')]);
+    }
+
+    #[test]
+    fn test_get_bottom_offset() {
+        let content = lines(&["1", "2", "3", "4", "5"]);
+        assert_eq!(get_bottom_offset(&content, 10, 3, false), 2);
+        assert_eq!(get_bottom_offset(&content, 10, 5, false), 0);
+        assert_eq!(get_bottom_offset(&content, 10, 10, false), 0);
+        assert_eq!(get_bottom_offset(&[], 10, 3, false), 0);
+
+        // the last line takes 3 rows when wrapped
+        let content = lines(&["1", "2", "3", "aaaaaaaaaaaaaaaaaaaaaaaaa"]);
+        assert_eq!(get_bottom_offset(&content, 10, 4, false), 0);
+        assert_eq!(get_bottom_offset(&content, 10, 4, true), 2);
+        assert_eq!(get_bottom_offset(&content, 10, 2, true), 3);
+    }
+
+    #[test]
+    fn test_scroll_is_clamped_to_content() {
+        let mut previewer = Previewer::new(None, || {});
+        *previewer.content_lines.lock() = lines(&["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+        previewer.height.store(4, Ordering::Relaxed);
+        previewer.bottom_offset.store(6, Ordering::Relaxed);
+
+        let arg: EventArg = Box::new(true);
+        previewer.handle(Event::EvActPreviewDown, &arg);
+        assert_eq!(previewer.vscroll_offset, 1);
+        previewer.handle(Event::EvActPreviewPageDown, &arg);
+        assert_eq!(previewer.vscroll_offset, 5);
+        previewer.handle(Event::EvActPreviewPageDown, &arg);
+        assert_eq!(previewer.vscroll_offset, 6);
+        previewer.handle(Event::EvActPreviewPageUp, &arg);
+        assert_eq!(previewer.vscroll_offset, 2);
+        previewer.handle(Event::EvActPreviewPageUp, &arg);
+        assert_eq!(previewer.vscroll_offset, 0);
+        previewer.handle(Event::EvActPreviewUp, &arg);
+        assert_eq!(previewer.vscroll_offset, 0);
+    }
+
+    #[test]
+    fn test_scroll_up_stops_following() {
+        let mut previewer = Previewer::new(None, || {}).follow(true);
+        *previewer.content_lines.lock() = lines(&["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+        previewer.height.store(4, Ordering::Relaxed);
+        previewer.bottom_offset.store(6, Ordering::Relaxed);
+
+        let arg: EventArg = Box::new(true);
+        previewer.handle(Event::EvActPreviewUp, &arg);
+        assert!(!previewer.following);
+        assert_eq!(previewer.vscroll_offset, 5);
+        previewer.handle(Event::EvActPreviewPageDown, &arg);
+        assert!(previewer.following);
+    }
+
+    fn preview_with_fn(item: Option<Arc<Item>>) -> Vec<(Vec<String>, bool)> {
+        let preview_fn = PreviewCallback::new(|item| format!("preview of\n\x1b[31m{}\x1b[m", item.get_output_text()));
+        let (tx, rx) = channel();
+        let request = PreviewInput {
+            callback: Some((preview_fn, item)),
+            ..PreviewInput::default()
+        };
+        let _ = tx.send((Event::EvPreviewRequest, request));
+        drop(tx);
+
+        let calls = Arc::new(SpinLock::new(Vec::new()));
+        let calls_clone = calls.clone();
+        run(rx, move |lines, append| {
+            let texts: Vec<_> = lines.iter().map(|line| line.get_stripped().to_string()).collect();
+            calls_clone.lock().push((texts, append));
+        });
+        let calls = calls.lock();
+        calls.clone()
+    }
+
+    #[test]
+    fn test_preview_fn() {
+        let delimiter = parse_delimiter(None);
+        let item = Arc::new(Item::new("a b".into(), false, &[], &[], &delimiter, (0, 0)));
+        assert_eq!(
+            preview_with_fn(Some(item)),
+            vec![(vec!["preview of".to_string(), "a b".to_string()], false)]
+        );
+
+        // no item, clear the preview
+        assert_eq!(preview_with_fn(None), vec![(Vec::<String>::new(), false)]);
+    }
+
+    #[test]
+    fn test_wait_streams_output() {
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg("echo 1; echo 2; sleep 0.2; echo 3; printf 4")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let calls = RefCell::new(Vec::new());
+        wait(spawned, Attr::default(), |lines, append| {
+            let texts: Vec<_> = lines.iter().map(|line| line.get_stripped().to_string()).collect();
+            calls.borrow_mut().push((texts, append));
+        });
+
+        let calls = calls.into_inner();
+        assert!(calls.len() >= 3);
+        assert!(!calls[0].1);
+        assert!(calls[1..].iter().all(|(_, append)| *append));
+        let texts: Vec<_> = calls.into_iter().flat_map(|(texts, _)| texts).collect();
+        assert_eq!(texts, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_wait_shows_stderr_on_failure() {
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg("echo out; echo err >&2; exit 1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let content = RefCell::new(Vec::new());
+        wait(spawned, Attr::default(), |lines, append| {
+            let mut content = content.borrow_mut();
+            if !append {
+                content.clear();
+            }
+            content.extend(lines.iter().map(|line| line.get_stripped().to_string()));
+        });
+        assert_eq!(
+            content.into_inner(),
+            vec!["[preview command failed with exit code 1]", "err"]
+        );
+    }
+
+    #[test]
+    fn test_wait_shows_missing_command() {
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg("skim-no-such-command {}")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let options = SkimOptions {
+            color: Some("error:1:underline"),
+            ..SkimOptions::default()
+        };
+        let theme = ColorTheme::init_from_options(&options);
+        let content = RefCell::new(Vec::new());
+        wait(spawned, theme.error(), |lines, _| content.borrow_mut().extend(lines));
+        let content = content.into_inner();
+        assert_eq!(content[0].get_stripped(), "[preview command failed with exit code 127]");
+        // drawn in the error color of the theme
+        assert!(content[0].iter().all(|(_, attr)| attr == theme.error()));
+        assert_eq!(theme.error().fg, Color::AnsiValue(1));
+        assert_eq!(theme.error().effect, Effect::UNDERLINE);
+        assert!(content[1..]
+            .iter()
+            .any(|line| line.get_stripped().contains("skim-no-such-command")));
+    }
+}
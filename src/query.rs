@@ -19,11 +19,16 @@ pub struct Query {
     query_after: Vec<char>,
     yank: Vec<char>,
 
+    // the other query buffer, exchanged with the current one by `swap-query`
+    alt_query_before: Vec<char>,
+    alt_query_after: Vec<char>,
+
     mode: QueryMode,
     base_cmd: String,
     replstr: String,
     query_prompt: String,
     cmd_prompt: String,
+    filepath_word: bool,
 
     theme: Arc<ColorTheme>,
 }
@@ -37,11 +42,14 @@ impl Query {
             query_before: Vec::new(),
             query_after: Vec::new(),
             yank: Vec::new(),
+            alt_query_before: Vec::new(),
+            alt_query_after: Vec::new(),
             mode: QueryMode::QUERY,
             base_cmd: String::new(),
             replstr: "{}".to_string(),
             query_prompt: "> ".to_string(),
             cmd_prompt: "c> ".to_string(),
+            filepath_word: false,
             theme: Arc::new(*DEFAULT_THEME),
         }
     }
@@ -69,6 +77,11 @@ impl Query {
         self
     }
 
+    pub fn filepath_word(mut self, filepath_word: bool) -> Self {
+        self.filepath_word = filepath_word;
+        self
+    }
+
     pub fn build(self) -> Self {
         self
     }
@@ -103,6 +116,8 @@ impl Query {
         if let Some(cmd_prompt) = options.cmd_prompt {
             self.cmd_prompt = cmd_prompt.to_string();
         }
+
+        self.filepath_word = options.filepath_word;
     }
 
     pub fn get_query(&self) -> String {
@@ -235,17 +250,16 @@ impl Query {
     }
 
     pub fn act_backward_kill_word(&mut self) {
+        let filepath_word = self.filepath_word;
         let mut yank = Vec::new();
 
         {
             let (before, _) = self.get_ref();
-            // kill things other than alphanumeric
-            while !before.is_empty() && !before[before.len() - 1].is_alphanumeric() {
+            // kill the separators, then the word before them
+            while !before.is_empty() && !is_word_char(before[before.len() - 1], filepath_word) {
                 yank.push(before.pop().unwrap());
             }
-
-            // kill word until whitespace (not alphanumeric)
-            while !before.is_empty() && before[before.len() - 1].is_alphanumeric() {
+            while !before.is_empty() && is_word_char(before[before.len() - 1], filepath_word) {
                 yank.push(before.pop().unwrap());
             }
         }
@@ -254,17 +268,16 @@ impl Query {
     }
 
     pub fn act_kill_word(&mut self) {
+        let filepath_word = self.filepath_word;
         let mut yank = Vec::new();
 
         {
+            // `after` is reversed, its last char is the one under the cursor
             let (_, after) = self.get_ref();
-
-            // kill non alphanumeric
-            while !after.is_empty() && !after[after.len() - 1].is_alphanumeric() {
+            while !after.is_empty() && !is_word_char(after[after.len() - 1], filepath_word) {
                 yank.push(after.pop().unwrap());
             }
-            // kill alphanumeric
-            while !after.is_empty() && after[after.len() - 1].is_alphanumeric() {
+            while !after.is_empty() && is_word_char(after[after.len() - 1], filepath_word) {
                 yank.push(after.pop().unwrap());
             }
         }
@@ -272,36 +285,26 @@ impl Query {
     }
 
     pub fn act_backward_word(&mut self) {
+        let filepath_word = self.filepath_word;
         let (before, after) = self.get_ref();
-        // skip whitespace
-        while !before.is_empty() && !before[before.len() - 1].is_alphanumeric() {
-            if let Some(ch) = before.pop() {
-                after.push(ch);
-            }
+        // move over the separators, then to the beginning of the word
+        while !before.is_empty() && !is_word_char(before[before.len() - 1], filepath_word) {
+            after.push(before.pop().unwrap());
         }
-
-        // backword char until whitespace
-        while !before.is_empty() && before[before.len() - 1].is_alphanumeric() {
-            if let Some(ch) = before.pop() {
-                after.push(ch);
-            }
+        while !before.is_empty() && is_word_char(before[before.len() - 1], filepath_word) {
+            after.push(before.pop().unwrap());
         }
     }
 
     pub fn act_forward_word(&mut self) {
+        let filepath_word = self.filepath_word;
         let (before, after) = self.get_ref();
-        // backword char until whitespace
-        // skip whitespace
-        while !after.is_empty() && after[after.len() - 1].is_whitespace() {
-            if let Some(ch) = after.pop() {
-                before.push(ch);
-            }
+        // move over the separators, then to the end of the word
+        while !after.is_empty() && !is_word_char(after[after.len() - 1], filepath_word) {
+            before.push(after.pop().unwrap());
         }
-
-        while !after.is_empty() && !after[after.len() - 1].is_whitespace() {
-            if let Some(ch) = after.pop() {
-                before.push(ch);
-            }
+        while !after.is_empty() && is_word_char(after[after.len() - 1], filepath_word) {
+            before.push(after.pop().unwrap());
         }
     }
 
@@ -324,16 +327,23 @@ impl Query {
     }
 
     pub fn act_kill_line(&mut self) {
-        let after = mem::replace(&mut self.query_after, Vec::new());
-        self.save_yank(after, false);
+        let (_, after) = self.get_ref();
+        let after = mem::take(after);
+        self.save_yank(after.into_iter().rev().collect(), false);
     }
 
     pub fn act_line_discard(&mut self) {
-        let before = mem::replace(&mut self.query_before, Vec::new());
-        self.query_before = Vec::new();
+        let (before, _) = self.get_ref();
+        let before = mem::take(before);
         self.save_yank(before, false);
     }
 
+    pub fn act_clear_query(&mut self) {
+        let (before, after) = self.get_ref();
+        before.clear();
+        after.clear();
+    }
+
     pub fn act_yank(&mut self) {
         let yank = mem::replace(&mut self.yank, Vec::new());
         for &c in &yank {
@@ -342,6 +352,11 @@ impl Query {
         let _ = mem::replace(&mut self.yank, yank);
     }
 
+    pub fn act_swap_query(&mut self) {
+        mem::swap(&mut self.query_before, &mut self.alt_query_before);
+        mem::swap(&mut self.query_after, &mut self.alt_query_after);
+    }
+
     fn query_changed(
         &self,
         mode: QueryMode,
@@ -369,6 +384,7 @@ impl EventHandler for Query {
             | EvActBackwardKillWord
             | EvActBackwardWord
             | EvActBeginningOfLine
+            | EvActClearQuery
             | EvActDeleteChar
             | EvActEndOfLine
             | EvActForwardChar
@@ -377,6 +393,7 @@ impl EventHandler for Query {
             | EvActKillWord
             | EvActNextHistory
             | EvActPreviousHistory
+            | EvActSwapQuery
             | EvActUnixLineDiscard
             | EvActUnixWordRubout
             | EvActYank
@@ -426,6 +443,10 @@ impl EventHandler for Query {
                 self.act_beginning_of_line();
             }
 
+            EvActClearQuery => {
+                self.act_clear_query();
+            }
+
             EvActEndOfLine => {
                 self.act_end_of_line();
             }
@@ -450,6 +471,10 @@ impl EventHandler for Query {
                 unimplemented!();
             }
 
+            EvActSwapQuery => {
+                self.act_swap_query();
+            }
+
             EvActUnixLineDiscard => {
                 self.act_line_discard();
             }
@@ -504,9 +529,37 @@ impl Draw for Query {
     }
 }
 
+/// readline/emacs style word boundary, unicode aware
+/// with `filepath_word`, only blanks and path separators break the words
+fn is_word_char(ch: char, filepath_word: bool) -> bool {
+    if filepath_word {
+        !ch.is_whitespace() && !['/', '.', '-', '_'].contains(&ch)
+    } else {
+        ch.is_alphanumeric()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::Query;
+    use crate::engine::{EngineFactory, FuzzyAlgorithm, MatcherMode};
+    use crate::item::Item;
+    use crate::options::SkimOptions;
+    use regex::Regex;
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    fn matched(query: &Query, lines: &[&str]) -> Vec<String> {
+        let engine = EngineFactory::build(&query.get_query(), MatcherMode::Fuzzy, FuzzyAlgorithm::V2);
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| Arc::new(Item::new(Cow::Borrowed(*line), false, &[], &[], &delimiter, (0, idx))))
+            .filter(|item| engine.match_item(item.clone()).is_some())
+            .map(|item| item.get_text().to_string())
+            .collect()
+    }
 
     #[test]
     fn test_new_query() {
@@ -517,6 +570,29 @@ mod test {
         assert_eq!(query2.get_query(), "abc");
     }
 
+    #[test]
+    fn test_query_from_options() {
+        let options = SkimOptions {
+            query: Some("foo"),
+            cmd_query: Some("bar"),
+            cmd: Some("grep {}"),
+            ..SkimOptions::default()
+        };
+        let mut query = Query::from_options(&options);
+        assert_eq!(query.get_query(), "foo");
+        assert_eq!(query.get_cmd_query(), "bar");
+        assert_eq!(query.get_cmd(), "grep bar");
+
+        // the cursor is at the end of both
+        assert_eq!(query.get_after(), "");
+        query.act_add_char('d');
+        assert_eq!(query.get_query(), "food");
+        query.act_query_toggle_interactive();
+        assert_eq!(query.get_after(), "");
+        query.act_add_char('d');
+        assert_eq!(query.get_cmd(), "grep bard");
+    }
+
     #[test]
     fn test_add_char() {
         let mut query1 = Query::builder().query("").build();
@@ -528,6 +604,21 @@ mod test {
         assert_eq!(query1.get_query(), "ab中");
     }
 
+    #[test]
+    fn test_clear_query() {
+        let mut query = Query::builder().query("abc").build();
+        query.act_backward_char();
+        query.act_clear_query();
+        assert_eq!(query.get_query(), "");
+
+        // the yanked text is kept
+        query.act_add_char('x');
+        query.act_line_discard();
+        query.act_clear_query();
+        query.act_yank();
+        assert_eq!(query.get_query(), "x");
+    }
+
     #[test]
     fn test_backward_delete_char() {
         let mut query = Query::builder().query("AB中c").build();
@@ -548,4 +639,147 @@ mod test {
         query.act_backward_delete_char();
         assert_eq!(query.get_query(), "");
     }
+
+    #[test]
+    fn test_swap_query() {
+        let lines = ["apple", "banana", "cherry"];
+        let mut query = Query::builder().query("ap").build();
+        assert_eq!(matched(&query, &lines), vec!["apple"]);
+
+        // the alternate buffer starts out empty
+        query.act_swap_query();
+        assert_eq!(query.get_query(), "");
+        query.act_add_char('n');
+        query.act_add_char('a');
+        assert_eq!(matched(&query, &lines), vec!["banana"]);
+
+        query.act_swap_query();
+        assert_eq!(query.get_query(), "ap");
+        assert_eq!(matched(&query, &lines), vec!["apple"]);
+
+        query.act_swap_query();
+        assert_eq!(query.get_query(), "na");
+        assert_eq!(matched(&query, &lines), vec!["banana"]);
+    }
+
+    #[test]
+    fn test_word_motion() {
+        let mut query = Query::builder().query("foo-bar  日本語 x").build();
+        query.act_beginning_of_line();
+        assert_eq!(query.get_before(), "");
+
+        query.act_forward_word();
+        assert_eq!(query.get_before(), "foo");
+        query.act_forward_word();
+        assert_eq!(query.get_before(), "foo-bar");
+        query.act_forward_word();
+        assert_eq!(query.get_before(), "foo-bar  日本語");
+        query.act_forward_word();
+        query.act_forward_word();
+        assert_eq!(query.get_before(), "foo-bar  日本語 x");
+
+        query.act_backward_word();
+        assert_eq!(query.get_before(), "foo-bar  日本語 ");
+        query.act_backward_word();
+        assert_eq!(query.get_before(), "foo-bar  ");
+        query.act_backward_word();
+        assert_eq!(query.get_before(), "foo-");
+        query.act_end_of_line();
+        assert_eq!(query.get_after(), "");
+        assert_eq!(query.get_query(), "foo-bar  日本語 x");
+    }
+
+    #[test]
+    fn test_kill_word() {
+        let mut query = Query::builder().query("foo-bar 日本語").build();
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "foo-bar ");
+        query.act_yank();
+        assert_eq!(query.get_query(), "foo-bar 日本語");
+
+        query.act_beginning_of_line();
+        query.act_kill_word();
+        assert_eq!(query.get_query(), "-bar 日本語");
+        query.act_kill_word();
+        assert_eq!(query.get_query(), " 日本語");
+        query.act_yank();
+        assert_eq!(query.get_query(), "-bar 日本語");
+    }
+
+    #[test]
+    fn test_kill_line_in_cmd_mode() {
+        let mut query = Query::builder().query("abc").build();
+        query.act_query_toggle_interactive();
+        query.cmd_before = "de fg".chars().collect();
+        query.act_backward_word();
+        query.act_kill_line();
+        assert_eq!(query.get_before(), "de ");
+        assert_eq!(query.get_after(), "");
+        query.act_line_discard();
+        assert_eq!(query.get_before(), "");
+        query.act_yank();
+        assert_eq!(query.get_before(), "de ");
+
+        query.act_query_toggle_interactive();
+        assert_eq!(query.get_query(), "abc");
+    }
+
+    #[test]
+    fn test_filepath_word() {
+        let path = "/usr/local/my_app.d/node:v12";
+
+        let mut query = Query::builder().query(path).build();
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/local/my_app.d/node:");
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/local/my_app.d/");
+
+        let mut query = Query::builder().query(path).filepath_word(true).build();
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/local/my_app.d/");
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/local/my_app.");
+        query.act_backward_kill_word();
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/local/");
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "/usr/");
+
+        query.act_beginning_of_line();
+        query.act_forward_word();
+        assert_eq!(query.get_before(), "/usr");
+        query.act_kill_word();
+        assert_eq!(query.get_query(), "/usr");
+        query.act_backward_word();
+        assert_eq!(query.get_before(), "/");
+    }
+
+    #[test]
+    fn test_yank() {
+        // nothing killed yet
+        let mut query = Query::builder().query("abc").build();
+        query.act_yank();
+        assert_eq!(query.get_query(), "abc");
+
+        let mut query = Query::builder().query("foo bar").build();
+        query.act_backward_kill_word();
+        assert_eq!(query.get_query(), "foo ");
+
+        // paste into the middle of the query
+        query.act_beginning_of_line();
+        query.act_forward_char();
+        query.act_yank();
+        assert_eq!(query.get_before(), "fbar");
+        assert_eq!(query.get_query(), "fbaroo ");
+
+        // the killed text is kept after yanking
+        query.act_yank();
+        assert_eq!(query.get_query(), "fbarbaroo ");
+
+        // killing nothing keeps the last killed text
+        query.act_end_of_line();
+        query.act_kill_line();
+        query.act_yank();
+        assert_eq!(query.get_query(), "fbarbaroo bar");
+    }
 }
@@ -1,29 +1,42 @@
 ///! Reader is used for reading items from datasource (e.g. stdin or command output)
 ///!
 ///! After reading in a line, reader will save an item into the pool(items)
-use crate::field::FieldRange;
+use crate::field::{parse_delimiter, FieldRange};
 use crate::item::Item;
 use crate::options::SkimOptions;
 use crate::spinlock::SpinLock;
+use crate::util::{panic_message, parse_max_items};
 use regex::Regex;
+use std::borrow::Cow;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::env;
 use std::error::Error;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::panic::{self, AssertUnwindSafe};
 use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
 
-const DELIMITER_STR: &str = r"[\t\n ]+";
+const CHUNK_QUEUE_SIZE: usize = 64;
+// how often the receiver of the items is checked for being stopped
+const RECV_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 #[derive(Debug)]
 pub struct ReaderControl {
     stopped: Arc<AtomicBool>,
     thread_reader: JoinHandle<()>,
     pub items: Arc<SpinLock<Vec<Arc<Item>>>>,
+    // set if the reader thread panicked
+    error: Arc<SpinLock<Option<String>>>,
+    // set if the reader gave up waiting for input (--reader-timeout)
+    timed_out: Arc<AtomicBool>,
+    // set if the reader stopped at the maximum number of items (--max-items)
+    capped: Arc<AtomicBool>,
 }
 
 impl ReaderControl {
@@ -43,11 +56,37 @@ impl ReaderControl {
         let items = self.items.lock();
         self.stopped.load(Ordering::Relaxed) && items.is_empty()
     }
+
+    pub fn get_error(&self) -> Option<String> {
+        self.error.lock().clone()
+    }
+
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    pub fn is_capped(&self) -> bool {
+        self.capped.load(Ordering::Relaxed)
+    }
+
+    /// block until the reader stops or has read `max_items` items (if given), the items are
+    /// left for `take`. Returns the number of items read.
+    pub fn wait(&self, max_items: Option<usize>) -> usize {
+        loop {
+            let stopped = self.stopped.load(Ordering::Relaxed);
+            let num_items = self.items.lock().len();
+            if stopped || max_items.map(|max_items| num_items >= max_items).unwrap_or(false) {
+                return num_items;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
 }
 
 pub struct Reader {
     option: Arc<ReaderOption>,
     source_file: Option<Box<dyn BufRead + Send>>,
+    source_receiver: Option<Receiver<String>>,
 }
 
 impl Reader {
@@ -55,6 +94,7 @@ impl Reader {
         Self {
             option: Arc::new(ReaderOption::with_options(&options)),
             source_file: None,
+            source_receiver: None,
         }
     }
 
@@ -63,6 +103,18 @@ impl Reader {
         self
     }
 
+    /// the delimiter of the fields, to split them the same way everywhere else
+    pub fn delimiter(&self) -> Arc<Regex> {
+        self.option.delimiter.clone()
+    }
+
+    /// read the items sent over the channel instead, until all its senders are dropped. Only the
+    /// first run reads the channel, the later ones (e.g. `reload`) run their command.
+    pub fn source_receiver(mut self, source_receiver: Option<Receiver<String>>) -> Self {
+        self.source_receiver = source_receiver;
+        self
+    }
+
     // -c オプションのコマンド実行してそうなところ
     // 引数にコマンドを入れると実行してくレル関数
     // TODO Readerの初期化状態を確認すべき
@@ -74,20 +126,63 @@ impl Reader {
         let items = Arc::new(SpinLock::new(Vec::new()));
         let items_clone = items.clone();
 
+        let error = Arc::new(SpinLock::new(None));
+        let error_clone = error.clone();
+
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let timed_out_clone = timed_out.clone();
+
+        let capped = Arc::new(AtomicBool::new(false));
+        let capped_clone = capped.clone();
+
         let option_clone = self.option.clone(); // 多分Model::newで初期化されたことをクローンしている?
         let source_file = self.source_file.take(); // Noneで初期化されている
+        let source_receiver = self.source_receiver.take();
         let cmd = cmd.to_string();
 
         // start the new command
         // コマンド実行は別スレッドされるが、コマンド結果を受け取るまでこのスレッドはブロックする
-        let thread_reader = thread::spawn(move || {
-            reader(&cmd, stopped_clone, items_clone, option_clone, source_file);
-        });
+        let thread_reader = thread::Builder::new()
+            .name("reader".to_string())
+            .spawn(move || {
+                // a panic would otherwise leave the reader "running" forever and hang the UI
+                let result = panic::catch_unwind(AssertUnwindSafe(|| match source_receiver {
+                    Some(receiver) => receiver_reader(
+                        &cmd,
+                        stopped_clone.clone(),
+                        items_clone,
+                        option_clone,
+                        receiver,
+                        timed_out_clone,
+                        capped_clone,
+                    ),
+                    None => reader(
+                        &cmd,
+                        stopped_clone.clone(),
+                        items_clone,
+                        option_clone,
+                        source_file,
+                        timed_out_clone,
+                        capped_clone,
+                    ),
+                }));
+
+                if let Err(payload) = result {
+                    let msg = panic_message(payload.as_ref());
+                    error!("reader: thread panicked: {}", msg);
+                    error_clone.lock().replace(msg);
+                    stopped_clone.store(true, Ordering::SeqCst);
+                }
+            })
+            .expect("reader: failed to spawn reader thread");
 
         ReaderControl {
             stopped,       // AtomicBool(コマンドの実行の終了を渡す)
             thread_reader, // 実行結果を渡す
             items,
+            error,
+            timed_out,
+            capped,
         }
     }
 }
@@ -97,9 +192,13 @@ struct ReaderOption {
     pub default_arg: String,
     pub transform_fields: Vec<FieldRange>,
     pub matching_fields: Vec<FieldRange>,
-    pub delimiter: Regex,
+    pub delimiter: Arc<Regex>,
     pub replace_str: String,
     pub line_ending: u8,
+    pub use_cache: bool,
+    pub timeout: Option<Duration>,
+    // stop reading after this many items, unless the oldest ones are dropped instead
+    pub max_items: Option<usize>,
 }
 
 impl ReaderOption {
@@ -109,9 +208,12 @@ impl ReaderOption {
             default_arg: String::new(),
             transform_fields: Vec::new(),
             matching_fields: Vec::new(),
-            delimiter: Regex::new(DELIMITER_STR).unwrap(),
+            delimiter: parse_delimiter(None),
             replace_str: "{}".to_string(),
             line_ending: b'\n',
+            use_cache: false,
+            timeout: None,
+            max_items: None,
         }
     }
 
@@ -126,9 +228,7 @@ impl ReaderOption {
             self.use_ansi_color = true;
         }
 
-        if let Some(delimiter) = options.delimiter {
-            self.delimiter = Regex::new(delimiter).unwrap_or_else(|_| Regex::new(DELIMITER_STR).unwrap());
-        }
+        self.delimiter = parse_delimiter(options.delimiter);
 
         if let Some(transform_fields) = options.with_nth {
             self.transform_fields = transform_fields
@@ -147,6 +247,24 @@ impl ReaderOption {
         if options.read0 {
             self.line_ending = b'\0';
         }
+
+        if options.cache {
+            self.use_cache = true;
+        }
+
+        if let Some(timeout) = options.reader_timeout {
+            // already validated by `SkimOptionsBuilder::build`
+            self.timeout = timeout
+                .parse::<u64>()
+                .ok()
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis);
+        }
+
+        // with `N:drop` the model drops the oldest items, the input is read to the end
+        if let Some(Ok((max_items, false))) = options.max_items.map(parse_max_items) {
+            self.max_items = Some(max_items);
+        }
     }
 }
 // Sendでスレッド間で送信可能になる
@@ -159,7 +277,7 @@ type CommandOutput = (Option<Child>, Box<dyn BufRead + Send>);
 // CommandOputputってErrorをトレイトオブジェクトで返す
 fn get_command_output(cmd: &str) -> Result<CommandOutput, Box<dyn Error>> {
     let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-    println!("{}", shell);
+    debug!("reader: run {:?} with {}", cmd, shell);
     let mut command = Command::new(shell)
         .arg("-c")
         .arg(cmd)
@@ -188,6 +306,74 @@ lazy_static! {
     static ref NUM_MAP: RwLock<HashMap<String, usize>> = RwLock::new(HashMap::new());
 }
 
+// With `--cache`, the items of every command that ran to completion are kept here so that
+// invoking the same command again reuses them instead of spawning it again.
+// The cache lives as long as the process, it is never written to disk.
+lazy_static! {
+    static ref CMD_CACHE: RwLock<HashMap<String, Arc<Vec<Arc<Item>>>>> = RwLock::new(HashMap::new());
+}
+
+// the run number of the command, the same for the same command
+fn get_run_num(cmd: &str) -> usize {
+    let run_num = { *RUN_NUM.read().expect("reader: failed to lock RUN_NUM") };
+    *NUM_MAP
+        .write()
+        .expect("reader: failed to lock NUM_MAP")
+        .entry(cmd.to_string())
+        .or_insert_with(|| {
+            *(RUN_NUM.write().expect("reader: failed to lock RUN_NUM for write")) = run_num + 1;
+            run_num + 1
+        })
+}
+
+// like `reader`, but the lines are sent by the embedding program
+fn receiver_reader(
+    cmd: &str,
+    stopped: Arc<AtomicBool>,
+    items: Arc<SpinLock<Vec<Arc<Item>>>>,
+    option: Arc<ReaderOption>,
+    receiver: Receiver<String>,
+    timed_out: Arc<AtomicBool>,
+    capped: Arc<AtomicBool>,
+) {
+    let run_num = get_run_num(cmd);
+    let mut index = 0;
+    let mut waited = Duration::from_millis(0);
+    while !stopped.load(Ordering::SeqCst) {
+        match receiver.recv_timeout(RECV_POLL_INTERVAL) {
+            Ok(_) if option.max_items == Some(index) => {
+                // one more item than `--max-items`, the rest are not read
+                capped.store(true, Ordering::Relaxed);
+                break;
+            }
+            Ok(text) => {
+                let item = Item::new(
+                    Cow::Owned(text),
+                    option.use_ansi_color,
+                    &option.transform_fields,
+                    &option.matching_fields,
+                    &option.delimiter,
+                    (run_num, index),
+                );
+                items.lock().push(Arc::new(item));
+                index += 1;
+                waited = Duration::from_millis(0);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                waited += RECV_POLL_INTERVAL;
+                if option.timeout.map(|timeout| waited >= timeout).unwrap_or(false) {
+                    warn!("reader: no item received for {:?}, stop reading", waited);
+                    timed_out.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    stopped.store(true, Ordering::Relaxed);
+}
+
 // reader.runでは別スレッド上で実行されている
 fn reader(
     cmd: &str,
@@ -195,7 +381,28 @@ fn reader(
     items: Arc<SpinLock<Vec<Arc<Item>>>>,
     option: Arc<ReaderOption>,
     source_file: Option<Box<dyn BufRead + Send>>,
+    timed_out: Arc<AtomicBool>,
+    capped: Arc<AtomicBool>,
 ) {
+    let use_cache = option.use_cache && source_file.is_none();
+    if use_cache {
+        let cached = CMD_CACHE
+            .read()
+            .expect("reader: failed to lock CMD_CACHE")
+            .get(cmd)
+            .cloned();
+        if let Some(cached) = cached {
+            let num_items = option
+                .max_items
+                .map(|max_items| min(max_items, cached.len()))
+                .unwrap_or(cached.len());
+            items.lock().extend(cached[..num_items].iter().cloned());
+            capped.store(num_items < cached.len(), Ordering::Relaxed);
+            stopped.store(true, Ordering::Relaxed);
+            return;
+        }
+    }
+
     // command実行箇所?
     // コマンド実行はブロックする
     let (command, mut source) = source_file // Some(ChildStdout), Box::new(BufReader::new(stdout))
@@ -204,90 +411,176 @@ fn reader(
         .unwrap_or_else(|| get_command_output(cmd).expect("command not found"));
 
     let command_stopped = Arc::new(AtomicBool::new(false));
+    // set if the command exited by itself with success, only then is its output cached
+    let command_succeeded = Arc::new(AtomicBool::new(false));
 
     let stopped_clone = stopped.clone(); // stopped(false)
     let command_stopped_clone = command_stopped.clone(); // command_stopped(false)
-    thread::spawn(move || {
-        // kill command if it is got
-        // 起動直後はこのループが周り続けそう(stopped_cloneの値がどこかでtrueになったら終わる)
-        // stopped_cloneは、おそらく-cオプションで指定したコマンド終了時にtrueになる?
-        // 誰がstopped_cloneを書き換えているのか?
-        while command.is_some() && !stopped_clone.load(Ordering::Relaxed) {
-            // println!("{}", stopped_clone.load(Ordering::Relaxed));
-            thread::sleep(Duration::from_millis(5));
-        }
+    let command_succeeded_clone = command_succeeded.clone();
+    thread::Builder::new()
+        .name("reader-command".to_string())
+        .spawn(move || {
+            // kill command if it is got
+            // 起動直後はこのループが周り続けそう(stopped_cloneの値がどこかでtrueになったら終わる)
+            // stopped_cloneは、おそらく-cオプションで指定したコマンド終了時にtrueになる?
+            // 誰がstopped_cloneを書き換えているのか?
+            if let Some(mut x) = command {
+                while !stopped_clone.load(Ordering::Relaxed) {
+                    match x.try_wait() {
+                        Ok(Some(status)) => {
+                            command_succeeded_clone.store(status.success(), Ordering::Relaxed);
+                            break;
+                        }
+                        Ok(None) => thread::sleep(Duration::from_millis(5)),
+                        Err(_) => break,
+                    }
+                }
 
-        // clean up resources
-        // Optionの中身のChildを操作している
-        if let Some(mut x) = command {
-            let _ = x.kill();
-            let _ = x.wait();
-        }
-        command_stopped_clone.store(true, Ordering::Relaxed);
-    });
+                // clean up resources
+                // Optionの中身のChildを操作している
+                let _ = x.kill();
+                let _ = x.wait();
+            }
+            command_stopped_clone.store(true, Ordering::Relaxed);
+        })
+        .expect("reader: failed to spawn command watcher thread");
 
     let opt = option;
 
-    // set the proper run number
-    let run_num = { *RUN_NUM.read().expect("reader: failed to lock RUN_NUM") };
-    let run_num = *NUM_MAP
-        .write()
-        .expect("reader: failed to lock NUM_MAP")
-        .entry(cmd.to_string())
-        .or_insert_with(|| {
-            *(RUN_NUM.write().expect("reader: failed to lock RUN_NUM for write")) = run_num + 1;
-            run_num + 1
-        });
+    let run_num = get_run_num(cmd);
+
+    // read on a separate thread, so that a stalled command can be given up on (--reader-timeout)
+    let (tx_chunk, rx_chunk) = sync_channel::<Vec<u8>>(CHUNK_QUEUE_SIZE);
+    let source_thread = thread::Builder::new()
+        .name("reader-source".to_string())
+        .spawn(move || loop {
+            let len = match source.fill_buf() {
+                Ok([]) => break, // EOF
+                Ok(buf) => {
+                    if tx_chunk.send(buf.to_vec()).is_err() {
+                        break;
+                    }
+                    buf.len()
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            source.consume(len);
+        })
+        .expect("reader: failed to spawn source thread");
 
     let mut index = 0;
-    let mut buffer = Vec::with_capacity(100);
+    let mut read_items = Vec::new();
+    let mut completed = false;
+    let mut emit = |mut buffer: &[u8]| {
+        if buffer.ends_with(b"\r\n") {
+            buffer = &buffer[..buffer.len() - 2];
+        } else if buffer.ends_with(b"\n") || buffer.ends_with(b"\0") {
+            buffer = &buffer[..buffer.len() - 1];
+        }
+
+        // invalid UTF-8 is replaced for display & matching, but the raw bytes are kept for output
+        let text = String::from_utf8_lossy(buffer);
+        let is_lossy = match text {
+            Cow::Owned(_) => true,
+            Cow::Borrowed(_) => false,
+        };
+        let mut item = Item::new(
+            text,
+            opt.use_ansi_color,
+            &opt.transform_fields,
+            &opt.matching_fields,
+            &opt.delimiter,
+            (run_num, index),
+        );
+        if is_lossy {
+            item = item.raw_bytes(buffer.to_vec());
+        }
+
+        // save item into pool
+        // ReaderControlのitemsフィールド, ArcでSpinLockなVec
+        // TUIのrowを管理している?
+        let item = Arc::new(item);
+        if use_cache {
+            read_items.push(item.clone());
+        }
+        items.lock().push(item);
+        index += 1;
+    };
+
+    // bytes read but not terminated by `line_ending` yet
+    let mut pending: Vec<u8> = Vec::with_capacity(100);
+    let mut num_read = 0;
+    let reached_max = |num_read| opt.max_items.map(|max_items| num_read >= max_items).unwrap_or(false);
     loop {
-        buffer.clear();
-        // start reading
-        // line_endingはデフォルト b'\n'
-        // コマンド実行結果のうち改行を含むまでの数値がnに入る
-        // 改行までの値は、bufferに入る。sourceはbufferに入った分なくなる
-        match source.read_until(opt.line_ending, &mut buffer) {
-            Ok(n) => {
-                // コマンド実行後この条件が満たされて、stoppedがtrueになる
-                // 結果をbufferに改行ごとに入れて、sourceの中身がなくなったらbreak
-                if n == 0 {
-                    break;
+        let chunk = match opt.timeout {
+            Some(timeout) => rx_chunk.recv_timeout(timeout),
+            None => rx_chunk.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match chunk {
+            Ok(chunk) => pending.extend_from_slice(&chunk),
+            Err(RecvTimeoutError::Disconnected) => {
+                // the source thread is gone, pass its panic on if it had one
+                if let Err(payload) = source_thread.join() {
+                    panic::resume_unwind(payload);
                 }
+                completed = true;
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                warn!(
+                    "reader: no input from `{}` for {:?}, stop reading",
+                    cmd,
+                    opt.timeout.unwrap()
+                );
+                timed_out.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
 
-                if buffer.ends_with(&[b'\r', b'\n']) {
-                    buffer.pop();
-                    buffer.pop();
-                } else if buffer.ends_with(&[b'\n']) || buffer.ends_with(&[b'\0']) {
-                    buffer.pop();
-                }
-                // thread::sleep_ms(3000);
+        // line_endingはデフォルト b'\n'
+        let mut consumed = 0;
+        while let Some(pos) = pending[consumed..].iter().position(|&b| b == opt.line_ending) {
+            if reached_max(num_read) {
+                break;
+            }
+            emit(&pending[consumed..consumed + pos + 1]);
+            consumed += pos + 1;
+            num_read += 1;
+        }
+        pending.drain(..consumed);
 
-                let item = Item::new(
-                    String::from_utf8_lossy(&buffer),
-                    opt.use_ansi_color,
-                    &opt.transform_fields,
-                    &opt.matching_fields,
-                    &opt.delimiter,
-                    (run_num, index),
-                );
+        // there is more input than `--max-items`, the rest is not read and the command is killed
+        if reached_max(num_read) && !pending.is_empty() {
+            warn!("reader: read {} items from `{}`, stop reading", num_read, cmd);
+            capped.store(true, Ordering::Relaxed);
+            break;
+        }
 
-                {
-                    // save item into pool
-                    // ReaderControlのitemsフィールド, ArcでSpinLockなVec
-                    // TUIのrowを管理している?
-                    let mut vec = items.lock();
-                    vec.push(Arc::new(item));
-                    index += 1;
-                }
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+    }
 
-                if stopped.load(Ordering::SeqCst) {
-                    break;
-                }
-            }
-            Err(_err) => {} // String not UTF8 or other error, skip.
+    // the last line without a line ending, or the partial line we got before timing out
+    if !pending.is_empty() && !stopped.load(Ordering::SeqCst) && !reached_max(num_read) {
+        emit(&pending);
+    }
+
+    // a command that was stopped (e.g. by a query change) or killed only produced part of its
+    // output, wait for the one that closed its output to exit, to cache only what a success produced
+    if use_cache && completed {
+        while !command_stopped.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(5));
         }
     }
+    if use_cache && completed && command_succeeded.load(Ordering::Relaxed) && !stopped.load(Ordering::SeqCst) {
+        CMD_CACHE
+            .write()
+            .expect("reader: failed to lock CMD_CACHE")
+            .insert(cmd.to_string(), Arc::new(read_items));
+    }
 
     stopped.store(true, Ordering::Relaxed); // -cオプションのコマンド終了時に上述で立ち上げたthreadのwhile条件から抜けさせる
 
@@ -296,3 +589,307 @@ fn reader(
         thread::sleep(Duration::from_millis(5));
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::io::{self, Read};
+    use std::time::Instant;
+
+    pub(crate) struct PanicSource;
+
+    impl Read for PanicSource {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("source exploded");
+        }
+    }
+
+    // sends some data, then stalls
+    struct StallSource {
+        data: Option<&'static [u8]>,
+    }
+
+    impl Read for StallSource {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.data.take() {
+                Some(data) => {
+                    buf[..data.len()].copy_from_slice(data);
+                    Ok(data.len())
+                }
+                None => {
+                    thread::sleep(Duration::from_secs(60));
+                    Ok(0)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_timeout_keeps_partial_line() {
+        let options = SkimOptions {
+            reader_timeout: Some("50"),
+            ..SkimOptions::default()
+        };
+        let source = StallSource {
+            data: Some(b"a\nb\npart"),
+        };
+        let ctrl = Reader::with_options(&options)
+            .source(Some(Box::new(BufReader::new(source))))
+            .run("");
+
+        let start = Instant::now();
+        while !ctrl.stopped.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < Duration::from_secs(5), "reader never timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert!(ctrl.is_timed_out());
+        let texts: Vec<_> = ctrl.take().iter().map(|item| item.get_text().to_string()).collect();
+        assert_eq!(texts, vec!["a", "b", "part"]);
+    }
+
+    #[test]
+    fn test_reader_wait() {
+        let source = StallSource {
+            data: Some(b"a\nb\nc\n"),
+        };
+        let ctrl = Reader::with_options(&SkimOptions::default())
+            .source(Some(Box::new(BufReader::new(source))))
+            .run("");
+        // enough items were read, the stalled input is not waited for
+        assert!(ctrl.wait(Some(2)) >= 2);
+        assert!(!ctrl.stopped.load(Ordering::Relaxed));
+        ctrl.stopped.store(true, Ordering::Relaxed);
+
+        let source: &[u8] = b"a\nb\nc\n";
+        let ctrl = Reader::with_options(&SkimOptions::default())
+            .source(Some(Box::new(BufReader::new(source))))
+            .run("");
+        assert_eq!(ctrl.wait(Some(10)), 3);
+        assert_eq!(ctrl.take().len(), 3);
+    }
+
+    #[test]
+    fn test_reader_max_items() {
+        let read = |max_items| {
+            let options = SkimOptions {
+                max_items: Some(max_items),
+                ..SkimOptions::default()
+            };
+            let source: &[u8] = b"a\nb\nc";
+            let ctrl = Reader::with_options(&options)
+                .source(Some(Box::new(BufReader::new(source))))
+                .run("");
+            ctrl.wait(None);
+            let texts: Vec<_> = ctrl.take().iter().map(|item| item.get_text().to_string()).collect();
+            (texts, ctrl.is_capped())
+        };
+
+        assert_eq!(read("2"), (vec!["a".to_string(), "b".to_string()], true));
+        // all the input fits
+        assert_eq!(
+            read("3"),
+            (vec!["a".to_string(), "b".to_string(), "c".to_string()], false)
+        );
+        // the oldest items are dropped by the model instead, the input is read to the end
+        assert_eq!(read("2:drop").0.len(), 3);
+    }
+
+    #[test]
+    fn test_reader_source_receiver() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctrl = Reader::with_options(&SkimOptions::default())
+            .source_receiver(Some(rx))
+            .run("");
+        tx.send("a".to_string()).unwrap();
+        tx.send("b".to_string()).unwrap();
+        // still open, more items may come
+        assert_eq!(ctrl.wait(Some(2)), 2);
+        assert!(!ctrl.is_done());
+
+        tx.send("c".to_string()).unwrap();
+        drop(tx);
+        assert_eq!(ctrl.wait(None), 3);
+        let texts: Vec<String> = ctrl.take().iter().map(|item| item.get_text().to_string()).collect();
+        assert_eq!(texts, vec!["a", "b", "c"]);
+        assert!(ctrl.is_done());
+    }
+
+    #[test]
+    fn test_reader_source_receiver_is_read_once() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut reader = Reader::with_options(&SkimOptions::default()).source_receiver(Some(rx));
+        tx.send("a".to_string()).unwrap();
+        drop(tx);
+        let ctrl = reader.run("echo b");
+        assert_eq!(ctrl.wait(None), 1);
+        assert_eq!(ctrl.take()[0].get_text(), "a");
+
+        // the channel is used up, a reload runs the command
+        let ctrl = reader.run("echo b");
+        assert_eq!(ctrl.wait(None), 1);
+        assert_eq!(ctrl.take()[0].get_text(), "b");
+    }
+
+    #[test]
+    fn test_reader_delimiter_is_shared() {
+        use crate::field::get_string_by_range;
+        use crate::util::{inject_command, InjectContext};
+
+        let options = SkimOptions {
+            delimiter: Some(":"),
+            nth: Some("2"),
+            ..SkimOptions::default()
+        };
+        let source: &[u8] = b"a b:c d:e\n";
+        let mut reader = Reader::with_options(&options).source(Some(Box::new(BufReader::new(source))));
+        let ctrl = reader.run("");
+        assert_eq!(ctrl.wait(None), 1);
+        let items = ctrl.take();
+        let text = items[0].get_text();
+
+        // `--nth`, `{2}` in the preview/execute and `--accept-nth` split with the same regex
+        let delimiter = reader.delimiter();
+        let (begin, end) = items[0].get_matching_ranges()[0];
+        assert_eq!(&text[begin..end], "c d:");
+        assert_eq!(get_string_by_range(&delimiter, text, "2"), Some("c d"));
+        let context = InjectContext {
+            delimiter: &delimiter,
+            current_selection: text,
+            selections: &[],
+            query: "",
+            cmd_query: "",
+        };
+        assert_eq!(inject_command("echo {2}", context), "echo 'c d'");
+
+        // an invalid delimiter falls back to the default one everywhere
+        let options = SkimOptions {
+            delimiter: Some("("),
+            ..SkimOptions::default()
+        };
+        let delimiter = Reader::with_options(&options).delimiter();
+        assert_eq!(get_string_by_range(&delimiter, "a b:c", "2"), Some("b:c"));
+    }
+
+    #[test]
+    fn test_reader_keeps_invalid_utf8_bytes() {
+        let source: &[u8] = b"a\x80b\nok\n";
+        let ctrl = Reader::with_options(&SkimOptions::default())
+            .source(Some(Box::new(BufReader::new(source))))
+            .run("");
+
+        let start = Instant::now();
+        while !ctrl.stopped.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < Duration::from_secs(5), "reader never stopped");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let items = ctrl.take();
+        assert_eq!(items[0].get_text(), "a\u{FFFD}b");
+        assert_eq!(&*items[0].get_output_bytes(), b"a\x80b");
+        assert_eq!(&*items[1].get_output_bytes(), b"ok");
+    }
+
+    #[test]
+    fn test_reader_read0_keeps_newlines() {
+        let options = SkimOptions {
+            read0: true,
+            ..SkimOptions::default()
+        };
+        let source: &[u8] = b"a1\nx\0b2\0a3";
+        let ctrl = Reader::with_options(&options)
+            .source(Some(Box::new(BufReader::new(source))))
+            .run("");
+
+        let start = Instant::now();
+        while !ctrl.stopped.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < Duration::from_secs(5), "reader never stopped");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let items = ctrl.take();
+        let outputs: Vec<_> = items.iter().map(|item| item.get_output_bytes().into_owned()).collect();
+        assert_eq!(outputs, vec![b"a1\nx".to_vec(), b"b2".to_vec(), b"a3".to_vec()]);
+    }
+
+    #[test]
+    fn test_reader_panic_is_isolated() {
+        let mut reader =
+            Reader::with_options(&SkimOptions::default()).source(Some(Box::new(BufReader::new(PanicSource))));
+        let ctrl = reader.run("");
+
+        let start = Instant::now();
+        while !ctrl.is_done() {
+            assert!(start.elapsed() < Duration::from_secs(5), "reader never stopped");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(ctrl.get_error(), Some("source exploded".to_string()));
+        ctrl.kill();
+    }
+
+    #[test]
+    fn test_reader_reuses_cached_command() {
+        let cmd = "sk-test-cached-command";
+        let delimiter = parse_delimiter(None);
+        let cached: Vec<_> = ["a", "b"]
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| Arc::new(Item::new((*text).into(), false, &[], &[], &delimiter, (0, idx))))
+            .collect();
+        CMD_CACHE.write().unwrap().insert(cmd.to_string(), Arc::new(cached));
+
+        let options = SkimOptions {
+            cache: true,
+            ..SkimOptions::default()
+        };
+        let ctrl = Reader::with_options(&options).run(cmd);
+
+        let start = Instant::now();
+        while !ctrl.stopped.load(Ordering::Relaxed) {
+            assert!(start.elapsed() < Duration::from_secs(5), "reader never stopped");
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let texts: Vec<_> = ctrl.take().iter().map(|item| item.get_text().to_string()).collect();
+        assert_eq!(texts, vec!["a", "b"]);
+        ctrl.kill();
+    }
+
+    #[test]
+    fn test_reader_cache() {
+        let read = |cmd: &str, max_items| {
+            let options = SkimOptions {
+                cache: true,
+                max_items,
+                ..SkimOptions::default()
+            };
+            let ctrl = Reader::with_options(&options).run(cmd);
+            ctrl.wait(None);
+            (ctrl.take().len(), ctrl.is_capped())
+        };
+        let cached = |cmd: &str| CMD_CACHE.read().unwrap().contains_key(cmd);
+
+        let cmd = "printf 'a\\nb\\nc\\n' # test_reader_cache";
+        assert_eq!(read(cmd, None), (3, false));
+        assert!(cached(cmd));
+        // a cache hit is capped as the command would be
+        assert_eq!(read(cmd, Some("2")), (2, true));
+
+        // a failed command is run again
+        let cmd = "printf 'a\\nb\\n'; exit 1 # test_reader_cache";
+        assert_eq!(read(cmd, None), (2, false));
+        assert!(!cached(cmd));
+
+        // nor is the output of a command that was stopped
+        let cmd = "echo a; sleep 60 # test_reader_cache";
+        let ctrl = Reader::with_options(&SkimOptions {
+            cache: true,
+            ..SkimOptions::default()
+        })
+        .run(cmd);
+        assert_eq!(ctrl.wait(Some(1)), 1);
+        ctrl.kill();
+        assert!(!cached(cmd));
+    }
+}
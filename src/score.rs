@@ -13,6 +13,90 @@ pub fn fuzzy_match(choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
     fuzzy_matcher::skim::fuzzy_indices(choice, pattern)
 }
 
+// scores of the simple (v1) fuzzy algorithm
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BOUNDARY: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// The fast fuzzy algorithm (fzf calls it v1). Instead of searching for the best alignment, it
+/// greedily takes the first occurrence of the pattern, then walks back from its end to find the
+/// shortest window ending there. Trades match quality for speed on large inputs.
+/// Smart case: case insensitive unless the pattern contains an upper case character.
+pub fn fuzzy_match_v1(choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    } else if choice.is_empty() {
+        return None;
+    }
+
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let normalize = |ch: char| {
+        if case_sensitive {
+            ch
+        } else if ch.is_ascii() {
+            ch.to_ascii_lowercase()
+        } else {
+            ch.to_lowercase().next().unwrap_or(ch)
+        }
+    };
+    let pattern: Vec<char> = pattern.chars().map(normalize).collect();
+
+    // forward: find the end of the first occurrence, most choices are rejected here
+    let mut pattern_idx = 0;
+    let mut end = 0;
+    for (idx, ch) in choice.chars().enumerate() {
+        if normalize(ch) == pattern[pattern_idx] {
+            pattern_idx += 1;
+            if pattern_idx == pattern.len() {
+                end = idx;
+                break;
+            }
+        }
+    }
+    if pattern_idx < pattern.len() {
+        return None;
+    }
+
+    // backward: shrink the window from its end
+    let choice: Vec<char> = choice.chars().take(end + 1).collect();
+    let mut indices = Vec::with_capacity(pattern.len());
+    let mut pattern_idx = pattern.len();
+    for idx in (0..=end).rev() {
+        if normalize(choice[idx]) == pattern[pattern_idx - 1] {
+            indices.push(idx);
+            pattern_idx -= 1;
+            if pattern_idx == 0 {
+                break;
+            }
+        }
+    }
+    indices.reverse();
+
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+    for &idx in &indices {
+        score += SCORE_MATCH;
+        if idx == 0 || is_boundary(choice[idx - 1], choice[idx]) {
+            score += BONUS_BOUNDARY;
+        }
+        match prev {
+            Some(prev) if prev + 1 == idx => score += BONUS_CONSECUTIVE,
+            Some(prev) => score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (idx - prev - 2) as i64,
+            None => {}
+        }
+        prev = Some(idx);
+    }
+
+    Some((score, indices))
+}
+
+// `ch` starts a new word: after a separator or a lower-upper case change
+fn is_boundary(prev: char, ch: char) -> bool {
+    (!prev.is_alphanumeric() && ch.is_alphanumeric()) || (prev.is_lowercase() && ch.is_uppercase())
+}
+
 pub fn regex_match(choice: &str, pattern: &Option<Regex>) -> Option<(usize, usize)> {
     match *pattern {
         Some(ref pat) => {
@@ -32,3 +116,33 @@ pub fn exact_match(choice: &str, pattern: &str) -> Option<((usize, usize), (usiz
     let last_occur = (last_pos, last_pos + pattern.len());
     Some((first_occur, last_occur))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_v1() {
+        assert_eq!(fuzzy_match_v1("abc", ""), Some((0, vec![])));
+        assert_eq!(fuzzy_match_v1("", "a"), None);
+        assert_eq!(fuzzy_match_v1("abc", "abd"), None);
+        assert_eq!(fuzzy_match_v1("abc", "cb"), None);
+
+        // the window is shrunk from its end: "a" at 3 is closer to "c" than the one at 0
+        assert_eq!(
+            fuzzy_match_v1("axxabxc", "abc").map(|(_, idx)| idx),
+            Some(vec![3, 4, 6])
+        );
+
+        // smart case
+        assert!(fuzzy_match_v1("ABC", "abc").is_some());
+        assert!(fuzzy_match_v1("abc", "Abc").is_none());
+
+        // consecutive & boundary matches score higher
+        let (consecutive, _) = fuzzy_match_v1("xabcx", "abc").unwrap();
+        let (scattered, _) = fuzzy_match_v1("xaxbxc", "abc").unwrap();
+        let (boundary, _) = fuzzy_match_v1("x/abc", "abc").unwrap();
+        assert!(consecutive > scattered);
+        assert!(boundary > consecutive);
+    }
+}
@@ -1,18 +1,28 @@
 ///! Handle the selections of items
+use crate::ansi::AnsiString;
 use crate::event::{Event, EventArg, EventHandler, UpdateScreen};
+use crate::field::{get_columns, parse_delimiter};
+use crate::frecency::Frecency;
 use crate::item::{parse_criteria, RankCriteria};
 use crate::item::{Item, MatchedItem, MatchedRange};
 use crate::orderedvec::CompareFunction;
 use crate::orderedvec::OrderedVec;
 use crate::theme::{ColorTheme, DEFAULT_THEME};
-use crate::util::{reshape_string, LinePrinter};
+use crate::util::{accumulate_text_width, reshape_string, skip_to_pattern, wrap_text, LinePrinter};
 use crate::SkimOptions;
+use regex::Regex;
+use std::borrow::Cow;
 use std::cmp::max;
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::ops::Range;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tuikit::prelude::*;
+use unicode_width::UnicodeWidthStr;
+
+const DEFAULT_JUMP_LABELS: &str = "abcdefghijklmnopqrstuvwxyz";
 
 lazy_static! {
     static ref DEFAULT_CRITERION: Vec<RankCriteria> = vec![
@@ -23,10 +33,42 @@ lazy_static! {
     ];
 }
 
+// the continuation rows of a wrapped item (`--wrap`) are indented
+const WRAP_INDENT: usize = 2;
+
+/// the items to select as they are loaded, given by `--pre-select-n` and `--pre-select-pat`
+struct PreSelect {
+    indices: HashSet<usize>,
+    pattern: Option<Regex>,
+    // the items of later runs (e.g. of another command) are not pre-selected
+    run_num: Option<usize>,
+}
+
+impl PreSelect {
+    fn should_select(&mut self, item: &Item) -> bool {
+        let (run_num, index) = item.get_full_index();
+        if *self.run_num.get_or_insert(run_num) != run_num {
+            return false;
+        }
+
+        self.indices.contains(&index) || self.pattern.as_ref().map(|re| re.is_match(item.get_text())) == Some(true)
+    }
+}
+
 pub struct Selection {
     criterion: Vec<RankCriteria>,
+    // rank the items by `criterion`, or keep them in input order
+    sort: bool,
+    // the sort state is shown only once it was toggled
+    sort_toggled: bool,
+    tac: bool,
     items: OrderedVec<MatchedItem>, // all items
+    // the items accepted before rank higher (--frecency)
+    frecency: Option<Frecency>,
     selected: HashMap<(usize, usize), Arc<Item>>,
+    pre_select: Option<PreSelect>,
+    // put the cursor on the first literal match of the query once the initial items are matched
+    cursor_on_exact: bool,
 
     //
     // |>------ items[items.len()-1]
@@ -43,12 +85,25 @@ pub struct Selection {
     line_cursor: usize, // line No.
     hscroll_offset: usize,
     height: AtomicUsize,
+    // the columns for the text of an item, as of the last draw
+    width: AtomicUsize,
     tabstop: usize,
+    // the labels of the visible items are shown while jumping
+    jumping: bool,
+    jump_labels: Vec<char>,
 
     // Options
     multi_selection: bool,
     reverse: bool,
     no_hscroll: bool,
+    // start the items at the first match of the pattern, unless the query matched
+    skip_to_pattern: Option<Regex>,
+    // show the long items on several rows instead of cutting them
+    wrap: bool,
+    // align the columns (split by the delimiter) of the items on screen
+    tabular: bool,
+    delimiter: Arc<Regex>,
+    ellipsis: String,
     theme: Arc<ColorTheme>,
 }
 
@@ -56,16 +111,30 @@ impl Selection {
     pub fn new() -> Self {
         Selection {
             criterion: DEFAULT_CRITERION.clone(),
+            sort: true,
+            sort_toggled: false,
+            tac: false,
             items: OrderedVec::new(build_compare_function(DEFAULT_CRITERION.clone())),
+            frecency: None,
             selected: HashMap::new(),
+            pre_select: None,
+            cursor_on_exact: false,
             item_cursor: 0,
             line_cursor: 0,
             hscroll_offset: 0,
             height: AtomicUsize::new(0),
-            tabstop: 0,
+            width: AtomicUsize::new(0),
+            tabstop: 8,
+            jumping: false,
+            jump_labels: DEFAULT_JUMP_LABELS.chars().collect(),
             multi_selection: false,
             reverse: false,
             no_hscroll: false,
+            skip_to_pattern: None,
+            wrap: false,
+            tabular: false,
+            delimiter: parse_delimiter(None),
+            ellipsis: "..".to_string(),
             theme: Arc::new(*DEFAULT_THEME),
         }
     }
@@ -81,6 +150,19 @@ impl Selection {
             self.multi_selection = true;
         }
 
+        if options.multi && (options.pre_select_n.is_some() || options.pre_select_pat.is_some()) {
+            self.pre_select = Some(PreSelect {
+                indices: options
+                    .pre_select_n
+                    .map(|indices| indices.split(',').filter_map(|idx| idx.trim().parse().ok()).collect())
+                    .unwrap_or_default(),
+                pattern: options.pre_select_pat.and_then(|pattern| Regex::new(pattern).ok()),
+                run_num: None,
+            });
+        }
+
+        self.cursor_on_exact = options.cursor_on_exact;
+
         if options.layout.starts_with("reverse") {
             self.reverse = true;
         }
@@ -89,16 +171,48 @@ impl Selection {
             self.no_hscroll = true;
         }
 
+        self.skip_to_pattern = options
+            .skip_to_pattern
+            .filter(|pattern| !pattern.is_empty())
+            .and_then(|pattern| Regex::new(pattern).ok());
+
+        self.wrap = options.wrap;
+        self.tabular = options.tabular;
+
+        if let Some(ellipsis) = options.ellipsis {
+            self.ellipsis = ellipsis.to_string();
+        }
+
         if let Some(tabstop_str) = options.tabstop {
             let tabstop = tabstop_str.parse::<usize>().unwrap_or(8);
             self.tabstop = max(1, tabstop);
         }
 
+        if let Some(jump_labels) = options.jump_labels.filter(|labels| !labels.is_empty()) {
+            self.jump_labels = jump_labels.chars().collect();
+        }
+
         if let Some(ref tie_breaker) = options.tiebreak {
-            let criterion = tie_breaker.split(',').filter_map(parse_criteria).collect();
+            let mut criterion: Vec<RankCriteria> = tie_breaker.split(',').filter_map(parse_criteria).collect();
+            // the criteria break the ties of the score, unless the score is ranked explicitly
+            if !criterion
+                .iter()
+                .any(|&criteria| criteria == RankCriteria::Score || criteria == RankCriteria::NegScore)
+            {
+                criterion.insert(0, RankCriteria::Score);
+            }
             self.criterion = criterion;
         }
 
+        self.frecency = options
+            .frecency
+            .map(Frecency::load)
+            .filter(|frecency| !frecency.is_empty());
+
+        // keep the input order, the query only filters
+        self.sort = !options.no_sort;
+        self.tac = options.tac;
+
         if options.tac {
             let criterion = self
                 .criterion
@@ -112,7 +226,53 @@ impl Selection {
             self.criterion = criterion;
         }
 
-        self.items = OrderedVec::new(build_compare_function(self.criterion.clone()));
+        // already validated by `SkimOptionsBuilder::build`, 0 keeps all the matches
+        let max_matches = options
+            .max_matches
+            .and_then(|max_matches| max_matches.parse::<usize>().ok())
+            .unwrap_or(0);
+        self.items = OrderedVec::new(build_compare_function(self.get_criterion())).max_len(max_matches);
+    }
+
+    /// the criterion the items are ranked with now
+    fn get_criterion(&self) -> Vec<RankCriteria> {
+        if self.sort {
+            self.criterion.clone()
+        } else if self.tac {
+            vec![RankCriteria::NegIndex]
+        } else {
+            vec![RankCriteria::Index]
+        }
+    }
+
+    /// whether matched items were dropped because of `--max-matches`
+    pub fn is_truncated(&self) -> bool {
+        self.items.is_truncated()
+    }
+
+    /// whether the items are sorted, `None` until toggle-sort was used
+    pub fn sort_state(&self) -> Option<bool> {
+        Some(self.sort).filter(|_| self.sort_toggled)
+    }
+
+    /// switch between ranking the items and keeping them in input order, the cursor stays on
+    /// the current item
+    pub fn act_toggle_sort(&mut self) {
+        let current = self.get_current_item().map(|item| item.get_full_index());
+
+        self.sort = !self.sort;
+        self.sort_toggled = true;
+        self.items.set_compare(build_compare_function(self.get_criterion()));
+
+        let position = current.and_then(|index| {
+            self.items
+                .iter()
+                .position(|matched| matched.item.get_full_index() == index)
+        });
+        if let Some(position) = position {
+            self.item_cursor = position.saturating_sub(self.line_cursor);
+            self.line_cursor = position - self.item_cursor;
+        }
     }
 
     pub fn theme(mut self, theme: Arc<ColorTheme>) -> Self {
@@ -120,7 +280,34 @@ impl Selection {
         self
     }
 
-    pub fn append_sorted_items(&mut self, items: Vec<MatchedItem>) {
+    pub fn delimiter(mut self, delimiter: Arc<Regex>) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn append_sorted_items(&mut self, mut items: Vec<MatchedItem>) {
+        if let Some(frecency) = self.frecency.as_ref() {
+            // the bonus is added to the score, unless the criteria rank by it on its own
+            let bonus_in_score = !self
+                .criterion
+                .iter()
+                .any(|&criteria| criteria == RankCriteria::Frecency || criteria == RankCriteria::NegFrecency);
+            for matched in items.iter_mut() {
+                let bonus = frecency.bonus(&matched.item.get_output_text());
+                if bonus_in_score {
+                    matched.rank.score -= bonus;
+                }
+                matched.rank.frecency = -bonus;
+            }
+        }
+
+        if let Some(pre_select) = self.pre_select.as_mut() {
+            for matched in items.iter().filter(|matched| pre_select.should_select(&matched.item)) {
+                self.selected
+                    .insert(matched.item.get_full_index(), matched.item.clone());
+            }
+        }
+
         self.items.append_ordered(items);
 
         let height = self.height.load(Ordering::Relaxed);
@@ -135,10 +322,56 @@ impl Selection {
         }
     }
 
+    /// called once the initial items are loaded, later matches are not pre-selected, so that
+    /// the items deselected by the user stay deselected
+    pub fn stop_pre_select(&mut self) {
+        self.pre_select = None;
+    }
+
+    /// called once the initial items are matched, move the cursor to the best ranked item containing
+    /// the query as it is (`--cursor-on-exact`), it stays on the top item if there is none
+    pub fn move_to_exact_match(&mut self, query: &str) {
+        if !mem::replace(&mut self.cursor_on_exact, false) || query.is_empty() {
+            return;
+        }
+
+        let exact = self
+            .items
+            .iter()
+            .position(|matched| matched.item.get_text().contains(query));
+        if let Some(item_idx) = exact {
+            self.move_cursor_to(item_idx);
+        }
+    }
+
+    // the item is shown on the last line of the screen if it isn't on the first page
+    fn move_cursor_to(&mut self, item_idx: usize) {
+        let height = max(self.height.load(Ordering::Relaxed), 1);
+        self.item_cursor = (item_idx + 1).saturating_sub(height);
+        self.line_cursor = item_idx - self.item_cursor;
+        self.scroll_to_cursor();
+    }
+
     pub fn clear(&mut self) {
         self.items.clear();
     }
 
+    /// move the cursor to the `position`th matched item (1-based), a negative position counts
+    /// from the last item, the position is clamped to the matched items
+    pub fn act_move_to_position(&mut self, position: i32) {
+        let item_len = self.items.len() as i32;
+        if item_len == 0 {
+            return;
+        }
+
+        let item_idx = if position < 0 {
+            item_len + position
+        } else {
+            position - 1
+        };
+        self.move_cursor_to(max(0, min(item_idx, item_len - 1)) as usize);
+    }
+
     pub fn act_move_line_cursor(&mut self, diff: i32) {
         let diff = if self.reverse { -diff } else { diff };
 
@@ -152,7 +385,7 @@ impl Selection {
         if line_cursor >= height {
             item_cursor += line_cursor - height + 1;
             item_cursor = max(0, min(item_cursor, item_len - height));
-            line_cursor = min(height - 1, item_len - item_cursor);
+            line_cursor = min(height - 1, item_len - 1 - item_cursor);
         } else if line_cursor < 0 {
             item_cursor += line_cursor;
             item_cursor = max(item_cursor, 0);
@@ -165,6 +398,146 @@ impl Selection {
 
         self.item_cursor = item_cursor as usize;
         self.line_cursor = line_cursor as usize;
+        self.scroll_to_cursor();
+    }
+
+    /// move the cursor to the item drawn on the `line_no`th line of the list,
+    /// return false if there is no item on that line
+    pub fn act_move_to_line(&mut self, line_no: usize) -> bool {
+        let height = self.height.load(Ordering::Relaxed);
+        if line_no >= height {
+            return false;
+        }
+
+        let line_cursor = if self.reverse { line_no } else { height - 1 - line_no };
+        if self.wrap {
+            // the rows are counted from the screen start, as the items are
+            let mut rows = 0;
+            for item_idx in self.item_cursor..self.items.len() {
+                rows += self.item_rows(item_idx);
+                if line_cursor < rows {
+                    self.line_cursor = item_idx - self.item_cursor;
+                    self.scroll_to_cursor();
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        if self.item_cursor + line_cursor >= self.items.len() {
+            return false;
+        }
+
+        self.line_cursor = line_cursor;
+        true
+    }
+
+    pub fn start_jump(&mut self) {
+        self.jumping = true;
+    }
+
+    pub fn stop_jump(&mut self) {
+        self.jumping = false;
+    }
+
+    /// leave the jump mode and move the cursor to the visible item with the label,
+    /// return false if no item has that label
+    pub fn act_jump(&mut self, label: char) -> bool {
+        self.stop_jump();
+
+        let visible = self.num_visible_items();
+        match self.jump_labels.iter().position(|&ch| ch == label) {
+            Some(line_cursor) if line_cursor < visible && self.item_cursor + line_cursor < self.items.len() => {
+                self.line_cursor = line_cursor;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// lines to jump for a page (keeping one line of the last page) or half a page,
+    /// items rather than lines with `--wrap`
+    fn page_size(&self, half: bool) -> i32 {
+        let height = self.num_visible_items();
+        let page = if half { height / 2 } else { height.saturating_sub(1) };
+        max(page, 1) as i32
+    }
+
+    /// the rows the item takes, more than one only for a long item with `--wrap`
+    fn item_rows(&self, item_idx: usize) -> usize {
+        let width = self.width.load(Ordering::Relaxed);
+        match self.items.get(item_idx) {
+            Some(matched) if self.wrap && width > 0 => {
+                wrap_text(matched.item.get_text(), width, WRAP_INDENT, self.tabstop)
+                    .last()
+                    .map(|&(row, _, _)| row + 1)
+                    .unwrap_or(1)
+            }
+            _ => 1,
+        }
+    }
+
+    /// the number of items shown (maybe partly) from the screen start, the height without `--wrap`
+    fn num_visible_items(&self) -> usize {
+        let height = self.height.load(Ordering::Relaxed);
+        if !self.wrap {
+            return height;
+        }
+
+        let mut rows = 0;
+        let mut count = 0;
+        for item_idx in self.item_cursor..self.items.len() {
+            if rows >= height {
+                break;
+            }
+            rows += self.item_rows(item_idx);
+            count += 1;
+        }
+        count
+    }
+
+    /// with `--wrap`, scroll the list until the rows of the current item are on screen,
+    /// or its first rows if it is higher than the screen
+    fn scroll_to_cursor(&mut self) {
+        if !self.wrap {
+            return;
+        }
+
+        let height = self.height.load(Ordering::Relaxed);
+        let mut rows: usize = (self.item_cursor..=self.item_cursor + self.line_cursor)
+            .map(|item_idx| self.item_rows(item_idx))
+            .sum();
+        while self.line_cursor > 0 && rows > height {
+            rows -= self.item_rows(self.item_cursor);
+            self.item_cursor += 1;
+            self.line_cursor -= 1;
+        }
+    }
+
+    /// keep the current item on screen after the list height changed (e.g. the terminal was
+    /// resized), and fill the freed lines with the items above the screen.
+    /// Return true if the view was scrolled.
+    pub fn fit_to_height(&mut self) -> bool {
+        let height = self.height.load(Ordering::Relaxed);
+        if height == 0 {
+            return false;
+        }
+
+        let (item_cursor, line_cursor) = (self.item_cursor, self.line_cursor);
+        if self.wrap {
+            // the rows of the items changed with the width too
+            self.scroll_to_cursor();
+        } else if self.line_cursor >= height {
+            let shift = self.line_cursor - (height - 1);
+            self.item_cursor += shift;
+            self.line_cursor -= shift;
+        } else if self.item_cursor + height > self.items.len() {
+            let shift = min(self.item_cursor, self.item_cursor + height - self.items.len());
+            self.item_cursor -= shift;
+            self.line_cursor += shift;
+        }
+
+        (item_cursor, line_cursor) != (self.item_cursor, self.line_cursor)
     }
 
     pub fn act_toggle(&mut self) {
@@ -248,6 +621,11 @@ impl Selection {
         selected
     }
 
+    #[cfg(test)]
+    pub fn get_delimiter(&self) -> &Arc<Regex> {
+        &self.delimiter
+    }
+
     pub fn get_current_item_idx(&self) -> usize {
         self.item_cursor + self.line_cursor
     }
@@ -264,14 +642,20 @@ impl Selection {
         let item_idx = self.get_current_item_idx();
         self.items.get(item_idx).map(|item| item.item.clone())
     }
+
+    /// all the items currently matched, in no particular order
+    pub fn get_matched_items(&self) -> Vec<Arc<Item>> {
+        self.items.iter().map(|matched| matched.item.clone()).collect()
+    }
 }
 
 impl EventHandler for Selection {
     fn accept_event(&self, event: Event) -> bool {
         use crate::event::Event::*;
         match event {
-            EvActUp | EvActDown | EvActToggle | EvActToggleAll | EvActSelectAll | EvActDeselectAll | EvActPageDown
-            | EvActPageUp | EvActScrollLeft | EvActScrollRight => true,
+            EvActUp | EvActDown | EvActToggle | EvActToggleAll | EvActSelectAll | EvActDeselectAll
+            | EvActClearSelection | EvActPageDown | EvActPageUp | EvActHalfPageDown | EvActHalfPageUp
+            | EvActScrollLeft | EvActScrollRight | EvActToggleSort | EvActFirst | EvActLast | EvActPos => true,
             _ => false,
         }
     }
@@ -294,16 +678,42 @@ impl EventHandler for Selection {
             EvActSelectAll => {
                 self.act_select_all();
             }
-            EvActDeselectAll => {
+            EvActDeselectAll | EvActClearSelection => {
                 self.act_deselect_all();
             }
             EvActPageDown => {
-                let height = 1 - (self.height.load(Ordering::Relaxed) as i32);
-                self.act_move_line_cursor(height);
+                let page = self.page_size(false);
+                self.act_move_line_cursor(-page);
             }
             EvActPageUp => {
-                let height = (self.height.load(Ordering::Relaxed) as i32) - 1;
-                self.act_move_line_cursor(height);
+                let page = self.page_size(false);
+                self.act_move_line_cursor(page);
+            }
+            EvActHalfPageDown => {
+                let page = self.page_size(true);
+                self.act_move_line_cursor(-page);
+            }
+            EvActHalfPageUp => {
+                let page = self.page_size(true);
+                self.act_move_line_cursor(page);
+            }
+            EvActFirst => {
+                self.act_move_to_position(1);
+            }
+            EvActLast => {
+                self.act_move_to_position(-1);
+            }
+            EvActPos => {
+                let position = arg
+                    .downcast_ref::<Option<String>>()
+                    .and_then(|arg| arg.as_ref())
+                    .and_then(|arg| arg.trim().parse::<i32>().ok());
+                if let Some(position) = position {
+                    self.act_move_to_position(position);
+                }
+            }
+            EvActToggleSort => {
+                self.act_toggle_sort();
             }
             EvActScrollLeft => {
                 self.act_scroll(*arg.downcast_ref::<i32>().unwrap_or(&-1));
@@ -318,22 +728,14 @@ impl EventHandler for Selection {
 }
 
 impl Selection {
-    fn draw_item(
+    // print the mark of a selected item, return the attributes of the text and the matches
+    fn print_selected_mark(
         &self,
         canvas: &mut dyn Canvas,
         row: usize,
         matched_item: &MatchedItem,
         is_current: bool,
-    ) -> Result<()> {
-        let (screen_width, screen_height) = canvas.size()?;
-
-        // update item heights
-        self.height.store(screen_height, Ordering::Relaxed);
-
-        if screen_width < 3 {
-            return Err("screen width is too small".into());
-        }
-
+    ) -> (Attr, Attr) {
         let index = matched_item.item.get_full_index();
 
         let default_attr = if is_current {
@@ -355,17 +757,244 @@ impl Selection {
             let _ = canvas.print_with_attr(row, 1, " ", default_attr);
         }
 
+        (default_attr, matched_attr)
+    }
+
+    // the label before the item: the cursor, or the jump label of the item while jumping
+    fn cursor_label(&self, line_cursor: usize) -> char {
+        match self.jump_labels.get(line_cursor) {
+            Some(&ch) if self.jumping => ch,
+            _ if line_cursor == self.line_cursor => '>',
+            _ => ' ',
+        }
+    }
+
+    /// `--wrap`: print the first `rows` rows of the item from `row` on, the continuation rows
+    /// are indented
+    fn draw_wrapped_item(
+        &self,
+        canvas: &mut dyn Canvas,
+        row: usize,
+        rows: usize,
+        matched_item: &MatchedItem,
+        is_current: bool,
+    ) -> Result<()> {
+        let (screen_width, _screen_height) = canvas.size()?;
+        if screen_width < 3 {
+            return Err("screen width is too small".into());
+        }
+
+        let (default_attr, matched_attr) = self.print_selected_mark(canvas, row, matched_item, is_current);
+
+        let item = &matched_item.item;
+        let text = item.get_text();
+        let text_attrs: Vec<Attr> = match item.get_text_struct() {
+            Some(text_struct) if text_struct.has_attrs() => {
+                text_struct.iter().map(|(_, attr)| default_attr.extend(attr)).collect()
+            }
+            _ => Vec::new(),
+        };
+        let is_matched = |ch_idx: usize, byte_idx: usize| match matched_item.matched_range {
+            Some(MatchedRange::Chars(ref matched_indices)) => matched_indices.binary_search(&ch_idx).is_ok(),
+            Some(MatchedRange::ByteRange(start, end)) => byte_idx >= start && byte_idx < end,
+            None => false,
+        };
+
+        let placements = wrap_text(text, screen_width - 2, WRAP_INDENT, self.tabstop);
+        for (ch_idx, ((byte_idx, ch), &(text_row, col, width))) in
+            text.char_indices().zip(placements.iter()).enumerate()
+        {
+            if text_row >= rows {
+                break;
+            }
+
+            let attr = if is_matched(ch_idx, byte_idx) {
+                matched_attr
+            } else {
+                text_attrs.get(ch_idx).cloned().unwrap_or(default_attr)
+            };
+            let col = 2 + col + if text_row > 0 { WRAP_INDENT } else { 0 };
+            if ch == '\t' {
+                for col in col..col + width {
+                    let _ = canvas.put_char_with_attr(row + text_row, col, ' ', attr);
+                }
+            } else {
+                let _ = canvas.put_char_with_attr(row + text_row, col, ch, attr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `--wrap`: draw the items from the screen start, as many rows of each as it takes
+    fn draw_wrapped(&self, canvas: &mut dyn Canvas, screen_height: usize) -> Result<()> {
+        // the rows taken so far, from the screen start
+        let mut rows_taken = 0;
+        for item_idx in self.item_cursor..self.items.len() {
+            if rows_taken >= screen_height {
+                break;
+            }
+
+            let line_cursor = item_idx - self.item_cursor;
+            let rows = min(self.item_rows(item_idx), screen_height - rows_taken);
+            let row = if self.reverse {
+                rows_taken
+            } else {
+                screen_height - rows_taken - rows
+            };
+            let _ = canvas.put_char_with_attr(row, 0, self.cursor_label(line_cursor), self.theme.cursor());
+
+            let item = self
+                .items
+                .get(item_idx)
+                .unwrap_or_else(|| panic!("model:draw_items: failed to get item at {}", item_idx));
+            let _ = self.draw_wrapped_item(canvas, row, rows, item, line_cursor == self.line_cursor);
+            rows_taken += rows;
+        }
+
+        Ok(())
+    }
+
+    /// `--tabular`: the display width of every column, the widest among the items
+    fn column_widths(&self, items: Range<usize>) -> Vec<usize> {
+        let mut widths: Vec<usize> = Vec::new();
+        for item_idx in items {
+            let text = match self.items.get(item_idx) {
+                Some(matched_item) => matched_item.item.get_text(),
+                None => continue,
+            };
+            for (column, (start, end)) in get_columns(&self.delimiter, text).into_iter().enumerate() {
+                let width = text[start..end].width();
+                match widths.get_mut(column) {
+                    Some(max_width) => *max_width = max(*max_width, width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        widths
+    }
+
+    /// `--tabular`: the text of the item with every column padded to its width, and its ANSI
+    /// attributes and matched range moved to the padded text
+    fn align_columns(
+        &self,
+        matched_item: &MatchedItem,
+        column_widths: &[usize],
+    ) -> (String, Option<AnsiString>, Option<MatchedRange>) {
         let item = &matched_item.item;
         let text = item.get_text();
-        let (match_start_char, match_end_char) = match matched_item.matched_range {
-            Some(MatchedRange::Chars(ref matched_indices)) => {
+        let attrs: Option<Vec<Attr>> = item
+            .get_text_struct()
+            .filter(|text_struct| text_struct.has_attrs())
+            .map(|text_struct| text_struct.iter().map(|(_, attr)| attr).collect());
+
+        let columns = get_columns(&self.delimiter, text);
+        let mut aligned = String::new();
+        let mut aligned_attrs = Vec::new();
+        // the char index in the aligned text of every char of the text, none for the whitespace
+        // replaced by the padding
+        let mut positions = vec![None; text.chars().count()];
+        let mut num_aligned_chars = 0;
+        for (column, &(start, end)) in columns.iter().enumerate() {
+            let char_start = text[..start].chars().count();
+            for (offset, ch) in text[start..end].chars().enumerate() {
+                positions[char_start + offset] = Some(num_aligned_chars);
+                aligned.push(ch);
+                aligned_attrs.push(
+                    attrs
+                        .as_ref()
+                        .map(|attrs| attrs[char_start + offset])
+                        .unwrap_or_default(),
+                );
+                num_aligned_chars += 1;
+            }
+
+            // the columns are separated by a space, the last one isn't padded
+            if column + 1 < columns.len() {
+                let width = column_widths.get(column).cloned().unwrap_or(0);
+                for _ in text[start..end].width()..width + 1 {
+                    aligned.push(' ');
+                    aligned_attrs.push(Attr::default());
+                    num_aligned_chars += 1;
+                }
+            }
+        }
+
+        let text_struct = attrs.map(|_| {
+            let fragments = aligned_attrs
+                .into_iter()
+                .zip(aligned.chars())
+                .map(|(attr, ch)| (attr, Cow::Owned(ch.to_string())))
+                .collect();
+            AnsiString::new(aligned.clone(), fragments)
+        });
+
+        let matched_range = matched_item.matched_range.as_ref().map(|matched_range| {
+            let matched_chars: Vec<usize> = match matched_range {
+                MatchedRange::Chars(matched_indices) => matched_indices.clone(),
+                &MatchedRange::ByteRange(start, end) => text
+                    .char_indices()
+                    .enumerate()
+                    .filter(|&(_, (byte_idx, _))| byte_idx >= start && byte_idx < end)
+                    .map(|(ch_idx, _)| ch_idx)
+                    .collect(),
+            };
+            MatchedRange::Chars(
+                matched_chars
+                    .into_iter()
+                    .filter_map(|ch_idx| positions.get(ch_idx).cloned().and_then(|position| position))
+                    .collect(),
+            )
+        });
+
+        (aligned, text_struct, matched_range)
+    }
+
+    fn draw_item(
+        &self,
+        canvas: &mut dyn Canvas,
+        row: usize,
+        matched_item: &MatchedItem,
+        is_current: bool,
+        column_widths: &[usize],
+    ) -> Result<()> {
+        let (screen_width, screen_height) = canvas.size()?;
+
+        // update item heights
+        self.height.store(screen_height, Ordering::Relaxed);
+
+        if screen_width < 3 {
+            return Err("screen width is too small".into());
+        }
+
+        let (default_attr, matched_attr) = self.print_selected_mark(canvas, row, matched_item, is_current);
+
+        let item = &matched_item.item;
+        let aligned = if column_widths.is_empty() {
+            None
+        } else {
+            Some(self.align_columns(matched_item, column_widths))
+        };
+        let (text, text_struct, matched_range) = match aligned {
+            Some((ref text, ref text_struct, ref matched_range)) => {
+                (text.as_str(), text_struct.as_ref(), matched_range.as_ref())
+            }
+            None => (
+                item.get_text(),
+                item.get_text_struct(),
+                matched_item.matched_range.as_ref(),
+            ),
+        };
+
+        let (match_start_char, match_end_char) = match matched_range {
+            Some(MatchedRange::Chars(matched_indices)) => {
                 if !matched_indices.is_empty() {
                     (matched_indices[0], matched_indices[matched_indices.len() - 1] + 1)
                 } else {
                     (0, 0)
                 }
             }
-            Some(MatchedRange::ByteRange(match_start, match_end)) => {
+            Some(&MatchedRange::ByteRange(match_start, match_end)) => {
                 let match_start_char = text[..match_start].chars().count();
                 let diff = text[match_start..match_end].chars().count();
                 (match_start_char, match_start_char + diff)
@@ -374,8 +1003,30 @@ impl Selection {
         };
 
         let container_width = screen_width - 2;
-        let (shift, full_width) =
-            reshape_string(&text, container_width, match_start_char, match_end_char, self.tabstop);
+        let skipped = match self.skip_to_pattern {
+            // the part matching the query comes first
+            Some(ref pattern) if match_end_char == 0 => {
+                skip_to_pattern(text, pattern, container_width, self.tabstop, self.ellipsis.width())
+            }
+            _ => None,
+        };
+
+        let (shift, full_width) = if let Some(skipped) = skipped {
+            skipped
+        } else if self.no_hscroll {
+            // no need to find the shift that shows the match
+            let full_width = accumulate_text_width(text, self.tabstop).last().cloned().unwrap_or(0);
+            (0, full_width)
+        } else {
+            reshape_string(
+                text,
+                container_width,
+                match_start_char,
+                match_end_char,
+                self.tabstop,
+                self.ellipsis.width(),
+            )
+        };
 
         // 行の箱を作成。これはutil.rsから作られている
         let mut printer = LinePrinter::builder()
@@ -383,26 +1034,27 @@ impl Selection {
             .col(2)
             .tabstop(self.tabstop)
             .container_width(container_width)
-            .shift(if self.no_hscroll { 0 } else { shift })
+            .shift(shift)
             .text_width(full_width)
             .hscroll_offset(self.hscroll_offset)
+            .ellipsis(&self.ellipsis)
             .build();
 
         // print out the original content
-        if item.get_text_struct().is_some() && item.get_text_struct().as_ref().unwrap().has_attrs() {
-            for (ch, attr) in item.get_text_struct().as_ref().unwrap().iter() {
+        if let Some(text_struct) = text_struct.filter(|text_struct| text_struct.has_attrs()) {
+            for (ch, attr) in text_struct.iter() {
                 printer.print_char(canvas, ch, default_attr.extend(attr), false); // 作った箱をprint_charでcanvasにwrite
             }
         } else {
-            for ch in item.get_text().chars() {
+            for ch in text.chars() {
                 printer.print_char(canvas, ch, default_attr, false); // rowの結果を一文字ずつセットしている
             }
         }
 
         // print the highlighted content
         printer.reset();
-        match matched_item.matched_range {
-            Some(MatchedRange::Chars(ref matched_indices)) => {
+        match matched_range {
+            Some(MatchedRange::Chars(matched_indices)) => {
                 let mut matched_indices_iter = matched_indices.iter().peekable();
 
                 for (ch_idx, ch) in text.chars().enumerate() {
@@ -418,7 +1070,7 @@ impl Selection {
                 }
             }
 
-            Some(MatchedRange::ByteRange(start, end)) => {
+            Some(&MatchedRange::ByteRange(start, end)) => {
                 for (idx, ch) in text.char_indices() {
                     printer.print_char(canvas, ch, matched_attr, !(idx >= start && idx < end));
                 }
@@ -433,12 +1085,23 @@ impl Selection {
 
 impl Draw for Selection {
     fn draw(&self, canvas: &mut dyn Canvas) -> Result<()> {
-        let (_screen_width, screen_height) = canvas.size()?;
+        let (screen_width, screen_height) = canvas.size()?;
         canvas.clear()?;
 
+        if self.wrap {
+            self.height.store(screen_height, Ordering::Relaxed);
+            self.width.store(screen_width.saturating_sub(2), Ordering::Relaxed);
+            return self.draw_wrapped(canvas, screen_height);
+        }
+
         let item_idx_lower = self.item_cursor;
         let max_upper = self.item_cursor + screen_height;
         let item_idx_upper = min(max_upper, self.items.len());
+        let column_widths = if self.tabular {
+            self.column_widths(item_idx_lower..item_idx_upper)
+        } else {
+            Vec::new()
+        };
 
         for item_idx in item_idx_lower..item_idx_upper {
             let line_cursor = item_idx - item_idx_lower;
@@ -450,9 +1113,9 @@ impl Draw for Selection {
                 screen_height - 1 - line_cursor
             };
 
-            // print the cursor label
-            let label = if line_cursor == self.line_cursor { ">" } else { " " };
-            let _next_col = canvas.print_with_attr(line_no, 0, label, self.theme.cursor()).unwrap();
+            let _next_col = canvas
+                .put_char_with_attr(line_no, 0, self.cursor_label(line_cursor), self.theme.cursor())
+                .unwrap();
 
             let item = self
                 .items
@@ -460,7 +1123,7 @@ impl Draw for Selection {
                 .unwrap_or_else(|| panic!("model:draw_items: failed to get item at {}", item_idx));
 
             // rowの中身を表示している処理
-            let _ = self.draw_item(canvas, line_no, &item, line_cursor == self.line_cursor);
+            let _ = self.draw_item(canvas, line_no, item, line_cursor == self.line_cursor, &column_widths);
         }
 
         Ok(())
@@ -528,8 +1191,474 @@ fn build_compare_function(criterion: Vec<RankCriteria>) -> CompareFunction<Match
                         return b.rank.score.cmp(&a.rank.score);
                     }
                 }
+                RankCriteria::Frecency => {
+                    if a.rank.frecency == b.rank.frecency {
+                        continue;
+                    } else {
+                        return a.rank.frecency.cmp(&b.rank.frecency);
+                    }
+                }
+                RankCriteria::NegFrecency => {
+                    if a.rank.frecency == b.rank.frecency {
+                        continue;
+                    } else {
+                        return b.rank.frecency.cmp(&a.rank.frecency);
+                    }
+                }
             }
         }
         CmpOrd::Equal
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::item::Rank;
+    use crate::options::SkimOptionsBuilder;
+    use crate::test_canvas::TestCanvas;
+    use regex::Regex;
+
+    fn selection_with_items(num: usize, height: usize) -> Selection {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let mut selection = Selection::new();
+        selection.height.store(height, Ordering::Relaxed);
+        selection.append_sorted_items(
+            (0..num)
+                .map(|idx| {
+                    let item = Item::new(idx.to_string().into(), false, &[], &[], &delimiter, (0, idx));
+                    MatchedItem::builder(Arc::new(item)).build()
+                })
+                .collect(),
+        );
+        selection
+    }
+
+    fn send(selection: &mut Selection, event: Event) -> usize {
+        selection.handle(event, &(Box::new(true) as EventArg));
+        selection.get_current_item_idx()
+    }
+
+    #[test]
+    fn test_page_navigation() {
+        let mut selection = selection_with_items(30, 10);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 9);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 18);
+        assert_eq!(send(&mut selection, Event::EvActHalfPageUp), 23);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 29);
+        assert_eq!(send(&mut selection, Event::EvActHalfPageDown), 24);
+        assert_eq!(send(&mut selection, Event::EvActPageDown), 15);
+        assert_eq!(send(&mut selection, Event::EvActPageDown), 6);
+        assert_eq!(send(&mut selection, Event::EvActPageDown), 0);
+    }
+
+    #[test]
+    fn test_move_to_position() {
+        let mut selection = selection_with_items(30, 10);
+        let pos = |n: &str| Box::new(Some(n.to_string())) as EventArg;
+
+        selection.handle(Event::EvActPos, &pos("3"));
+        assert_eq!(selection.get_current_item_idx(), 2);
+        assert_eq!(send(&mut selection, Event::EvActLast), 29);
+        assert_eq!(send(&mut selection, Event::EvActFirst), 0);
+
+        selection.handle(Event::EvActPos, &pos("-2"));
+        assert_eq!(selection.get_current_item_idx(), 28);
+        selection.handle(Event::EvActPos, &pos("100"));
+        assert_eq!(selection.get_current_item_idx(), 29);
+        selection.handle(Event::EvActPos, &pos("0"));
+        assert_eq!(selection.get_current_item_idx(), 0);
+    }
+
+    #[test]
+    fn test_move_to_line() {
+        // the lines are counted from the top, the first item is drawn at the bottom
+        let mut selection = selection_with_items(30, 10);
+        assert!(selection.act_move_to_line(0));
+        assert_eq!(selection.get_current_item_idx(), 9);
+        assert!(selection.act_move_to_line(9));
+        assert_eq!(selection.get_current_item_idx(), 0);
+
+        // reverse and reverse-list draw the first item at the top
+        let mut selection = selection_with_items(3, 10);
+        selection.reverse = true;
+        assert!(selection.act_move_to_line(2));
+        assert_eq!(selection.get_current_item_idx(), 2);
+        assert!(!selection.act_move_to_line(3));
+        assert_eq!(selection.get_current_item_idx(), 2);
+    }
+
+    #[test]
+    fn test_fit_to_height() {
+        let mut selection = selection_with_items(30, 10);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 9);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 18);
+        assert!(!selection.fit_to_height());
+
+        // shrink: the current item stays on the last line
+        selection.height.store(5, Ordering::Relaxed);
+        assert!(selection.fit_to_height());
+        assert_eq!(selection.get_current_item_idx(), 18);
+        assert_eq!(selection.line_cursor, 4);
+
+        // grow: the items above the screen are shown again
+        selection.height.store(40, Ordering::Relaxed);
+        assert!(selection.fit_to_height());
+        assert_eq!(selection.get_current_item_idx(), 18);
+        assert_eq!(selection.item_cursor, 0);
+        assert_eq!(selection.line_cursor, 18);
+    }
+
+    #[test]
+    fn test_move_to_exact_match() {
+        let mut selection = selection_with_items(30, 10);
+        selection.move_to_exact_match("25");
+        assert_eq!(selection.get_current_item_idx(), 0);
+
+        selection.cursor_on_exact = true;
+        selection.move_to_exact_match("25");
+        assert_eq!(selection.get_current_item_idx(), 25);
+        assert_eq!((selection.item_cursor, selection.line_cursor), (16, 9));
+        // only the initial match moves the cursor
+        send(&mut selection, Event::EvActDown);
+        selection.move_to_exact_match("25");
+        assert_eq!(selection.get_current_item_idx(), 24);
+
+        let mut selection = selection_with_items(30, 10);
+        selection.cursor_on_exact = true;
+        selection.move_to_exact_match("3");
+        assert_eq!((selection.item_cursor, selection.line_cursor), (0, 3));
+
+        // no exact match, stay on the top item
+        let mut selection = selection_with_items(30, 10);
+        selection.cursor_on_exact = true;
+        selection.move_to_exact_match("42");
+        assert_eq!(selection.get_current_item_idx(), 0);
+    }
+
+    #[test]
+    fn test_page_navigation_with_few_items() {
+        let mut selection = selection_with_items(5, 10);
+        assert_eq!(send(&mut selection, Event::EvActHalfPageUp), 4);
+        assert_eq!(send(&mut selection, Event::EvActPageDown), 0);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 4);
+        assert!(selection.get_current_item().is_some());
+
+        let mut selection = selection_with_items(0, 10);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 0);
+        assert!(selection.get_current_item().is_none());
+    }
+
+    #[test]
+    fn test_tabular() {
+        let delimiter = Arc::new(Regex::new(",").unwrap());
+        let options = SkimOptionsBuilder::default()
+            .tabular(true)
+            .layout("reverse")
+            .build()
+            .unwrap();
+        let mut selection = Selection::with_options(&options).delimiter(delimiter.clone());
+        let items = ["name,ready,age", "web-1,1/1,3d", "db,0/1"]
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                let item = Arc::new(Item::new((*text).into(), false, &[], &[], &delimiter, (0, idx)));
+                let builder = MatchedItem::builder(item).rank(Rank {
+                    index: idx as i64,
+                    ..Rank::default()
+                });
+                if idx == 1 {
+                    builder.matched_range(MatchedRange::ByteRange(6, 9)).build()
+                } else {
+                    builder.build()
+                }
+            })
+            .collect();
+        selection.append_sorted_items(items);
+
+        let mut canvas = TestCanvas::new(30, 4);
+        selection.draw(&mut canvas).unwrap();
+        assert_eq!(
+            canvas.rows(),
+            vec!["> name,  ready, age", "  web-1, 1/1,   3d", "  db,    0/1", ""]
+        );
+        // the match moves along with its column
+        assert_eq!(canvas.cell(1, 9), ('1', selection.theme.matched()));
+        assert_eq!(canvas.cell(1, 11), ('1', selection.theme.matched()));
+        assert_eq!(canvas.cell(1, 12), (',', selection.theme.normal()));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let options = SkimOptionsBuilder::default()
+            .wrap(true)
+            .layout("reverse")
+            .build()
+            .unwrap();
+        let mut selection = Selection::with_options(&options);
+        let items = ["abcdefghij", "xy", "0123456789abcd"]
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                let item = Arc::new(Item::new((*text).into(), false, &[], &[], &delimiter, (0, idx)));
+                MatchedItem::builder(item)
+                    .rank(Rank {
+                        index: idx as i64,
+                        ..Rank::default()
+                    })
+                    .matched_range(MatchedRange::ByteRange(5, 8))
+                    .build()
+            })
+            .collect();
+        selection.append_sorted_items(items);
+
+        // 6 columns for the text, 4 for the continuation rows
+        let mut canvas = TestCanvas::new(8, 4);
+        selection.draw(&mut canvas).unwrap();
+        assert_eq!(canvas.rows(), vec!["> abcdef", "    ghij", "  xy", "  012345"]);
+
+        // the cursor moves by item, the list scrolls until the current item is shown as a whole
+        assert_eq!(send(&mut selection, Event::EvActDown), 1);
+        assert_eq!(send(&mut selection, Event::EvActDown), 2);
+        selection.draw(&mut canvas).unwrap();
+        assert_eq!(canvas.rows(), vec!["  xy", "> 012345", "    6789", "    abcd"]);
+        // the match is highlighted on the continuation row as well
+        let current_match = selection.theme.current_match();
+        assert_eq!(canvas.cell(1, 7), ('5', current_match));
+        assert_eq!(canvas.cell(2, 4), ('6', current_match));
+        assert_eq!(canvas.cell(2, 6), ('8', selection.theme.current()));
+
+        // two items are on screen, a page is one item
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 1);
+        assert_eq!(send(&mut selection, Event::EvActPageUp), 0);
+        selection.draw(&mut canvas).unwrap();
+        assert_eq!(canvas.rows()[0], "> abcdef");
+
+        // a click on a continuation row picks its item
+        assert!(selection.act_move_to_line(3));
+        assert_eq!(selection.get_current_item_idx(), 2);
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut selection = selection_with_items(30, 10);
+        selection.handle(Event::EvActPageUp, &(Box::new(true) as EventArg));
+        selection.handle(Event::EvActPageUp, &(Box::new(true) as EventArg));
+        assert_eq!(selection.get_current_item_idx(), 18);
+
+        // labels start from the first visible item
+        selection.start_jump();
+        assert!(selection.act_jump('c'));
+        assert_eq!(selection.get_current_item_idx(), 11);
+        assert!(!selection.jumping);
+
+        // beyond the screen or an unknown label
+        selection.start_jump();
+        assert!(!selection.act_jump('k'));
+        assert!(!selection.act_jump('?'));
+        assert_eq!(selection.get_current_item_idx(), 11);
+
+        let mut selection = selection_with_items(2, 10);
+        selection.jump_labels = "12".chars().collect();
+        assert!(selection.act_jump('2'));
+        assert_eq!(selection.get_current_item_idx(), 1);
+        assert!(!selection.act_jump('a'));
+    }
+
+    #[test]
+    fn test_toggle_sort() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let mut selection = Selection::new();
+        selection.height.store(10, Ordering::Relaxed);
+        // the later items have the better scores
+        selection.append_sorted_items(
+            (0..5)
+                .map(|idx| {
+                    let item = Item::new(idx.to_string().into(), false, &[], &[], &delimiter, (0, idx));
+                    let rank = Rank {
+                        score: -(idx as i64),
+                        index: idx as i64,
+                        begin: 0,
+                        end: 0,
+                        frecency: 0,
+                    };
+                    MatchedItem::builder(Arc::new(item)).rank(rank).build()
+                })
+                .collect(),
+        );
+        let texts = |selection: &Selection| -> Vec<String> {
+            selection
+                .items
+                .iter()
+                .map(|matched| matched.item.get_text().to_string())
+                .collect()
+        };
+
+        assert_eq!(selection.sort_state(), None);
+        assert_eq!(texts(&selection), vec!["4", "3", "2", "1", "0"]);
+        send(&mut selection, Event::EvActUp);
+        assert_eq!(selection.get_current_item().unwrap().get_text(), "3");
+
+        // the cursor follows the item
+        send(&mut selection, Event::EvActToggleSort);
+        assert_eq!(selection.sort_state(), Some(false));
+        assert_eq!(texts(&selection), vec!["0", "1", "2", "3", "4"]);
+        assert_eq!(selection.get_current_item().unwrap().get_text(), "3");
+
+        send(&mut selection, Event::EvActToggleSort);
+        assert_eq!(selection.sort_state(), Some(true));
+        assert_eq!(texts(&selection), vec!["4", "3", "2", "1", "0"]);
+        assert_eq!(selection.get_current_item().unwrap().get_text(), "3");
+    }
+
+    #[test]
+    fn test_tiebreak_begin() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let options = SkimOptionsBuilder::default()
+            .tiebreak(Some("begin".to_string()))
+            .build()
+            .unwrap();
+        let mut selection = Selection::with_options(&options);
+        let matched = |text: &str, idx: usize, score: i64, begin: i64| {
+            let item = Item::new(text.to_string().into(), false, &[], &[], &delimiter, (0, idx));
+            let rank = Rank {
+                score,
+                index: idx as i64,
+                begin,
+                end: begin + 2,
+                frecency: 0,
+            };
+            MatchedItem::builder(Arc::new(item)).rank(rank).build()
+        };
+
+        // the score still ranks first, the earlier match wins the tie
+        selection.append_sorted_items(vec![
+            matched("xxab", 0, -10, 2),
+            matched("ab", 1, -10, 0),
+            matched("xab", 2, -10, 1),
+            matched("xxxxxab", 3, -20, 5),
+        ]);
+        let texts: Vec<String> = selection
+            .items
+            .iter()
+            .map(|matched| matched.item.get_text().to_string())
+            .collect();
+        assert_eq!(texts, vec!["xxxxxab", "ab", "xab", "xxab"]);
+    }
+
+    #[test]
+    fn test_no_sort_keeps_input_order() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let ranked = |no_sort: bool, tac: bool| -> Vec<String> {
+            let options = SkimOptionsBuilder::default().no_sort(no_sort).tac(tac).build().unwrap();
+            let mut selection = Selection::with_options(&options);
+            // the later items have the better scores, they come in more than one batch
+            let matched = |idx: usize| {
+                let item = Item::new(idx.to_string().into(), false, &[], &[], &delimiter, (0, idx));
+                let rank = Rank {
+                    score: -(idx as i64),
+                    index: idx as i64,
+                    begin: 0,
+                    end: 0,
+                    frecency: 0,
+                };
+                MatchedItem::builder(Arc::new(item)).rank(rank).build()
+            };
+            selection.append_sorted_items(vec![matched(2), matched(0)]);
+            selection.append_sorted_items(vec![matched(3), matched(1)]);
+            selection
+                .items
+                .iter()
+                .map(|matched| matched.item.get_text().to_string())
+                .collect()
+        };
+
+        assert_eq!(ranked(false, false), vec!["3", "2", "1", "0"]);
+        assert_eq!(ranked(true, false), vec!["0", "1", "2", "3"]);
+        assert_eq!(ranked(true, true), vec!["3", "2", "1", "0"]);
+    }
+
+    #[test]
+    fn test_frecency() {
+        let path = std::env::temp_dir().join(format!("sk-test-selection-frecency-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        crate::frecency::record(path, vec!["b", "b"].into_iter()).unwrap();
+
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let ranked = |tiebreak: Option<&str>| -> Vec<String> {
+            let options = SkimOptionsBuilder::default()
+                .frecency(Some(path))
+                .tiebreak(tiebreak.map(str::to_string))
+                .build()
+                .unwrap();
+            let mut selection = Selection::with_options(&options);
+            let matched = |text: &str, idx: usize, score: i64| {
+                let item = Item::new(text.to_string().into(), false, &[], &[], &delimiter, (0, idx));
+                let rank = Rank {
+                    score,
+                    index: idx as i64,
+                    ..Rank::default()
+                };
+                MatchedItem::builder(Arc::new(item)).rank(rank).build()
+            };
+            selection.append_sorted_items(vec![matched("a", 0, -11), matched("b", 1, -10), matched("c", 2, -25)]);
+            selection
+                .items
+                .iter()
+                .map(|matched| matched.item.get_text().to_string())
+                .collect()
+        };
+
+        // the bonus is added to the score, a much better match still ranks first
+        assert_eq!(ranked(None), vec!["c", "b", "a"]);
+        // ranked by the tiebreak, the bonus is left out of the score
+        assert_eq!(ranked(Some("frecency")), vec!["c", "a", "b"]);
+        assert_eq!(ranked(Some("frecency,score")), vec!["b", "c", "a"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_pre_select() {
+        let delimiter = Regex::new(r"[\t\n ]+").unwrap();
+        let items = |run_num: usize, range: std::ops::Range<usize>| -> Vec<MatchedItem> {
+            range
+                .map(|idx| {
+                    let item = Item::new(idx.to_string().into(), false, &[], &[], &delimiter, (run_num, idx));
+                    MatchedItem::builder(Arc::new(item)).build()
+                })
+                .collect()
+        };
+        let selected = |selection: &Selection| -> Vec<(usize, usize)> {
+            let mut selected: Vec<_> = selection.selected.keys().cloned().collect();
+            selected.sort();
+            selected
+        };
+
+        let options = SkimOptionsBuilder::default()
+            .multi(true)
+            .pre_select_n(Some("0, 3,42"))
+            .pre_select_pat(Some("^1"))
+            .build()
+            .unwrap();
+        let mut selection = Selection::with_options(&options);
+        selection.append_sorted_items(items(0, 0..5));
+        selection.append_sorted_items(items(0, 5..12));
+        // the items of another run are left alone
+        selection.append_sorted_items(items(1, 0..5));
+        assert_eq!(selected(&selection), vec![(0, 0), (0, 1), (0, 3), (0, 10), (0, 11)]);
+
+        // the items matched again after the initial load keep the user's choice
+        selection.stop_pre_select();
+        selection.act_deselect_all();
+        selection.clear();
+        selection.append_sorted_items(items(0, 0..12));
+        assert!(selected(&selection).is_empty());
+
+        let options = SkimOptionsBuilder::default().pre_select_n(Some("0")).build().unwrap();
+        let mut selection = Selection::with_options(&options);
+        selection.append_sorted_items(items(0, 0..5));
+        assert!(selected(&selection).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+///! A canvas for the tests, it keeps the cells drawn on it
+use tuikit::prelude::*;
+use unicode_width::UnicodeWidthChar;
+
+/// The cells printed on a screen of a fixed size. The ones out of the screen are dropped as the
+/// terminal would.
+pub struct TestCanvas {
+    width: usize,
+    cells: Vec<Vec<Cell>>,
+}
+
+impl TestCanvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        TestCanvas {
+            width,
+            cells: vec![vec![Cell::default(); width]; height],
+        }
+    }
+
+    /// the character and its attribute at the position
+    pub fn cell(&self, row: usize, col: usize) -> (char, Attr) {
+        let cell = self.cells[row][col];
+        (cell.ch, cell.attr)
+    }
+
+    /// the characters of a row, with the trailing blanks. The cells a window clears to empty
+    /// (`\0`) are shown as blanks, as the terminal does.
+    pub fn row(&self, row: usize) -> String {
+        self.cells[row]
+            .iter()
+            .map(|cell| if cell.ch == '\0' { ' ' } else { cell.ch })
+            .collect()
+    }
+
+    /// the characters of all the rows, without the trailing blanks
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.cells.len())
+            .map(|row| self.row(row).trim_end().to_string())
+            .collect()
+    }
+}
+
+impl Canvas for TestCanvas {
+    fn size(&self) -> Result<(usize, usize)> {
+        Ok((self.width, self.cells.len()))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = Cell::default();
+            }
+        }
+        Ok(())
+    }
+
+    fn put_cell(&mut self, row: usize, col: usize, cell: Cell) -> Result<usize> {
+        if row < self.cells.len() && col < self.width {
+            self.cells[row][col] = cell;
+        }
+        Ok(cell.ch.width().unwrap_or(2))
+    }
+
+    fn set_cursor(&mut self, _row: usize, _col: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn show_cursor(&mut self, _show: bool) -> Result<()> {
+        Ok(())
+    }
+}
@@ -37,12 +37,21 @@ pub struct ColorTheme {
     query_bg:             Color,
     query_effect:         Effect,
     spinner:              Color,
+    spinner_effect:       Effect,
     info:                 Color,
+    info_effect:          Effect,
     prompt:               Color,
+    prompt_effect:        Effect,
     cursor:               Color,
+    cursor_effect:        Effect,
     selected:             Color,
+    selected_effect:      Effect,
     header:               Color,
+    header_effect:        Effect,
     border:               Color,
+    border_effect:        Effect,
+    error:                Color,
+    error_effect:         Effect,
 }
 
 #[rustfmt::skip]
@@ -76,12 +85,21 @@ impl ColorTheme {
             query_bg:             Color::Default,
             query_effect:         Effect::empty(),
             spinner:              Color::Default,
+            spinner_effect:       Effect::BOLD,
             info:                 Color::Default,
+            info_effect:          Effect::empty(),
             prompt:               Color::Default,
+            prompt_effect:        Effect::empty(),
             cursor:               Color::Default,
+            cursor_effect:        Effect::empty(),
             selected:             Color::Default,
+            selected_effect:      Effect::empty(),
             header:               Color::Default,
+            header_effect:        Effect::empty(),
             border:               Color::Default,
+            border_effect:        Effect::empty(),
+            error:                Color::Default,
+            error_effect:         Effect::BOLD,
         }
     }
 
@@ -109,6 +127,7 @@ impl ColorTheme {
             selected:         Color::MAGENTA,
             header:           Color::CYAN,
             border:           Color::LIGHT_BLACK,
+            error:            Color::RED,
             ..ColorTheme::empty()
         }
     }
@@ -129,6 +148,7 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(109),
             border:           Color::AnsiValue(59),
+            error:            Color::AnsiValue(160),
             ..ColorTheme::empty()
         }
     }
@@ -148,6 +168,7 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(109),
             border:           Color::AnsiValue(59),
+            error:            Color::AnsiValue(160),
             ..ColorTheme::empty()
         }
     }
@@ -167,6 +188,7 @@ impl ColorTheme {
             selected:         Color::AnsiValue(168),
             header:           Color::AnsiValue(31),
             border:           Color::AnsiValue(145),
+            error:            Color::AnsiValue(160),
             ..ColorTheme::empty()
         }
     }
@@ -187,37 +209,57 @@ impl ColorTheme {
                 continue;
             }
 
-            let new_color = if color[1].len() == 7 {
-                // 256 color
-                let r = u8::from_str_radix(&color[1][1..3], 16).unwrap_or(255);
-                let g = u8::from_str_radix(&color[1][3..5], 16).unwrap_or(255);
-                let b = u8::from_str_radix(&color[1][5..7], 16).unwrap_or(255);
-                Color::Rgb(r, g, b)
-            } else {
-                color[1].parse::<u8>()
-                    .map(Color::AnsiValue)
-                    .unwrap_or(Color::Default)
-            };
-
-            match color[0] {
-                "fg"                    => theme.fg               = new_color,
-                "bg"                    => theme.bg               = new_color,
-                "matched" | "hl"        => theme.matched          = new_color,
-                "matched_bg"            => theme.matched_bg       = new_color,
-                "current" | "fg+"       => theme.current          = new_color,
-                "current_bg" | "bg+"    => theme.current_bg       = new_color,
-                "current_match" | "hl+" => theme.current_match    = new_color,
-                "current_match_bg"      => theme.current_match_bg = new_color,
-                "query"                 => theme.query_fg         = new_color,
-                "query_bg"              => theme.query_bg         = new_color,
-                "spinner"               => theme.spinner          = new_color,
-                "info"                  => theme.info             = new_color,
-                "prompt"                => theme.prompt           = new_color,
-                "cursor" | "pointer"    => theme.cursor           = new_color,
-                "selected" | "marker"   => theme.selected         = new_color,
-                "header"                => theme.header           = new_color,
-                "border"                => theme.border           = new_color,
-                _ => {}
+            // COLOR:VALUE[:EFFECT...], the value may be left out to change the effects only
+            let mut new_color = None;
+            let mut new_effect = None;
+            for value in &color[1..] {
+                match parse_effect(value) {
+                    Some(effect) => new_effect = Some(new_effect.unwrap_or_else(Effect::empty) | effect),
+                    None => new_color = Some(parse_color(value)),
+                }
+            }
+
+            if let Some(new_color) = new_color {
+                match color[0] {
+                    "fg"                    => theme.fg               = new_color,
+                    "bg"                    => theme.bg               = new_color,
+                    "matched" | "hl"        => theme.matched          = new_color,
+                    "matched_bg"            => theme.matched_bg       = new_color,
+                    "current" | "fg+"       => theme.current          = new_color,
+                    "current_bg" | "bg+"    => theme.current_bg       = new_color,
+                    "current_match" | "hl+" => theme.current_match    = new_color,
+                    "current_match_bg"      => theme.current_match_bg = new_color,
+                    "query"                 => theme.query_fg         = new_color,
+                    "query_bg"              => theme.query_bg         = new_color,
+                    "spinner"               => theme.spinner          = new_color,
+                    "info"                  => theme.info             = new_color,
+                    "prompt"                => theme.prompt           = new_color,
+                    "cursor" | "pointer"    => theme.cursor           = new_color,
+                    "selected" | "marker"   => theme.selected         = new_color,
+                    "header"                => theme.header           = new_color,
+                    "border"                => theme.border           = new_color,
+                    "error"                 => theme.error            = new_color,
+                    _ => {}
+                }
+            }
+
+            if let Some(new_effect) = new_effect {
+                match color[0] {
+                    "fg"                    => theme.normal_effect        = new_effect,
+                    "matched" | "hl"        => theme.matched_effect       = new_effect,
+                    "current" | "fg+"       => theme.current_effect       = new_effect,
+                    "current_match" | "hl+" => theme.current_match_effect = new_effect,
+                    "query"                 => theme.query_effect         = new_effect,
+                    "spinner"               => theme.spinner_effect       = new_effect,
+                    "info"                  => theme.info_effect          = new_effect,
+                    "prompt"                => theme.prompt_effect        = new_effect,
+                    "cursor" | "pointer"    => theme.cursor_effect        = new_effect,
+                    "selected" | "marker"   => theme.selected_effect      = new_effect,
+                    "header"                => theme.header_effect        = new_effect,
+                    "border"                => theme.border_effect        = new_effect,
+                    "error"                 => theme.error_effect         = new_effect,
+                    _ => {}
+                }
             }
         }
         theme
@@ -267,7 +309,7 @@ impl ColorTheme {
         Attr {
             fg: self.spinner,
             bg: self.bg,
-            effect: Effect::BOLD,
+            effect: self.spinner_effect,
         }
     }
 
@@ -275,7 +317,15 @@ impl ColorTheme {
         Attr {
             fg: self.info,
             bg: self.bg,
-            effect: Effect::empty(),
+            effect: self.info_effect,
+        }
+    }
+
+    /// the numbers in the info line that stand out, e.g. the number of selected items
+    pub fn info_emphasis(&self) -> Attr {
+        Attr {
+            effect: self.info_effect | Effect::BOLD,
+            ..self.info()
         }
     }
 
@@ -283,7 +333,7 @@ impl ColorTheme {
         Attr {
             fg: self.prompt,
             bg: self.bg,
-            effect: Effect::empty(),
+            effect: self.prompt_effect,
         }
     }
 
@@ -291,7 +341,7 @@ impl ColorTheme {
         Attr {
             fg: self.cursor,
             bg: self.current_bg,
-            effect: Effect::empty(),
+            effect: self.cursor_effect,
         }
     }
 
@@ -299,7 +349,7 @@ impl ColorTheme {
         Attr {
             fg: self.selected,
             bg: self.current_bg,
-            effect: Effect::empty(),
+            effect: self.selected_effect,
         }
     }
 
@@ -307,7 +357,7 @@ impl ColorTheme {
         Attr {
             fg: self.header,
             bg: self.bg,
-            effect: Effect::empty(),
+            effect: self.header_effect,
         }
     }
 
@@ -315,7 +365,127 @@ impl ColorTheme {
         Attr {
             fg: self.border,
             bg: self.bg,
-            effect: Effect::empty(),
+            effect: self.border_effect,
+        }
+    }
+
+    pub fn error(&self) -> Attr {
+        Attr {
+            fg: self.error,
+            bg: self.bg,
+            effect: self.error_effect,
         }
     }
 }
+
+#[rustfmt::skip]
+fn parse_effect(value: &str) -> Option<Effect> {
+    match value {
+        "bold"      => Some(Effect::BOLD),
+        "underline" => Some(Effect::UNDERLINE),
+        "reverse"   => Some(Effect::REVERSE),
+        "blink"     => Some(Effect::BLINK),
+        "regular"   => Some(Effect::empty()),
+        _ => None,
+    }
+}
+
+fn parse_color(value: &str) -> Color {
+    if value.len() == 7 {
+        // 256 color
+        let r = u8::from_str_radix(&value[1..3], 16).unwrap_or(255);
+        let g = u8::from_str_radix(&value[3..5], 16).unwrap_or(255);
+        let b = u8::from_str_radix(&value[5..7], 16).unwrap_or(255);
+        Color::Rgb(r, g, b)
+    } else {
+        value.parse::<u8>().map(Color::AnsiValue).unwrap_or(Color::Default)
+    }
+}
+
+/// `--no-bold`: a canvas that drops the bold effect of everything drawn on it, including the
+/// ANSI colors of the items and of the preview output
+pub struct NoBoldCanvas<'a> {
+    canvas: &'a mut dyn Canvas,
+}
+
+impl<'a> NoBoldCanvas<'a> {
+    pub fn new(canvas: &'a mut dyn Canvas) -> Self {
+        Self { canvas }
+    }
+}
+
+fn without_bold(attr: Attr) -> Attr {
+    Attr {
+        effect: attr.effect - Effect::BOLD,
+        ..attr
+    }
+}
+
+impl<'a> Canvas for NoBoldCanvas<'a> {
+    fn size(&self) -> Result<(usize, usize)> {
+        self.canvas.size()
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.canvas.clear()
+    }
+
+    fn put_cell(&mut self, row: usize, col: usize, cell: Cell) -> Result<usize> {
+        let attr = without_bold(cell.attr);
+        self.canvas.put_cell(row, col, Cell { attr, ..cell })
+    }
+
+    fn print_with_attr(&mut self, row: usize, col: usize, content: &str, attr: Attr) -> Result<usize> {
+        self.canvas.print_with_attr(row, col, content, without_bold(attr))
+    }
+
+    fn set_cursor(&mut self, row: usize, col: usize) -> Result<()> {
+        self.canvas.set_cursor(row, col)
+    }
+
+    fn show_cursor(&mut self, show: bool) -> Result<()> {
+        self.canvas.show_cursor(show)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_canvas::TestCanvas;
+
+    #[test]
+    fn test_effects_from_options() {
+        let theme = ColorTheme::from_options("bw,hl:regular,fg+:1:bold:underline,info:reverse,spinner:2");
+        assert_eq!(theme.matched().effect, Effect::empty());
+        assert_eq!(theme.current().fg, Color::AnsiValue(1));
+        assert_eq!(theme.current().effect, Effect::BOLD | Effect::UNDERLINE);
+        assert_eq!(theme.info().fg, Color::Default);
+        assert_eq!(theme.info().effect, Effect::REVERSE);
+        assert_eq!(theme.info_emphasis().effect, Effect::REVERSE | Effect::BOLD);
+        // a color alone keeps the effects
+        assert_eq!(theme.spinner().fg, Color::AnsiValue(2));
+        assert_eq!(theme.spinner().effect, Effect::BOLD);
+    }
+
+    #[test]
+    fn test_no_bold_canvas() {
+        let mut canvas = TestCanvas::new(3, 1);
+        let bold = Attr {
+            fg: Color::RED,
+            effect: Effect::BOLD | Effect::UNDERLINE,
+            ..Attr::default()
+        };
+        {
+            let mut no_bold = NoBoldCanvas::new(&mut canvas);
+            no_bold.put_char_with_attr(0, 0, 'a', bold).unwrap();
+            no_bold.print_with_attr(0, 1, "bc", bold).unwrap();
+        }
+
+        let expected = Attr {
+            effect: Effect::UNDERLINE,
+            ..bold
+        };
+        assert_eq!(canvas.row(0), "abc");
+        assert!((0..3).all(|col| canvas.cell(0, col).1 == expected));
+    }
+}
@@ -1,22 +1,91 @@
 use crate::field::get_string_by_range;
+use nix::libc;
 use regex::{Captures, Regex};
+use std::any::Any;
 use std::borrow::Cow;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::env;
+use std::fs::OpenOptions;
+use std::mem;
+use std::os::unix::io::AsRawFd;
 use std::prelude::v1::*;
 use tuikit::prelude::*;
-use unicode_width::UnicodeWidthChar;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 lazy_static! {
-    static ref RE_FIELDS: Regex = Regex::new(r"\\?(\{ *-?[0-9.,cq+]*? *})").unwrap();
+    static ref RE_FIELDS: Regex = Regex::new(r"\\?(\{ *\+?r?-?[0-9.,cq]*? *})").unwrap();
+    static ref RE_SCROLL: Regex = Regex::new(r"^\+?(\{[^}]*\}|[0-9]+)((?:[+-][0-9]+)*)(?:/([0-9]+))?$").unwrap();
+    static ref RE_SCROLL_SHIFT: Regex = Regex::new(r"[+-][0-9]+").unwrap();
 }
 
 pub fn escape_single_quote(text: &str) -> String {
     text.replace("'", "'\\''")
 }
 
+/// extract the message from the payload of a caught panic
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// the command to read the items from if neither `--cmd` nor the input is given
+pub fn default_command() -> String {
+    match env::var("SKIM_DEFAULT_COMMAND").as_ref().map(String::as_ref) {
+        Ok("") | Err(_) => "find .".to_owned(),
+        Ok(val) => val.to_owned(),
+    }
+}
+
+/// (width, height) given by `$COLUMNS` and `$LINES`, e.g. by a popup that is smaller than the
+/// terminal it runs in. Values that are not positive numbers are ignored.
+pub fn env_terminal_size() -> (Option<usize>, Option<usize>) {
+    parse_terminal_size(env::var("COLUMNS").ok().as_deref(), env::var("LINES").ok().as_deref())
+}
+
+fn parse_terminal_size(columns: Option<&str>, lines: Option<&str>) -> (Option<usize>, Option<usize>) {
+    let parse = |size: Option<&str>| size?.trim().parse::<usize>().ok().filter(|&size| size > 0);
+    (parse(columns), parse(lines))
+}
+
+/// (width, height) of the controlling terminal, `$COLUMNS` and `$LINES` take precedence
+pub fn terminal_size() -> Option<(usize, usize)> {
+    env_or_tty_size(env_terminal_size(), tty_size)
+}
+
+// the tty is only asked for the size the env doesn't give
+fn env_or_tty_size(
+    env_size: (Option<usize>, Option<usize>),
+    tty_size: impl FnOnce() -> Option<(usize, usize)>,
+) -> Option<(usize, usize)> {
+    match env_size {
+        (Some(width), Some(height)) => Some((width, height)),
+        (width, height) => {
+            let (tty_width, tty_height) = tty_size()?;
+            Some((width.unwrap_or(tty_width), height.unwrap_or(tty_height)))
+        }
+    }
+}
+
+/// (width, height) of the controlling terminal as the tty reports it
+pub fn tty_size() -> Option<(usize, usize)> {
+    let tty = OpenOptions::new().read(true).open("/dev/tty").ok()?;
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_row > 0 {
+        Some((size.ws_col as usize, size.ws_row as usize))
+    } else {
+        None
+    }
+}
+
 /// use to print a single line, properly handle the tabsteop and shift of a string
 /// e.g. a long line will be printed as `..some content` or `some content..` or `..some content..`
-/// depends on the container's width and the size of the content.
+/// depends on the container's width and the size of the content. The hint (`..` by default) is
+/// set by `ellipsis`.
 ///
 /// ```text
 /// let's say we have a very long line with lots of useless information
@@ -25,12 +94,10 @@ pub fn escape_single_quote(text: &str) -> String {
 ///             |<-    shift    -> |
 /// |< hscroll >|
 /// ```
-
 pub struct LinePrinter {
     start: usize,
     end: usize,
     current_pos: i32,
-    screen_col: usize,
 
     // start position
     row: usize,
@@ -41,6 +108,7 @@ pub struct LinePrinter {
     text_width: usize,
     container_width: usize,
     hscroll_offset: usize,
+    ellipsis: String,
 }
 
 impl LinePrinter {
@@ -49,7 +117,6 @@ impl LinePrinter {
             start: 0,
             end: 0,
             current_pos: -1,
-            screen_col: 0,
 
             row: 0,
             col: 0,
@@ -59,6 +126,7 @@ impl LinePrinter {
             text_width: 0,
             container_width: 0,
             hscroll_offset: 0,
+            ellipsis: "..".to_string(),
         }
     }
 
@@ -97,6 +165,12 @@ impl LinePrinter {
         self
     }
 
+    /// the hint printed where the content is cut off, could be empty
+    pub fn ellipsis(mut self, ellipsis: &str) -> Self {
+        self.ellipsis = ellipsis.to_string();
+        self
+    }
+
     pub fn build(mut self) -> Self {
         self.reset();
         self
@@ -104,20 +178,28 @@ impl LinePrinter {
 
     pub fn reset(&mut self) {
         self.current_pos = 0;
-        self.screen_col = self.col;
 
         self.start = self.shift + self.hscroll_offset;
         self.end = self.start + self.container_width;
     }
 
-    fn print_ch_to_canvas(&mut self, canvas: &mut dyn Canvas, ch: char, attr: Attr, skip: bool) {
-        let w = ch.width().unwrap_or(2);
-
+    fn print_ch_to_canvas(&self, canvas: &mut dyn Canvas, pos: usize, ch: char, attr: Attr, skip: bool) {
         if !skip {
-            let _ = canvas.put_cell(self.row, self.screen_col, Cell::default().ch(ch).attribute(attr));
+            let col = self.col + pos - self.start;
+            let _ = canvas.put_cell(self.row, col, Cell::default().ch(ch).attribute(attr));
         }
+    }
 
-        self.screen_col += w;
+    // the character of the ellipsis that starts at the `offset`th column of it
+    fn ellipsis_char_at(&self, offset: usize) -> Option<char> {
+        let mut width = 0;
+        for ch in self.ellipsis.chars() {
+            if width == offset {
+                return Some(ch);
+            }
+            width += ch.width().unwrap_or(2);
+        }
+        None
     }
 
     fn print_char_raw(&mut self, canvas: &mut dyn Canvas, ch: char, attr: Attr, skip: bool) {
@@ -128,24 +210,41 @@ impl LinePrinter {
 
         assert!(self.current_pos >= 0);
         let current = self.current_pos as usize;
+        self.current_pos += w as i32;
 
-        if current < self.start || current >= self.end {
-            // pass if it is hidden
-        } else if current < self.start + 2 && (self.shift > 0 || self.hscroll_offset > 0) {
-            // print left ".."
-            for _ in 0..min(w, current - self.start + 1) {
-                self.print_ch_to_canvas(canvas, '.', attr, skip);
-            }
-        } else if self.end - current <= 2 && (self.text_width > self.end) {
-            // print right ".."
-            for _ in 0..min(w, self.end - current) {
-                self.print_ch_to_canvas(canvas, '.', attr, skip);
-            }
+        // the columns taken by the left and right hint
+        let ellipsis_width = self.ellipsis.width();
+        let left_end = if self.shift > 0 || self.hscroll_offset > 0 {
+            min(self.start + ellipsis_width, self.end)
+        } else {
+            self.start
+        };
+        let right_start = if self.text_width > self.end {
+            max(self.end.saturating_sub(ellipsis_width), left_end)
         } else {
-            self.print_ch_to_canvas(canvas, ch, attr, skip);
+            self.end
+        };
+
+        if current >= left_end && current + w <= right_start {
+            self.print_ch_to_canvas(canvas, current, ch, attr, skip);
+            return;
         }
 
-        self.current_pos += w as i32;
+        // the character is (partly) hidden or covered by the hint
+        for pos in max(current, self.start)..min(current + w, self.end) {
+            if pos < left_end {
+                if let Some(hint) = self.ellipsis_char_at(pos - self.start) {
+                    self.print_ch_to_canvas(canvas, pos, hint, attr, skip);
+                }
+            } else if pos >= right_start {
+                if let Some(hint) = self.ellipsis_char_at(pos - right_start) {
+                    self.print_ch_to_canvas(canvas, pos, hint, attr, skip);
+                }
+            } else {
+                // the visible half of a wide character
+                self.print_ch_to_canvas(canvas, pos, ' ', attr, skip);
+            }
+        }
     }
 
     pub fn print_char(&mut self, canvas: &mut dyn Canvas, ch: char, attr: Attr, skip: bool) {
@@ -180,6 +279,36 @@ pub fn accumulate_text_width(text: &str, tabstop: usize) -> Vec<usize> {
     ret
 }
 
+/// wrap the text in rows of `width` columns, the continuation rows are `indent` columns narrower.
+/// return (row, col, width) of every char, col counts from the start of its row.
+/// A wide char isn't split between rows, a tab only fills the rest of its row.
+pub fn wrap_text(text: &str, width: usize, indent: usize, tabstop: usize) -> Vec<(usize, usize, usize)> {
+    let char_width = |ch: char, col: usize| {
+        if ch == '\t' {
+            tabstop - (col % tabstop)
+        } else {
+            ch.width().unwrap_or(2)
+        }
+    };
+
+    let mut ret = Vec::new();
+    let (mut row, mut col) = (0, 0);
+    let mut row_width = max(width, 1);
+    for ch in text.chars() {
+        let mut w = char_width(ch, col);
+        if ch == '\t' {
+            w = min(w, row_width.saturating_sub(col));
+        } else if col > 0 && col + w > row_width {
+            row += 1;
+            col = 0;
+            row_width = max(width.saturating_sub(indent), 1);
+        }
+        ret.push((row, col, w));
+        col += w;
+    }
+    ret
+}
+
 /// "smartly" calculate the "start" position of the string in order to show the matched contents
 /// for example, if the match appear in the end of a long string, we need to show the right part.
 /// ```text
@@ -194,6 +323,7 @@ pub fn reshape_string(
     match_start: usize,
     match_end: usize,
     tabstop: usize,
+    ellipsis_width: usize,
 ) -> (usize, usize) {
     if text.is_empty() {
         return (0, 0);
@@ -218,7 +348,7 @@ pub fn reshape_string(
     };
     let w3 = acc_width[acc_width.len() - 1] - w1 - w2;
 
-    if (w1 > w3 && w2 + w3 <= container_width) || (w3 <= 2) {
+    if (w1 > w3 && w2 + w3 <= container_width) || (w3 <= ellipsis_width) {
         // right-fixed
         //(right_fixed(&acc_width, container_width), full_width)
         (full_width - container_width, full_width)
@@ -227,8 +357,45 @@ pub fn reshape_string(
         (0, full_width)
     } else {
         // left-right
-        (acc_width[match_end] - container_width + 2, full_width)
+        (acc_width[match_end] + ellipsis_width - container_width, full_width)
+    }
+}
+
+/// `--skip-to-pattern`: the left shift that starts the text at the first match of the pattern,
+/// right after the ellipsis, without scrolling past the end of the text.
+/// None if the pattern doesn't match.
+///
+/// return (left_shift, full_print_width)
+pub fn skip_to_pattern(
+    text: &str,
+    pattern: &Regex,
+    container_width: usize,
+    tabstop: usize,
+    ellipsis_width: usize,
+) -> Option<(usize, usize)> {
+    let match_start = pattern.find(text)?.start();
+
+    let acc_width = accumulate_text_width(text, tabstop);
+    let full_width = acc_width.last().cloned().unwrap_or(0);
+    if full_width <= container_width {
+        return Some((0, full_width));
     }
+
+    let match_start_char = text[..match_start].chars().count();
+    let width_before = if match_start_char == 0 {
+        0
+    } else {
+        acc_width[match_start_char - 1]
+    };
+    let shift = if width_before == 0 {
+        0
+    } else {
+        min(
+            width_before.saturating_sub(ellipsis_width),
+            full_width - container_width,
+        )
+    };
+    Some((shift, full_width))
 }
 
 /// margin option string -> Size
@@ -242,38 +409,225 @@ pub fn margin_string_to_size(margin: &str) -> Size {
     }
 }
 
+/// strict version of `margin_string_to_size`, return error on invalid input
+/// 10 -> Ok(Size::Fixed(10))
+/// 10% -> Ok(Size::Percent(10))
+/// 1x -> Err
+fn parse_margin_size(margin: &str) -> std::result::Result<Size, String> {
+    let margin = margin.trim();
+    if margin.ends_with('%') {
+        let percent = margin[0..margin.len() - 1]
+            .parse::<usize>()
+            .map_err(|_| format!("invalid margin size: {:?}", margin))?;
+        if percent > 100 {
+            return Err(format!("margin percentage exceeds 100%: {:?}", margin));
+        }
+        Ok(Size::Percent(percent))
+    } else {
+        margin
+            .parse::<usize>()
+            .map(Size::Fixed)
+            .map_err(|_| format!("invalid margin size: {:?}", margin))
+    }
+}
+
 /// Parse margin configuration, e.g.
+/// - `0`        Shorthand for no margin at all
 /// - `TRBL`     Same  margin  for  top,  right, bottom, and left
 /// - `TB,RL`    Vertical, horizontal margin
 /// - `T,RL,B`   Top, horizontal, bottom margin
 /// - `T,R,B,L`  Top, right, bottom, left margin
-pub fn parse_margin(margin_option: &str) -> (Size, Size, Size, Size) {
+/// - `t:1,l:10%` Named sides(`t`/`top`, `r`/`right`, `b`/`bottom`, `l`/`left`), the sides that
+///   are not mentioned have no margin. Named and positional values could not be mixed.
+pub fn parse_margin(margin_option: &str) -> std::result::Result<(Size, Size, Size, Size), String> {
+    let margin_option = margin_option.trim();
+    if margin_option.is_empty() || margin_option == "0" {
+        return Ok((Size::Fixed(0), Size::Fixed(0), Size::Fixed(0), Size::Fixed(0)));
+    }
+
     let margins = margin_option.split(',').collect::<Vec<&str>>();
 
+    if margins.iter().any(|margin| margin.contains(':')) {
+        return parse_named_margin(&margins);
+    }
+
     match margins.len() {
         1 => {
-            let margin = margin_string_to_size(margins[0]);
-            (margin, margin, margin, margin)
+            let margin = parse_margin_size(margins[0])?;
+            Ok((margin, margin, margin, margin))
         }
         2 => {
-            let margin_tb = margin_string_to_size(margins[0]);
-            let margin_rl = margin_string_to_size(margins[1]);
-            (margin_tb, margin_rl, margin_tb, margin_rl)
+            let margin_tb = parse_margin_size(margins[0])?;
+            let margin_rl = parse_margin_size(margins[1])?;
+            Ok((margin_tb, margin_rl, margin_tb, margin_rl))
         }
         3 => {
-            let margin_top = margin_string_to_size(margins[0]);
-            let margin_rl = margin_string_to_size(margins[1]);
-            let margin_bottom = margin_string_to_size(margins[2]);
-            (margin_top, margin_rl, margin_bottom, margin_rl)
+            let margin_top = parse_margin_size(margins[0])?;
+            let margin_rl = parse_margin_size(margins[1])?;
+            let margin_bottom = parse_margin_size(margins[2])?;
+            Ok((margin_top, margin_rl, margin_bottom, margin_rl))
         }
         4 => {
-            let margin_top = margin_string_to_size(margins[0]);
-            let margin_right = margin_string_to_size(margins[1]);
-            let margin_bottom = margin_string_to_size(margins[2]);
-            let margin_left = margin_string_to_size(margins[3]);
-            (margin_top, margin_right, margin_bottom, margin_left)
+            let margin_top = parse_margin_size(margins[0])?;
+            let margin_right = parse_margin_size(margins[1])?;
+            let margin_bottom = parse_margin_size(margins[2])?;
+            let margin_left = parse_margin_size(margins[3])?;
+            Ok((margin_top, margin_right, margin_bottom, margin_left))
         }
-        _ => (Size::Fixed(0), Size::Fixed(0), Size::Fixed(0), Size::Fixed(0)),
+        n => Err(format!(
+            "margin accepts at most 4 values, got {}: {:?}",
+            n, margin_option
+        )),
+    }
+}
+
+// ["t:1", "l:10%"] -> (Fixed(1), Fixed(0), Fixed(0), Percent(10))
+fn parse_named_margin(margins: &[&str]) -> std::result::Result<(Size, Size, Size, Size), String> {
+    let mut ret = [Size::Fixed(0); 4];
+    let mut seen = [false; 4];
+
+    for margin in margins {
+        let mut side_size = margin.splitn(2, ':');
+        let side = side_size.next().unwrap_or("").trim();
+        let size = side_size
+            .next()
+            .ok_or_else(|| format!("could not mix named and positional margins: {:?}", margin))?;
+
+        let idx = match side.to_lowercase().as_str() {
+            "t" | "top" => 0,
+            "r" | "right" => 1,
+            "b" | "bottom" => 2,
+            "l" | "left" => 3,
+            _ => return Err(format!("unknown margin side: {:?}", side)),
+        };
+
+        if seen[idx] {
+            return Err(format!("margin side specified more than once: {:?}", side));
+        }
+        seen[idx] = true;
+        ret[idx] = parse_margin_size(size)?;
+    }
+
+    Ok((ret[0], ret[1], ret[2], ret[3]))
+}
+
+/// Shrink the margins of the two opposite sides(e.g. top & bottom) so that there is at least one
+/// row/column left for the content. `total` is the size of the screen in that direction.
+///
+/// return (start, end) in absolute numbers
+pub fn clamp_margin(total: usize, start: Size, end: Size) -> (usize, usize) {
+    let (start, end) = margin_to_fixed(total, start, end);
+    let available = total.saturating_sub(1);
+    if start + end <= available {
+        return (start, end);
+    }
+
+    let new_start = start * available / (start + end);
+    (new_start, available - new_start)
+}
+
+/// whether the margins of the two opposite sides leave room for the content without shrinking
+pub fn margin_fits(total: usize, start: Size, end: Size) -> bool {
+    let (start, end) = margin_to_fixed(total, start, end);
+    start + end <= total.saturating_sub(1)
+}
+
+fn margin_to_fixed(total: usize, start: Size, end: Size) -> (usize, usize) {
+    let to_fixed = |size: Size| match size {
+        Size::Fixed(num) => num,
+        Size::Percent(percent) => total * percent / 100,
+        Size::Default => 0,
+    };
+    (to_fixed(start), to_fixed(end))
+}
+
+/// Split `total` rows/columns between the main window and the preview window of the given size.
+/// The preview shrinks so that at least `min_main` is left for the main window (if possible).
+///
+/// return (main, preview) in absolute numbers
+pub fn split_preview(total: usize, preview: Size, min_main: usize) -> (usize, usize) {
+    let preview = match preview {
+        Size::Fixed(num) => num,
+        Size::Percent(percent) => total * percent / 100,
+        Size::Default => total / 2,
+    };
+
+    let preview = min(preview, total.saturating_sub(min_main));
+    (total - preview, preview)
+}
+
+/// The vscroll offset of the preview for the scroll expression of `--preview-window`, e.g.
+/// `+{2}-5` shows the line of the 2nd field of `text` with 5 lines above it.
+///
+/// * `+N` or `+{N}` for the line (1-based) to show at the top
+/// * `-M` / `+M` to move it down or up by M lines
+/// * `/D` to show the line at 1/D of the `height` instead, e.g. `/2` for the middle
+///
+/// A field that isn't a number scrolls to the top.
+pub fn eval_scroll_offset(expr: &str, delimiter: &Regex, text: &str, height: usize) -> usize {
+    let caps = match RE_SCROLL.captures(expr.trim()) {
+        Some(caps) => caps,
+        None => return 0,
+    };
+
+    let base = &caps[1];
+    let line = match base.strip_prefix('{').and_then(|range| range.strip_suffix('}')) {
+        Some(range) => get_string_by_range(delimiter, text, range.trim()).map(|field| {
+            // the field may keep the delimiter, e.g. `12:` for `file:12:col`
+            field.trim_matches(|ch: char| !ch.is_ascii_digit()).parse::<i64>()
+        }),
+        None => Some(base.parse::<i64>()),
+    };
+    let line = match line {
+        Some(Ok(line)) => line,
+        _ => return 0,
+    };
+
+    let shift: i64 = RE_SCROLL_SHIFT
+        .find_iter(&caps[2])
+        .filter_map(|term| term.as_str().parse::<i64>().ok())
+        .sum();
+    let fraction = caps
+        .get(3)
+        .and_then(|denom| denom.as_str().parse::<usize>().ok())
+        .filter(|&denom| denom > 0)
+        .map(|denom| height / denom)
+        .unwrap_or(0);
+
+    max(line + shift - 1 - fraction as i64, 0) as usize
+}
+
+/// parse `--min-query-length`: `N[:empty|:all]` -> (N, whether the shorter queries show all items)
+pub fn parse_min_query_length(option: &str) -> std::result::Result<(usize, bool), String> {
+    let mut parts = option.splitn(2, ':');
+    let length = parts.next().unwrap_or("");
+    let length = length
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("invalid min query length: {:?}, expect N[:empty|:all]", option))?;
+    match parts.next().map(str::trim) {
+        None | Some("empty") => Ok((length, false)),
+        Some("all") => Ok((length, true)),
+        Some(_) => Err(format!("invalid min query length: {:?}, expect N[:empty|:all]", option)),
+    }
+}
+
+/// parse `--max-items`: `N[:stop|:drop]` -> (N, whether the oldest items are dropped instead of
+/// stopping to read)
+pub fn parse_max_items(option: &str) -> std::result::Result<(usize, bool), String> {
+    let mut parts = option.splitn(2, ':');
+    let max_items = parts
+        .next()
+        .unwrap_or("")
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|&max_items| max_items > 0)
+        .ok_or_else(|| format!("invalid max items: {:?}, expect N[:stop|:drop]", option))?;
+    match parts.next().map(str::trim) {
+        None | Some("stop") => Ok((max_items, false)),
+        Some("drop") => Ok((max_items, true)),
+        Some(_) => Err(format!("invalid max items: {:?}, expect N[:stop|:drop]", option)),
     }
 }
 
@@ -309,7 +663,24 @@ pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, s
         let range = &range[1..range.len() - 1];
         let range = range.trim();
 
-        if range == "+" {
+        // `+` expands to all the selections, `r` leaves the replacement unquoted
+        let (all, range) = match range.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, range),
+        };
+        let (raw, range) = match range.strip_prefix('r') {
+            Some(rest) => (true, rest),
+            None => (false, range),
+        };
+        let quote = |text: &str| {
+            if raw {
+                text.to_string()
+            } else {
+                format!("'{}'", escape_single_quote(text))
+            }
+        };
+
+        if all {
             let current_selection = vec![context.current_selection];
             let selections = if context.selections.is_empty() {
                 &current_selection
@@ -317,28 +688,41 @@ pub fn inject_command<'a>(cmd: &'a str, context: InjectContext<'a>) -> Cow<'a, s
                 context.selections
             };
 
-            return selections
-                .iter()
-                .map(|&s| format!("'{}'", escape_single_quote(s)))
-                .collect::<Vec<_>>()
-                .join(" ");
+            return selections.iter().map(|&s| quote(s)).collect::<Vec<_>>().join(" ");
         }
 
         let replacement = match range {
             "" => context.current_selection,
-            "+" => unreachable!(),
             "q" => context.query,
             "cq" => context.cmd_query,
             _ => get_string_by_range(context.delimiter, context.current_selection, range).unwrap_or(""),
         };
 
-        format!("'{}'", escape_single_quote(replacement))
+        quote(replacement)
     })
 }
 
+/// The environment variables for the commands run by skim (preview, execute), to spare scripts
+/// the quoting of placeholders.
+///
+/// * `SKIM_QUERY` for the query, like `{q}`
+/// * `SKIM_SELECTED` for the current selection, like `{}`
+/// * `SKIM_PREVIEW_LINES` for the height of the preview window, if there is one
+pub fn command_env(context: InjectContext, preview_lines: Option<usize>) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("SKIM_QUERY", context.query.to_string()),
+        ("SKIM_SELECTED", context.current_selection.to_string()),
+    ];
+    if let Some(lines) = preview_lines {
+        env.push(("SKIM_PREVIEW_LINES", lines.to_string()));
+    }
+    env
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_canvas::TestCanvas;
 
     #[test]
     fn test_accumulate_text_width() {
@@ -348,14 +732,131 @@ mod tests {
         assert_eq!(accumulate_text_width("ab中\te国g", 8), vec![1, 2, 4, 8, 9, 11, 12]);
     }
 
+    #[test]
+    fn test_wrap_text() {
+        let rows = |text: &str, width: usize| -> Vec<usize> {
+            wrap_text(text, width, 2, 8).iter().map(|&(row, _, _)| row).collect()
+        };
+        assert_eq!(rows("abcdefg", 3), vec![0, 0, 0, 1, 2, 3, 4]);
+        assert_eq!(rows("abcdefg", 10), vec![0; 7]);
+        // a wide char goes to the next row as a whole
+        assert_eq!(rows("ab中de国g", 5), vec![0, 0, 0, 0, 1, 1, 2]);
+        assert_eq!(wrap_text("ab中", 3, 0, 8), vec![(0, 0, 1), (0, 1, 1), (1, 0, 2)]);
+        // a tab is cut at the end of the row
+        assert_eq!(
+            wrap_text("ab\tc", 6, 2, 8),
+            vec![(0, 0, 1), (0, 1, 1), (0, 2, 4), (1, 0, 1)]
+        );
+        assert_eq!(wrap_text("", 6, 2, 8), vec![]);
+    }
+
     #[test]
     fn test_reshape_string() {
         // no match, left fixed to 0
-        assert_eq!(reshape_string("abc", 10, 0, 0, 8), (0, 3));
-        assert_eq!(reshape_string("a\tbc", 8, 0, 0, 8), (0, 10));
-        assert_eq!(reshape_string("a\tb\tc", 10, 0, 0, 8), (0, 17));
-        assert_eq!(reshape_string("a\t中b\tc", 8, 0, 0, 8), (0, 17));
-        assert_eq!(reshape_string("a\t中b\tc012345", 8, 0, 0, 8), (0, 23));
+        assert_eq!(reshape_string("abc", 10, 0, 0, 8, 2), (0, 3));
+        assert_eq!(reshape_string("a\tbc", 8, 0, 0, 8, 2), (0, 10));
+        assert_eq!(reshape_string("a\tb\tc", 10, 0, 0, 8, 2), (0, 17));
+        assert_eq!(reshape_string("a\t中b\tc", 8, 0, 0, 8, 2), (0, 17));
+        assert_eq!(reshape_string("a\t中b\tc012345", 8, 0, 0, 8, 2), (0, 23));
+    }
+
+    fn print_line(text: &str, width: usize, shift: usize, hscroll_offset: usize, ellipsis: &str) -> String {
+        let mut canvas = TestCanvas::new(width, 1);
+        let mut printer = LinePrinter::builder()
+            .container_width(width)
+            .shift(shift)
+            .text_width(accumulate_text_width(text, 8).last().cloned().unwrap_or(0))
+            .hscroll_offset(hscroll_offset)
+            .ellipsis(ellipsis)
+            .build();
+        for ch in text.chars() {
+            printer.print_char(&mut canvas, ch, Attr::default(), false);
+        }
+        canvas.row(0)
+    }
+
+    #[test]
+    fn test_skip_to_pattern() {
+        let pattern = Regex::new("ERROR|WARN").unwrap();
+        let skip = |text: &str, width: usize| skip_to_pattern(text, &pattern, width, 8, 2);
+
+        assert_eq!(skip("2020-01-01 00:00:00 INFO ok", 10), None);
+        // short enough, nothing to skip
+        assert_eq!(skip("12 ERROR", 10), Some((0, 8)));
+        // the match starts right after the ellipsis
+        let text = "2020-01-01 00:00:00 ERROR disk full, retrying";
+        let (shift, _) = skip(text, 14).unwrap();
+        assert_eq!(print_line(text, 14, shift, 0, ".."), "..ERROR disk..");
+        // but not past the end of the text
+        let text = "2020-01-01 00:00:00 WARN x";
+        let (shift, _) = skip(text, 10).unwrap();
+        assert_eq!(print_line(text, 10, shift, 0, ".."), "..0 WARN x");
+    }
+
+    #[test]
+    fn test_line_printer_ellipsis() {
+        assert_eq!(print_line("abcdefghij", 10, 0, 0, ".."), "abcdefghij");
+        assert_eq!(print_line("abcdefghij", 6, 0, 0, ".."), "abcd..");
+        assert_eq!(print_line("abcdefghij", 6, 4, 0, ".."), "..ghij");
+        assert_eq!(print_line("abcdefghij", 6, 0, 2, ".."), "..ef..");
+        assert_eq!(print_line("abcdefghij", 6, 0, 2, "…"), "…defg…");
+        assert_eq!(print_line("abcdefghij", 6, 0, 2, ""), "cdefgh");
+
+        // a wide character cut by the ellipsis leaves a blank
+        assert_eq!(print_line("a中文字b", 5, 0, 0, "…"), "a中  …");
+        assert_eq!(print_line("a中文字b", 5, 1, 0, "…"), "… 文 …");
+    }
+
+    #[test]
+    fn test_parse_margin() {
+        // tuikit's `Size` is not comparable, compare the debug output instead
+        let margin = |option| format!("{:?}", parse_margin(option));
+
+        assert_eq!(margin("0"), "Ok((Fixed(0), Fixed(0), Fixed(0), Fixed(0)))");
+        assert_eq!(margin(""), "Ok((Fixed(0), Fixed(0), Fixed(0), Fixed(0)))");
+        assert_eq!(
+            margin("10%"),
+            "Ok((Percent(10), Percent(10), Percent(10), Percent(10)))"
+        );
+        assert_eq!(margin("1,10%"), "Ok((Fixed(1), Percent(10), Fixed(1), Percent(10)))");
+        assert_eq!(margin("1,2,3"), "Ok((Fixed(1), Fixed(2), Fixed(3), Fixed(2)))");
+        assert_eq!(margin("1,2,3,4"), "Ok((Fixed(1), Fixed(2), Fixed(3), Fixed(4)))");
+        assert!(parse_margin("1,2,3,4,5").is_err());
+        assert!(parse_margin("1,a").is_err());
+        assert!(parse_margin("120%").is_err());
+
+        assert_eq!(margin("t:1,l:10%"), "Ok((Fixed(1), Fixed(0), Fixed(0), Percent(10)))");
+        assert_eq!(
+            margin("top:1,right:2,bottom:3,left:4"),
+            "Ok((Fixed(1), Fixed(2), Fixed(3), Fixed(4)))"
+        );
+        assert!(parse_margin("t:1,t:2").is_err());
+        assert!(parse_margin("t:1,2").is_err());
+        assert!(parse_margin("x:1").is_err());
+    }
+
+    #[test]
+    fn test_clamp_margin() {
+        assert_eq!(clamp_margin(10, Size::Fixed(2), Size::Fixed(3)), (2, 3));
+        assert_eq!(clamp_margin(10, Size::Percent(20), Size::Percent(30)), (2, 3));
+        assert_eq!(clamp_margin(10, Size::Percent(60), Size::Percent(60)), (4, 5));
+        assert_eq!(clamp_margin(10, Size::Fixed(20), Size::Fixed(0)), (9, 0));
+        assert_eq!(clamp_margin(0, Size::Fixed(1), Size::Fixed(1)), (0, 0));
+
+        assert!(margin_fits(10, Size::Fixed(4), Size::Percent(50)));
+        assert!(!margin_fits(10, Size::Percent(60), Size::Percent(60)));
+        assert!(!margin_fits(10, Size::Fixed(20), Size::Fixed(0)));
+    }
+
+    #[test]
+    fn test_split_preview() {
+        assert_eq!(split_preview(200, Size::Fixed(80), 20), (120, 80));
+        assert_eq!(split_preview(80, Size::Fixed(80), 20), (20, 60));
+        assert_eq!(split_preview(80, Size::Fixed(30), 20), (50, 30));
+        assert_eq!(split_preview(200, Size::Percent(50), 20), (100, 100));
+        assert_eq!(split_preview(80, Size::Percent(50), 20), (40, 40));
+        assert_eq!(split_preview(80, Size::Percent(100), 20), (20, 60));
+        assert_eq!(split_preview(10, Size::Fixed(80), 20), (10, 0));
     }
 
     #[test]
@@ -391,5 +892,113 @@ mod tests {
         assert_eq!("'query'", inject_command("{q}", default_context));
         assert_eq!("'cmd_query'", inject_command("{cq}", default_context));
         assert_eq!("'a,b,c' 'x,y,z'", inject_command("{+}", default_context));
+
+        assert_eq!("a,b,c", inject_command("{r}", default_context));
+        assert_eq!("b", inject_command("{r2}", default_context));
+        assert_eq!("query", inject_command("{rq}", default_context));
+        assert_eq!("a,b,c x,y,z", inject_command("{+r}", default_context));
+        assert_eq!("\\{r}", inject_command("\\{r}", default_context));
+    }
+
+    #[test]
+    fn test_inject_command_quotes_special_chars() {
+        let delimiter = Regex::new(r",").unwrap();
+        let context = |current_selection| InjectContext {
+            delimiter: &delimiter,
+            current_selection,
+            selections: &[],
+            query: "it's",
+            cmd_query: "",
+        };
+
+        assert_eq!("vim 'foo bar'", inject_command("vim {}", context("foo bar")));
+        assert_eq!(
+            "vim 'foo; rm -rf .'",
+            inject_command("vim {}", context("foo; rm -rf ."))
+        );
+        assert_eq!(r"vim 'it'\''s'", inject_command("vim {}", context("it's")));
+        assert_eq!(r"echo 'it'\''s'", inject_command("echo {q}", context("")));
+        assert_eq!(r#"vim '\"a\" $b'"#, inject_command("vim {+}", context(r#"\"a\" $b"#)));
+        assert_eq!("vim foo; rm -rf .", inject_command("vim {r}", context("foo; rm -rf .")));
+    }
+
+    #[test]
+    fn test_terminal_size_from_env() {
+        assert_eq!(parse_terminal_size(Some("80"), Some(" 20 ")), (Some(80), Some(20)));
+        // invalid values are ignored
+        assert_eq!(parse_terminal_size(Some("0"), Some("abc")), (None, None));
+        assert_eq!(parse_terminal_size(None, None), (None, None));
+
+        // the env overrides the size of the tty, e.g. of a popup in a larger terminal
+        let tty = || Some((100, 30));
+        assert_eq!(env_or_tty_size((Some(80), Some(20)), tty), Some((80, 20)));
+        assert_eq!(env_or_tty_size((Some(80), None), tty), Some((80, 30)));
+        assert_eq!(env_or_tty_size((None, Some(20)), tty), Some((100, 20)));
+        assert_eq!(env_or_tty_size((None, None), tty), Some((100, 30)));
+        // without a tty
+        assert_eq!(env_or_tty_size((Some(80), Some(20)), || None), Some((80, 20)));
+        assert_eq!(env_or_tty_size((Some(80), None), || None), None);
+    }
+
+    #[test]
+    fn test_eval_scroll_offset() {
+        let delimiter = Regex::new(r":").unwrap();
+        let text = "src/main.rs:42:7";
+        assert_eq!(eval_scroll_offset("+{2}", &delimiter, text, 20), 41);
+        assert_eq!(eval_scroll_offset("+{2}-5", &delimiter, text, 20), 36);
+        assert_eq!(eval_scroll_offset("+{2}-5+2", &delimiter, text, 20), 38);
+        assert_eq!(eval_scroll_offset("+{2}/2", &delimiter, text, 20), 31);
+        assert_eq!(eval_scroll_offset("+{3}-10", &delimiter, text, 20), 0);
+        assert_eq!(eval_scroll_offset("+10", &delimiter, text, 20), 9);
+
+        // non-numeric or missing fields, invalid expressions
+        assert_eq!(eval_scroll_offset("+{1}", &delimiter, text, 20), 0);
+        assert_eq!(eval_scroll_offset("+{5}", &delimiter, text, 20), 0);
+        assert_eq!(eval_scroll_offset("+{2}*2", &delimiter, text, 20), 0);
+        assert_eq!(eval_scroll_offset("+{2}/0", &delimiter, text, 20), 41);
+    }
+
+    #[test]
+    fn test_parse_min_query_length() {
+        assert_eq!(parse_min_query_length("3"), Ok((3, false)));
+        assert_eq!(parse_min_query_length("3:empty"), Ok((3, false)));
+        assert_eq!(parse_min_query_length("2:all"), Ok((2, true)));
+        assert!(parse_min_query_length("").is_err());
+        assert!(parse_min_query_length("-1").is_err());
+        assert!(parse_min_query_length("3:none").is_err());
+    }
+
+    #[test]
+    fn test_parse_max_items() {
+        assert_eq!(parse_max_items("100"), Ok((100, false)));
+        assert_eq!(parse_max_items("100:stop"), Ok((100, false)));
+        assert_eq!(parse_max_items("5:drop"), Ok((5, true)));
+        assert!(parse_max_items("0").is_err());
+        assert!(parse_max_items("-1").is_err());
+        assert!(parse_max_items("5:evict").is_err());
+    }
+
+    #[test]
+    fn test_command_env() {
+        let delimiter = Regex::new(r",").unwrap();
+        let context = InjectContext {
+            delimiter: &delimiter,
+            current_selection: "a,b,c",
+            selections: &[],
+            query: "query",
+            cmd_query: "cmd_query",
+        };
+
+        assert_eq!(
+            vec![
+                ("SKIM_QUERY", "query".to_string()),
+                ("SKIM_SELECTED", "a,b,c".to_string())
+            ],
+            command_env(context, None)
+        );
+        assert_eq!(
+            Some(&("SKIM_PREVIEW_LINES", "10".to_string())),
+            command_env(context, Some(10)).last()
+        );
     }
 }
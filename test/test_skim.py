@@ -69,7 +69,7 @@ class Alt(Key):
 
 class TmuxOutput(list):
     """A list that contains the output of tmux"""
-    RE = re.compile(r'(?:^|[^<]*). ([0-9]+)/([0-9]+)(?: \[([0-9]+)\])? *([0-9]+)(\.)?$')
+    RE = re.compile(r'(?:^|[^<]*). ([0-9]+)/([0-9]+)\+?(?: \[([0-9]+)\])? *([0-9]+)(\.)?$')
     def __init__(self, iteratable=[]):
         super(TmuxOutput, self).__init__(iteratable)
         self._counts = None
@@ -435,6 +435,14 @@ class TestSkim(TestBase):
             self.tmux.send_keys(Key('Enter'))
             self.assertEqual(f'> {expected}'.strip(), lines[-3])
 
+    def test_with_nth_reorder(self):
+        sk_command = self.sk("--delimiter ','", '--with-nth=3,1,1')
+        self.tmux.send_keys("echo -e 'field1,field2,field3' |" + sk_command, Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        lines = self.tmux.capture()
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('> field3,field1,field1,', lines[-3])
+
     def test_nth(self):
         # fields, query, match_count(0/1)
         tests = [
@@ -465,6 +473,98 @@ class TestSkim(TestBase):
         lines = self.readonce().strip()
         self.assertEqual(lines, '10\n10')
 
+    def test_initial_query(self):
+        # the first matcher pass already uses the query
+        self.tmux.send_keys(f"seq 100 | {self.sk('-q 10')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.matcher_stopped() and lines.match_count() == 2)
+        self.tmux.until(lambda lines: lines[-1] == '> 10')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('10', self.readonce().strip())
+
+        self.tmux.send_keys(self.sk("-i -c 'echo {}'", '--cmd-query hello'), Key('Enter'))
+        self.tmux.until(lambda lines: lines[-1] == 'c> hello' and lines[-3] == '> hello')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('hello', self.readonce().strip())
+
+    def test_adaptive_height(self):
+        self.tmux.send_keys(f"seq 3 | {self.sk('--height ~40%')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+        # the query, info and 3 item lines, right below the command
+        self.tmux.until(lambda lines: lines[-3:-6:-1] == ['> 1', '  2', '  3'] and 'seq 3' in lines[-6])
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_no_height(self):
+        # full screen, the command line is not shown; the selection goes to the redirected output
+        self.tmux.send_keys(f"seq 3 | {self.sk('--height 5', '--no-height')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3) and not any('seq 3' in line for line in lines))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_exit_term_on_empty(self):
+        # nothing to choose from, sk exits by itself without any key press
+        self.tmux.send_keys(f"printf '' | {self.sk('--exit-term-on-empty')}", Key('Enter'))
+        self.assertEqual('', self.readonce().strip())
+
+        self.tmux.send_keys(f"seq 3 | {self.sk('--exit-term-on-empty')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_cursor_on_exact(self):
+        args = ('--no-sort', '-q main', '--cursor-on-exact')
+        self.tmux.send_keys(f"echo -e 'my_app/init.rs\\nsrc/main.rs' | {self.sk(*args)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.match_count() == 2)
+        self.tmux.until(lambda lines: lines[-4] == '> src/main.rs')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('src/main.rs', self.readonce().strip())
+
+    def test_wrap(self):
+        # the rest of the long item is shown on an indented row below
+        self.tmux.send_keys(f"printf '%0300d\\nshort\\n' 0 | {self.sk('--wrap')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(2))
+        self.tmux.until(lambda lines: any(line.startswith('    000') for line in lines))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('0' * 300, self.readonce().strip())
+
+    def test_tabular(self):
+        self.tmux.send_keys(f"printf 'a,1\\nbbbb,2\\n' | {self.sk('--tabular', '-d ,')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(2))
+        self.tmux.until(lambda lines: lines[-3] == '> a,    1' and lines[-4] == '  bbbb, 2')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('a,1', self.readonce().strip())
+
+    def test_border(self):
+        self.tmux.send_keys(f"seq 3 | {self.sk('--border=sharp', '--height 7')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-1].startswith('└') and lines[-1].endswith('┘'))
+        self.tmux.until(lambda lines: lines[-7].startswith('┌') and lines[-2].startswith('│ >'))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_info_command(self):
+        info_command = "--info-command 'echo matched:$SKIM_MATCHED'"
+        self.tmux.send_keys(f"seq 10 | {self.sk(info_command)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].split()[0] == 'matched:10')
+        self.tmux.send_keys('1')
+        self.tmux.until(lambda lines: lines[-2].split()[0] == 'matched:2')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_accept_nth(self):
+        args = ('-m', '--with-nth 2..', '--accept-nth 1', '-q desc')
+        self.tmux.send_keys(f"echo -e '1 desc one\\n2 other\\n3 desc three' | {self.sk(*args)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.match_count() == 2)
+        self.tmux.send_keys(Key('BTab'), Key('BTab'), Key('Enter'))
+        self.assertEqual(['1', '3'], self.readonce().split())
+
+    def test_lines_env(self):
+        # the window is kept within $LINES, as in a popup smaller than the terminal
+        self.tmux.send_keys(f"seq 100 | LINES=5 {self.sk()}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(100))
+        self.tmux.until(lambda lines: lines[-3:-6:-1] == ['> 1', '  2', '  3'] and 'seq 100' in lines[-6])
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
     def test_print_cmd(self):
         self.tmux.send_keys(f"seq 1 1000 | {self.sk('--cmd-query 10', '--print-cmd')}", Key('Enter'))
         self.tmux.until(lambda lines: lines.ready_with_lines(1000))
@@ -481,6 +581,21 @@ class TestSkim(TestBase):
         lines = self.readonce().strip()
         self.assertEqual(lines, '10\ncmd\n10')
 
+    def test_print_query_cmd_and_expect(self):
+        args = ('-q 10', '--cmd-query cmd', '--print-query', '--print-cmd', '--expect ctrl-y')
+        self.tmux.send_keys(f"seq 1 1000 | {self.sk(*args)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1000))
+        self.tmux.send_keys(Ctrl('y'))
+
+        lines = self.readonce().strip()
+        self.assertEqual(lines, '10\ncmd\nctrl-y\n10')
+
+    def test_abort_print_query(self):
+        self.tmux.send_keys(f"seq 1 1000 | {self.sk('-q 10', '--bind esc:abort-print-query')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1000))
+        self.tmux.send_keys(Key('Escape'))
+        self.assertEqual('10', self.readonce().strip())
+
     def test_hscroll(self):
         # XXXXXXXXXXXXXXXXX..
         self.tmux.send_keys(f"cat <<EOF | {self.sk('-q b')}", Key('Enter'))
@@ -515,6 +630,181 @@ class TestSkim(TestBase):
         self.tmux.until(lambda lines: lines[-3].startswith('> a'))
         self.tmux.send_keys(Key('Enter'))
 
+    def test_skip_to_pattern(self):
+        skip_to_pattern = "--skip-to-pattern 'ERR[A-Z]+'"
+        self.tmux.send_keys(f"printf '%0300d ERROR %0300d\\n' 0 0 | {self.sk(skip_to_pattern)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.until(lambda lines: lines[-3].startswith('> ..ERROR 000'))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_ellipsis(self):
+        # XXXXXXXXXXXXXXXXX…
+        self.tmux.send_keys(f"cat <<EOF | {self.sk('-q b', '--ellipsis …')}", Key('Enter'))
+        self.tmux.send_keys(f"b{'a'*1000}", Key('Enter'))
+        self.tmux.send_keys(f"EOF", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.until(lambda lines: lines[-3].endswith('a…'))
+        self.tmux.send_keys(Key('Enter'))
+
+        # XXXXXXXMXXXXXXXXX, without ellipsis
+        no_ellipsis = "--ellipsis ''"
+        self.tmux.send_keys(f"cat <<EOF | {self.sk('-q b', no_ellipsis)}", Key('Enter'))
+        self.tmux.send_keys(f"{'a'*1000}b{'a'*1000}", Key('Enter'))
+        self.tmux.send_keys(f"EOF", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.until(lambda lines: lines[-3].startswith('> a') and lines[-3].endswith('a'))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_max_items(self):
+        """the reading stops at --max-items, or the oldest items are dropped with :drop"""
+        self.tmux.send_keys(f"seq 1000 | {self.sk('--max-items 100')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  100/100+') and lines[-3] == '> 1')
+        self.tmux.send_keys('1000')
+        self.tmux.until(lambda lines: lines[-2].startswith('  0/100+'))
+        self.tmux.send_keys(Ctrl('c'))
+
+        self.tmux.send_keys(f"seq 1000 | {self.sk('--max-items 100:drop')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  100/100+') and lines[-3] == '> 901')
+        self.tmux.send_keys(Key('Enter'))
+
+        # all the input fits
+        self.tmux.send_keys(f"seq 100 | {self.sk('--max-items 100')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  100/100 '))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_clear_query_and_selection(self):
+        sk_command = self.sk('--multi', '--bind', 'ctrl-x:clear-query,ctrl-y:clear-selection')
+        self.tmux.send_keys(f"echo -e 'a1\\na2\\nb1' | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  3/3'))
+        self.tmux.send_keys('a')
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/3'))
+        self.tmux.send_keys(Key('Tab'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/3 [1]'))
+
+        self.tmux.send_keys(Ctrl('x'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  3/3 [1]') and lines[-1] == '>')
+
+        self.tmux.send_keys(Ctrl('y'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  3/3 ') and '[' not in lines[-2])
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_jump(self):
+        sk_command = self.sk('--jump-labels', 'xyz', '--bind', 'ctrl-x:jump,ctrl-y:jump-accept')
+        self.tmux.send_keys(f"seq 3 | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  3/3'))
+
+        self.tmux.send_keys(Ctrl('x'))
+        self.tmux.until(lambda lines: lines[-3].startswith('x') and lines[-5].startswith('z'))
+        self.tmux.send_keys('y')
+        self.tmux.until(lambda lines: lines[-4].startswith('> 2'))
+
+        # unknown labels only leave the jump mode
+        self.tmux.send_keys(Ctrl('x'), 'a')
+        self.tmux.until(lambda lines: lines[-4].startswith('> 2') and lines[-1] == '>')
+
+        # other keys leave it without running their action
+        self.tmux.send_keys(Ctrl('x'))
+        self.tmux.until(lambda lines: lines[-3].startswith('x'))
+        self.tmux.send_keys(Key('Up'))
+        self.tmux.until(lambda lines: lines[-3].startswith('  1') and lines[-4].startswith('> 2'))
+
+        self.tmux.send_keys(Ctrl('y'), 'z')
+        self.assertEqual('3', self.readonce().strip())
+
+    def test_min_query_length(self):
+        self.tmux.send_keys(f"seq 100 | {self.sk('--min-query-length', '2')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  0/100'))
+        self.tmux.send_keys('1')
+        self.tmux.until(lambda lines: lines[-2].startswith('  0/100'))
+        self.tmux.send_keys('0')
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/100'))
+        self.tmux.send_keys(Key('BSpace'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  0/100'))
+        self.tmux.send_keys(Key('Enter'))
+
+        self.tmux.send_keys(f"seq 100 | {self.sk('--min-query-length', '2:all', '--print-query')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  100/100'))
+        self.tmux.send_keys('9')
+        self.tmux.until(lambda lines: lines[-2].startswith('  100/100') and lines[-3] == '> 1')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('9\n1', self.readonce().strip())
+
+    def test_disabled(self):
+        sk_command = self.sk('--disabled', '--bind', "'change:reload(seq {q})'", "-c 'seq 2'")
+        self.tmux.send_keys(sk_command, Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/2'))
+
+        # the query doesn't filter, the reloaded command does
+        self.tmux.send_keys('5')
+        self.tmux.until(lambda lines: lines[-2].startswith('  5/5'))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_disabled_query_does_not_filter(self):
+        self.tmux.send_keys(self.sk('--disabled', "-c 'seq 3'"), Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+
+        # nothing matches the query, all the items are kept in input order
+        self.tmux.send_keys('xyz')
+        self.tmux.until(lambda lines: lines[-1] == '> xyz' and lines[-2].startswith('  3/3'))
+        self.assertEqual(['> 1', '  2', '  3'], self.tmux.capture()[-3:-6:-1])
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+
+    def test_rotate_mode(self):
+        self.tmux.send_keys(f"echo -e 'a1b\\nab\\na.b' | {self.sk('-q', 'a.b')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  1/3 '))
+
+        # fuzzy -> exact
+        self.tmux.send_keys(Ctrl('r'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  1/3/EX'))
+
+        # exact -> regex
+        self.tmux.send_keys(Ctrl('r'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/3/RE'))
+
+        # regex -> fuzzy
+        self.tmux.send_keys(Ctrl('r'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  1/3 '))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_no_sort(self):
+        self.tmux.send_keys(f"echo -e 'a1b\\nxy\\nab' | {self.sk('--no-sort', '-q', 'ab')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/3'))
+        self.tmux.until(lambda lines: lines[-3:-5:-1] == ['> a1b', '  ab'])
+        self.tmux.send_keys(Key('Enter'))
+
+        self.tmux.send_keys(f"echo -e 'a1b\\nxy\\nab' | {self.sk('--no-sort', '--tac', '-q', 'ab')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-3:-5:-1] == ['> ab', '  a1b'])
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_max_matches(self):
+        self.tmux.send_keys(f"seq 1000 | {self.sk('--max-matches', '10', '--tac', '-q', '1')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  272/1000'))
+        self.tmux.send_keys(Key('PgUp'), Key('PgUp'))
+        self.tmux.until(lambda lines: lines[-2].split()[-1] == '9')
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_pre_select(self):
+        args = ('-m', '--pre-select-n', '2,0,42', '--pre-select-pat', "'^9'")
+        self.tmux.send_keys(f"seq 10 | {self.sk(*args)}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(10))
+        self.tmux.send_keys(Key('Up'), Key('Enter'))
+        self.assertEqual(['1', '2', '3', '9'], self.readonce().split())
+
+
+        self.tmux.send_keys(f"echo -e 'a1b\\nxy\\nab' | {self.sk('-q', 'ab')}", Key('Enter'))
+        # the sort state is shown only once it was toggled
+        self.tmux.until(lambda lines: lines[-3:-5:-1] == ['> ab', '  a1b'] and '+S' not in lines[-2])
+
+        # the cursor stays on the current item
+        self.tmux.send_keys(Ctrl('s'))
+        self.tmux.until(lambda lines: lines[-3:-5:-1] == ['  a1b', '> ab'] and '-S' in lines[-2])
+        self.tmux.send_keys(Ctrl('s'))
+        self.tmux.until(lambda lines: lines[-3:-5:-1] == ['> ab', '  a1b'] and '+S' in lines[-2])
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('ab', self.readonce().strip())
+
     def test_tabstop(self):
         self.tmux.send_keys(f"echo -e 'a\\tb' | {self.sk()}", Key('Enter'))
         self.tmux.until(lambda lines: lines.ready_with_lines(1))
@@ -541,6 +831,18 @@ class TestSkim(TestBase):
         self.tmux.until(lambda lines: lines[-3].startswith('> a   b'))
         self.tmux.send_keys(Key('Enter'))
 
+    def test_tabstop_preview(self):
+        sk_command = self.sk("--tabstop 4", "--preview 'printf \"x\\tPREVIEW\\n\"'", "--preview-window 'left:20'")
+        self.tmux.send_keys(f"echo -e 'a\\tb' | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include('x   PREVIEW'))
+        self.tmux.send_keys(Key('Enter'))
+
+        # the output keeps the tab
+        self.tmux.send_keys(f"echo -e 'a\\tb' | {self.sk('--tabstop 4')} | od -c", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.send_keys(Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include('a  \\t   b  \\n'))
+
     def test_inline_info(self):
         INLINE_INFO_SEP = " <"
         ## the dot  accounts for spinner
@@ -596,16 +898,41 @@ class TestSkim(TestBase):
         self.command_until(sk_options=['--reverse', '--header-lines', '1'],
                            until_predicate=lambda lines: lines[2].find("  a1") != -1)
 
+    def test_header_with_header_lines(self):
+        header = '"$(printf \'h1\\nh2\')"'
+        self.command_until(sk_options=['--header', header, '--header-lines', '1'],
+                           until_predicate=lambda lines: lines[-5:-2] == ['  a1', '  h1', '  h2'])
+
+        self.command_until(sk_options=['--header', header, '--header-lines', '1', '--header-position', 'above'],
+                           until_predicate=lambda lines: lines[-5:-2] == ['  h1', '  h2', '  a1'])
+
+        self.command_until(sk_options=['--reverse', '--header', header, '--header-lines', '1'],
+                           until_predicate=lambda lines: lines[2:5] == ['  h1', '  h2', '  a1'])
+
+    def test_header_lines_are_not_selectable(self):
+        sk_command = self.sk('--multi', '--header-lines', '1', '--bind', 'ctrl-a:select-all')
+        self.tmux.send_keys(f"echo -e 'a1\\na2\\na3' | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(2))
+        self.tmux.send_keys('a')
+        self.tmux.until(lambda lines: lines[-2].startswith('  2/2'))
+        self.tmux.send_keys(Ctrl('a'), Key('Enter'))
+        self.assertEqual('a2\na3', self.readonce().strip())
+
+        # fewer lines than --header-lines
+        self.tmux.send_keys(f"echo -e 'a1' | {self.sk('--header-lines', '2')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('  0/0'))
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('', self.readonce().strip())
+
+        self.tmux.send_keys(self.sk("-c 'echo a1; echo a2'", '--header-lines 1', '--filter a'), Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\ta2\n', output), repr(output))
+
     def test_reserved_options(self):
         options = [
             '--extended',
-            '--algo=TYPE',
-            '--literal',
-            '--no-mouse',
             '--cycle',
             '--hscroll-off=COL',
-            '--filepath-word',
-            '--jump-labels=CHARS',
             '--border',
             '--inline-info',
             '--header=STR',
@@ -614,7 +941,6 @@ class TestSkim(TestBase):
             '--history=FILE',
             '--history-size=10',
             '--sync',
-            '--no-sort',
             # --select-1
             '--select-1',
             '-1',
@@ -624,6 +950,13 @@ class TestSkim(TestBase):
         for opt in options:
             self.command_until(sk_options=[opt], until_predicate=find_prompt)
 
+    def test_algo(self):
+        for algo in ['v1', 'v2']:
+            self.tmux.send_keys(f"echo -e 'axxbxc\\nabc' | {self.sk('--algo', algo, '-q', 'abc')}", Key('Enter'))
+            self.tmux.until(lambda lines: lines[-2].strip().startswith('2/2'))
+            self.tmux.until(lambda lines: lines[-3].startswith('> abc'))
+            self.tmux.send_keys(Key('Enter'))
+
     def test_multiple_option_values_should_be_accepted(self):
         # normally we'll put some default options to SKIM_DEFAULT_OPTIONS and override it in command
         # line. this test will ensure multiple values are accepted.
@@ -651,9 +984,16 @@ class TestSkim(TestBase):
             '--exact -e',
             '--regex --regex',
             '--literal --literal',
+            '--mouse --mouse',
+            '--jump-labels abc --jump-labels xyz',
+            '--min-query-length 1 --min-query-length 2:all',
+            '--max-matches 10 --max-matches 0',
+            '--pre-select-n 1 --pre-select-n 2',
+            '--pre-select-pat a --pre-select-pat b',
             '--no-mouse --no-mouse',
             '--cycle --cycle',
             '--no-hscroll --no-hscroll',
+            '--ellipsis .. --ellipsis …',
             '--filepath-word --filepath-word',
             '--border --border',
             '--inline-info --inline-info',
@@ -705,6 +1045,168 @@ class TestSkim(TestBase):
         output = ":".join("{:02x}".format(ord(c)) for c in self.readonce())
         self.assertTrue(output.find("61:00:62:0a") >= 0)
 
+    def test_first_paint_before_any_item(self):
+        """the prompt should be drawn before the (slow) input yields the first item"""
+        self.tmux.send_keys(f"(sleep 5; echo a1) | {self.sk()}", Key('Enter'))
+        self.tmux.until(lambda lines: find_prompt(lines))
+        lines = self.tmux.capture()
+        self.assertTrue(re.search(r' 0/0', lines[-2]))
+        self.tmux.send_keys(Ctrl('c'))
+
+    def test_reload(self):
+        sk_command = self.sk("--bind 'ctrl-r:reload(echo {q}1; echo {q}2; echo {q}3)'")
+        self.tmux.send_keys(f"echo a | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.send_keys('x', Ctrl('r'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+        lines = self.tmux.capture()
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('x1', lines[-3][2:])
+        self.assertEqual(3, lines.match_count())
+
+    def test_reload_after_clear_query(self):
+        sk_command = self.sk("--bind 'ctrl-r:clear-query+reload(echo {q}1; echo {q}2; echo {q}3)'")
+        self.tmux.send_keys(f"echo a | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.send_keys('x', Ctrl('r'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3) and lines[-1] == '>')
+        lines = self.tmux.capture()
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', lines[-3][2:])
+
+    def test_change_reload(self):
+        sk_command = self.sk("--bind 'change:reload(echo {q}1; echo {q}2)'")
+        self.tmux.send_keys(f"echo a | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(1))
+        self.tmux.send_keys('xy')
+        self.tmux.until(lambda lines: lines.ready_with_lines(2) and lines.match_count() == 2)
+        lines = self.tmux.capture()
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('xy1', lines[-3][2:])
+
+    def test_start_reload(self):
+        sk_command = self.sk("--bind 'start:reload(seq 5 7)'")
+        self.tmux.send_keys(f"seq 3 | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3) and lines[-3] == '> 5')
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('5', self.readonce().strip())
+
+    def test_result_accepts_single_match(self):
+        sk_command = self.sk("--bind 'result:if-single-match(accept)'")
+        self.tmux.send_keys(f"printf 'ab\\nbc\\ncd\\n' | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+        self.tmux.send_keys('b')
+        self.tmux.until(lambda lines: lines.match_count() == 2)
+        self.tmux.send_keys('c')
+        self.assertEqual('bc', self.readonce().strip())
+
+    def test_result_after_reload(self):
+        log = f"{self.tempname()}-result"
+        if os.path.exists(log):
+            os.remove(log)
+        sk_command = self.sk(f"--bind 'result:execute-silent(echo {{q}} >> {log})'", "--bind 'ctrl-r:reload(seq 3)'")
+        self.tmux.send_keys(f"seq 3 | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.ready_with_lines(3))
+        wait(lambda: os.path.exists(log) and len(open(log).readlines()) == 1)
+
+        # the same query and as many items as before
+        self.tmux.send_keys(Ctrl('r'))
+        wait(lambda: len(open(log).readlines()) == 2)
+        self.tmux.send_keys(Key('Enter'))
+        self.assertEqual('1', self.readonce().strip())
+        os.remove(log)
+
+    def test_preview_follow(self):
+        sk_command = self.sk("--preview 'seq 1000; sleep 1; echo done'", "--preview-window 'down:5:follow'")
+        self.tmux.send_keys(f"echo a | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include("done"))
+        self.tmux.until(lambda lines: lines.any_include("/1001"))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_preview_scroll(self):
+        sk_command = self.sk("--delimiter :", "--preview 'seq 100'", "--preview-window 'down:5:+{2}-2'")
+        self.tmux.send_keys(f"echo -e 'a:50\\nb:x' | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include("48/100"))
+
+        # not a number, back to the top
+        self.tmux.send_keys(Key('Up'))
+        self.tmux.until(lambda lines: lines.any_include("1/100"))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_preview_env(self):
+        sk_command = self.sk("--preview 'echo \"[$SKIM_QUERY][$SKIM_SELECTED][$SKIM_PREVIEW_LINES]\"'", "--preview-window 'down:5'")
+        self.tmux.send_keys(f"echo \"a'b\" | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include("[][a'b]["))
+        self.tmux.send_keys("a")
+        self.tmux.until(lambda lines: lines.any_include("[a][a'b]["))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_cycle_preview(self):
+        sk_command = self.sk("--preview 'echo PREVIEW'", "--preview-window right", "--bind ctrl-o:cycle-preview")
+        self.tmux.send_keys(f"echo a | {sk_command}", Key('Enter'))
+        self.tmux.until(lambda lines: lines.any_include("PREVIEW"))
+        lines = self.tmux.capture()
+        self.assertFalse(any(line.startswith("PREVIEW") for line in lines))
+
+        # right -> down
+        self.tmux.send_keys(Ctrl('o'))
+        self.tmux.until(lambda lines: any(line.startswith("PREVIEW") for line in lines))
+        self.tmux.send_keys(Key('Enter'))
+
+    def test_spinner(self):
+        self.tmux.send_keys(f"(sleep 5; echo a1) | {self.sk('--spinner', 'X', '--refresh', '50')}", Key('Enter'))
+        self.tmux.until(lambda lines: lines[-2].startswith('X'))
+        self.tmux.send_keys(Ctrl('c'))
+
+    def test_filter_prints_only_matches(self):
+        """nothing but the matches should be written to stdout"""
+        self.tmux.send_keys(self.sk("-c 'echo a1; echo b2'", '--filter a'), Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\ta1\n', output), repr(output))
+
+    def test_filter_read0_print0(self):
+        """the input and output endings are chosen independently"""
+        source = "printf 'a1\\nx\\0b2\\0a3\\0'"
+        self.tmux.send_keys(f"{source} | {self.sk('--read0', '--print0', '--filter a')}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\ta1\nx\0-?[0-9]+\ta3\0', output), repr(output))
+
+        self.tmux.send_keys(f"{source} | {self.sk('--read0', '--filter a')}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\ta1\nx\n-?[0-9]+\ta3\n', output), repr(output))
+
+        self.tmux.send_keys(self.sk("-c 'echo a1; echo a2'", '--print0', '--filter a'), Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\ta1\0-?[0-9]+\ta2\0', output), repr(output))
+
+    def test_filter_nth(self):
+        """the filter mode matches the --nth fields and prints the --with-nth fields on demand"""
+        source = "echo -e '1,ab,xy\\n2,xy,ab'"
+        self.tmux.send_keys(f"{source} | {self.sk('--delimiter ,', '--nth 2', '--filter ab')}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\t1,ab,xy\n', output), repr(output))
+
+        # --nth applies to the transformed fields
+        args = ('--delimiter ,', '--with-nth 2..', '--nth 1', '--filter ab')
+        self.tmux.send_keys(f"{source} | {self.sk(*args)}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\t1,ab,xy\n', output), repr(output))
+
+        self.tmux.send_keys(f"{source} | {self.sk('--print-transformed', *args)}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\tab,xy\n', output), repr(output))
+
+    def test_literal(self):
+        """`!a` negates by default, but is taken as is with --literal"""
+        source, query = "echo -e 'x!a\\nb'", "'!a'"
+        self.tmux.send_keys(f"{source} | {self.sk('--filter', query)}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\tb\n', output), repr(output))
+
+        self.tmux.send_keys(f"{source} | {self.sk('--literal', '--filter', query)}", Key('Enter'))
+        output = self.readonce()
+        self.assertTrue(re.fullmatch(r'-?[0-9]+\tx!a\n', output), repr(output))
+
 def find_prompt(lines, interactive=False, reverse=False):
     linen = -1
     prompt = ">"